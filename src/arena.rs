@@ -0,0 +1,390 @@
+use core::alloc::Layout;
+use core::array;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::{self, NonNull};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::layout::{NodeHdr, NodeLinks, NodeTag};
+use crate::node_alloc::layout_for;
+use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult, Global, NodeAllocator, NodeRef, NULL_NODE};
+
+/// Distinct block layouts an arena serves, such as the leaf and branch layouts
+/// of two kinds of map.
+const CLASSES: usize = 4;
+
+/// Low bits of an id that number a block within its class; the rest pick the
+/// class.
+const INDEX_BITS: u32 = 30;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+
+/// Blocks in the first slab of a class; every further slab doubles.
+const FIRST_SLAB: u32 = 64;
+
+/// Slabs a class needs for every index below `INDEX_MASK`.
+const SLABS: usize = (INDEX_BITS - FIRST_SLAB.trailing_zeros() + 1) as usize;
+
+/// Slab and position in it of the block numbered `index`. Slab `s` holds
+/// `FIRST_SLAB << s` blocks, starting after those of the slabs before it.
+#[inline(always)]
+fn locate(index: u32) -> (usize, usize) {
+    let i = index + FIRST_SLAB;
+    let top = i.ilog2();
+    (
+        (top - FIRST_SLAB.trailing_zeros()) as usize,
+        (i - (1 << top)) as usize,
+    )
+}
+
+/// Blocks of one layout: the slabs holding them, how many have been handed
+/// out, and the freed ones, chained through the first word of their headers.
+struct SlabClass {
+    layout: Cell<Option<Layout>>,
+    /// Distance between blocks: the layout size rounded up to its alignment.
+    stride: Cell<usize>,
+    /// Blocks handed out from the slabs so far, freed or not.
+    used: Cell<u32>,
+    /// Index of the first freed block, or `NULL_NODE`.
+    free: Cell<u32>,
+    live: Cell<usize>,
+    slabs: [Cell<*mut u8>; SLABS],
+}
+
+/// The slab table of an arena. It stays at one address for the arena's whole
+/// life, so that node layouts can look ids up in it.
+pub(crate) struct SlabDir {
+    classes: [SlabClass; CLASSES],
+}
+
+impl SlabDir {
+    fn new() -> Self {
+        Self {
+            classes: array::from_fn(|_| SlabClass {
+                layout: Cell::new(None),
+                stride: Cell::new(0),
+                used: Cell::new(0),
+                free: Cell::new(NULL_NODE),
+                live: Cell::new(0),
+                slabs: array::from_fn(|_| Cell::new(ptr::null_mut())),
+            }),
+        }
+    }
+
+    /// The block numbered `id`, or null for `NULL_NODE`. The id must have been
+    /// handed out by this arena.
+    #[inline(always)]
+    pub(crate) unsafe fn node_at(&self, id: u32) -> *mut u8 {
+        if id == NULL_NODE {
+            return ptr::null_mut();
+        }
+        let class = self.classes.get_unchecked((id >> INDEX_BITS) as usize);
+        let (slab, at) = locate(id & INDEX_MASK);
+        class
+            .slabs
+            .get_unchecked(slab)
+            .get()
+            .add(at * class.stride.get())
+    }
+
+    /// Like [`Self::node_at`], but `None` unless `id` is a block of `layout`
+    /// the arena has handed out and not freed.
+    pub(crate) fn lookup(&self, id: u32, layout: Layout) -> Option<NonNull<u8>> {
+        let class = self.classes.get((id >> INDEX_BITS) as usize)?;
+        if class.layout.get() != Some(layout) || id & INDEX_MASK >= class.used.get() {
+            return None;
+        }
+        let block = unsafe { self.node_at(id) };
+        // Freeing a block clears the id in its header.
+        let live = unsafe { ptr::addr_of!((*(block as *const NodeHdr)).id).read() } == id;
+        NonNull::new(block).filter(|_| live)
+    }
+
+    /// The class serving `layout`, claiming a free one if none does yet.
+    fn class_for(&self, layout: Layout) -> Option<usize> {
+        // Every block keeps its id in a node header.
+        if layout.size() < size_of::<NodeHdr>() || layout.align() < align_of::<NodeHdr>() {
+            return None;
+        }
+        let mut unclaimed = None;
+        for (c, class) in self.classes.iter().enumerate() {
+            match class.layout.get() {
+                Some(claimed) if claimed == layout => return Some(c),
+                Some(_) => {}
+                None => {
+                    unclaimed.get_or_insert(c);
+                }
+            }
+        }
+        let c = unclaimed?;
+        self.classes[c].layout.set(Some(layout));
+        self.classes[c].stride.set(layout.pad_to_align().size());
+        Some(c)
+    }
+}
+
+/// A [`NodeAllocator`] that carves nodes out of large slabs and numbers them, so
+/// that the maps using it link nodes by 32-bit ids rather than by address.
+///
+/// A link takes four bytes instead of eight, so branches fit more children in
+/// the same byte budget, and no node holds the address of another.
+/// [`BPlusTreeMap::root_ref`] and its neighbours walk a map by these ids.
+///
+/// Slabs grow geometrically, the first holding 64 blocks, and stay allocated
+/// until the arena is dropped; freed blocks are reused first. The arena serves
+/// up to four distinct block layouts (a map uses one or two), with up to 2^30 - 1
+/// blocks of each; allocations beyond either limit fail.
+///
+/// Maps can own an arena or share one by reference. Cloning, `split_off` and
+/// moving nodes in `append` need it shared, as `&SlabArena`.
+///
+/// ```
+/// use bplustree::{BPlusTreeMap, SlabArena};
+///
+/// let arena = SlabArena::new();
+/// let mut map = BPlusTreeMap::with_budgets_in(256, 256, &arena);
+/// map.extend((0..1000).map(|i| (i, i)));
+/// let right = map.split_off(&500);
+/// assert_eq!((map.len(), right.len()), (500, 500));
+/// assert!(map.branch_layout().cap > BPlusTreeMap::<i32, i32>::with_budgets(256, 256).branch_layout().cap);
+/// ```
+pub struct SlabArena<A: NodeAllocator = Global> {
+    dir: NonNull<SlabDir>,
+    alloc: A,
+}
+
+// SAFETY: the arena owns its slabs and table. It is not `Sync`: its counters and
+// free lists are updated through `&self`.
+unsafe impl<A: NodeAllocator + Send> Send for SlabArena<A> {}
+
+#[cfg(feature = "alloc")]
+impl SlabArena {
+    /// An empty arena whose slabs come from the global allocator.
+    pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for SlabArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: NodeAllocator> SlabArena<A> {
+    /// An empty arena whose slabs come from `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        let dir = alloc
+            .allocate(Layout::new::<SlabDir>())
+            .expect("alloc slab table")
+            .cast::<SlabDir>();
+        unsafe { dir.as_ptr().write(SlabDir::new()) };
+        Self { dir, alloc }
+    }
+
+    fn dir(&self) -> &SlabDir {
+        unsafe { self.dir.as_ref() }
+    }
+
+    /// Nodes handed out and not yet freed, across every map using the arena.
+    pub fn live_nodes(&self) -> usize {
+        self.dir().classes.iter().map(|c| c.live.get()).sum()
+    }
+
+    /// Bytes of slab memory the arena holds.
+    pub fn slab_bytes(&self) -> usize {
+        self.dir()
+            .classes
+            .iter()
+            .map(|c| {
+                let slabs = c.slabs.iter().take_while(|s| !s.get().is_null()).count();
+                c.stride.get()
+                    * ((FIRST_SLAB as usize) << slabs).saturating_sub(FIRST_SLAB as usize)
+            })
+            .sum()
+    }
+
+    /// Number the next block of class `c`, allocating the slab it lies in if
+    /// need be.
+    fn next_index(&self, c: usize) -> Option<u32> {
+        let class = &self.dir().classes[c];
+        let free = class.free.get();
+        if free != NULL_NODE {
+            let block = unsafe { self.dir().node_at(((c as u32) << INDEX_BITS) | free) };
+            class.free.set(unsafe { (block as *const u32).read() });
+            return Some(free);
+        }
+        let index = class.used.get();
+        if index >= INDEX_MASK {
+            return None;
+        }
+        let (slab, _) = locate(index);
+        if class.slabs[slab].get().is_null() {
+            let layout = class.layout.get()?;
+            let bytes = class
+                .stride
+                .get()
+                .checked_mul((FIRST_SLAB as usize) << slab)?;
+            let slab_layout = Layout::from_size_align(bytes, layout.align()).ok()?;
+            class.slabs[slab].set(self.alloc.allocate(slab_layout)?.as_ptr());
+        }
+        class.used.set(index + 1);
+        Some(index)
+    }
+}
+
+unsafe impl<A: NodeAllocator> NodeAllocator for SlabArena<A> {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let c = self.dir().class_for(layout)?;
+        let index = self.next_index(c)?;
+        let id = ((c as u32) << INDEX_BITS) | index;
+        let class = &self.dir().classes[c];
+        class.live.set(class.live.get() + 1);
+        unsafe {
+            let block = self.dir().node_at(id);
+            let hdr = block as *mut NodeHdr;
+            ptr::addr_of_mut!((*hdr).flags).write(NodeHdr::VACANT);
+            ptr::addr_of_mut!((*hdr).id).write(id);
+            NonNull::new(block)
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let hdr = ptr.as_ptr() as *mut NodeHdr;
+        let id = ptr::addr_of!((*hdr).id).read();
+        let class = &self.dir().classes[(id >> INDEX_BITS) as usize];
+        debug_assert_eq!(class.layout.get(), Some(layout));
+        debug_assert_eq!(self.dir().node_at(id), ptr.as_ptr());
+        (ptr.as_ptr() as *mut u32).write(class.free.get());
+        ptr::addr_of_mut!((*hdr).id).write(NULL_NODE);
+        class.free.set(id & INDEX_MASK);
+        class.live.set(class.live.get() - 1);
+    }
+
+    fn node_links(&self) -> NodeLinks {
+        NodeLinks::ids(self.dir)
+    }
+}
+
+impl<A: NodeAllocator> Drop for SlabArena<A> {
+    fn drop(&mut self) {
+        unsafe {
+            for class in &self.dir().classes {
+                let Some(layout) = class.layout.get() else {
+                    continue;
+                };
+                for (s, slab) in class.slabs.iter().enumerate() {
+                    let Some(base) = NonNull::new(slab.get()) else {
+                        break;
+                    };
+                    let bytes = class.stride.get() * ((FIRST_SLAB as usize) << s);
+                    let slab_layout = Layout::from_size_align_unchecked(bytes, layout.align());
+                    self.alloc.deallocate(base, slab_layout);
+                }
+            }
+            ptr::drop_in_place(self.dir.as_ptr());
+            self.alloc
+                .deallocate(self.dir.cast(), Layout::new::<SlabDir>());
+        }
+    }
+}
+
+impl<A: NodeAllocator> fmt::Debug for SlabArena<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlabArena")
+            .field("live_nodes", &self.live_nodes())
+            .field("slab_bytes", &self.slab_bytes())
+            .finish()
+    }
+}
+
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// The root node as a [`NodeRef`] carrying its arena id, or `None` while the
+    /// map has no root. Returns `ArenaError` unless the map's nodes come from a
+    /// [`SlabArena`].
+    pub fn root_ref(&self) -> BTreeResult<Option<NodeRef<K, V>>> {
+        self.arena_slabs()?;
+        Ok(self.root.map(|root| unsafe { node_ref(root) }))
+    }
+
+    /// The children of the branch `node`, left to right.
+    ///
+    /// Returns `NodeError` if `node` is not a branch in use with this map's layout,
+    /// and `ArenaError` as [`Self::root_ref`] does. The check takes O(1): it cannot
+    /// tell this map's branches from those of a map sharing the arena and layouts,
+    /// whose children are then returned.
+    #[cfg(feature = "alloc")]
+    pub fn child_refs(&self, node: NodeRef<K, V>) -> BTreeResult<Vec<NodeRef<K, V>>> {
+        let branch = self.node_for(node, false)?;
+        unsafe {
+            let b = crate::layout::carve_branch::<K>(branch, &self.branch_layout);
+            Ok((0..=(*b.hdr).len as usize)
+                .map(|i| node_ref(NonNull::new_unchecked(b.children.get(i))))
+                .collect())
+        }
+    }
+
+    /// The leaf after `leaf` in key order, or `None` for the last one.
+    ///
+    /// Returns `NodeError` if `leaf` is not a leaf in use with this map's layout,
+    /// checked as in [`Self::child_refs`], and `ArenaError` as [`Self::root_ref`]
+    /// does.
+    pub fn next_leaf_ref(&self, leaf: NodeRef<K, V>) -> BTreeResult<Option<NodeRef<K, V>>> {
+        let node = self.node_for(leaf, true)?;
+        unsafe { Ok(NonNull::new(self.leaf_layout.next_of(node.as_ptr())).map(|n| node_ref(n))) }
+    }
+
+    fn arena_slabs(&self) -> BTreeResult<&SlabDir> {
+        match self.leaf_layout.links.slabs() {
+            Some(slabs) => Ok(unsafe { slabs.as_ref() }),
+            None => Err(BPlusTreeError::arena_error(
+                "node lookup",
+                "the map links its nodes by pointer",
+            )),
+        }
+    }
+
+    /// The node `node` refers to, once the slab table shows it is a live block of
+    /// this map's leaf layout, or with `leaf` false its branch layout, whose header
+    /// holds a node of that kind.
+    fn node_for(&self, node: NodeRef<K, V>, leaf: bool) -> BTreeResult<NonNull<u8>> {
+        let kind = if node.is_leaf() { "leaf" } else { "branch" };
+        if node.is_leaf() != leaf {
+            let why = if leaf {
+                "not a leaf"
+            } else {
+                "has no children"
+            };
+            return Err(BPlusTreeError::node_error(kind, node.id(), why));
+        }
+        let block_layout = if leaf {
+            layout_for(self.leaf_layout.bytes, self.leaf_layout.max_align)
+        } else {
+            layout_for(self.branch_layout.bytes, self.branch_layout.max_align)
+        };
+        let tag = if leaf { NodeTag::Leaf } else { NodeTag::Branch };
+        let found = self
+            .arena_slabs()?
+            .lookup(node.id(), block_layout)
+            .filter(|block| unsafe {
+                // A vacant block's tag may be uninitialized, so check it first.
+                let hdr = block.as_ptr() as *const NodeHdr;
+                ptr::addr_of!((*hdr).flags).read() == 0
+                    && (ptr::addr_of!((*hdr).tag) as *const u8).read() == tag as u8
+            });
+        found.ok_or_else(|| BPlusTreeError::node_error(kind, node.id(), "not in this map"))
+    }
+}
+
+/// A reference to `node` by its arena id.
+unsafe fn node_ref<K, V>(node: NonNull<u8>) -> NodeRef<K, V> {
+    let hdr = &*(node.as_ptr() as *const NodeHdr);
+    match hdr.tag {
+        NodeTag::Leaf => NodeRef::Leaf(hdr.id, PhantomData),
+        NodeTag::Branch => NodeRef::Branch(hdr.id, PhantomData),
+    }
+}
@@ -0,0 +1,431 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use crate::insert::InsertResult;
+use crate::layout;
+use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult, NodeAllocator, NodeHdr, NodeTag};
+
+/// A node to hang under a branch level, after the leaf holding the first key of
+/// its subtree (its separator link).
+type NewChild = (NonNull<u8>, NonNull<u8>);
+
+/// A pair to insert, tagged with its position in the batch.
+type Tagged<K, V> = (K, V, usize);
+
+/// Buffers a batch insert reuses from run to run.
+struct Scratch<K, V> {
+    path: Vec<(NonNull<u8>, usize)>,
+    run: Vec<Tagged<K, V>>,
+    /// Pairs of the run not yet in the leaf, with the leaf position they go before.
+    fresh: Vec<Tagged<K, V>>,
+    leaves: Vec<NonNull<u8>>,
+    children: Vec<NewChild>,
+    split_off: Vec<NewChild>,
+}
+
+/// What a batch allocates: new nodes, and the largest size each scratch buffer
+/// reaches.
+#[derive(Default)]
+struct BatchNeeds {
+    leaves: usize,
+    branches: usize,
+    depth: usize,
+    run: usize,
+    fresh: usize,
+    pieces: usize,
+}
+
+impl<K, V> Scratch<K, V> {
+    fn new() -> Self {
+        Self {
+            path: Vec::new(),
+            run: Vec::new(),
+            fresh: Vec::new(),
+            leaves: Vec::new(),
+            children: Vec::new(),
+            split_off: Vec::new(),
+        }
+    }
+
+    /// Make room for everything the batch will hold, so that it allocates nothing
+    /// once it starts.
+    fn try_reserve(&mut self, needs: &BatchNeeds) -> BTreeResult<()> {
+        let new_children = needs.pieces.saturating_sub(1);
+        self.path
+            .try_reserve(needs.depth)
+            .and_then(|()| self.run.try_reserve(needs.run))
+            .and_then(|()| self.fresh.try_reserve(needs.fresh))
+            .and_then(|()| self.leaves.try_reserve(needs.pieces))
+            .and_then(|()| self.children.try_reserve(new_children))
+            .and_then(|()| self.split_off.try_reserve(new_children))
+            .map_err(|_| BPlusTreeError::allocation_error("batch buffers", "out of memory"))
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Insert every pair, returning for each what [`Self::insert`] would have
+    /// returned had the pairs been inserted one by one, in input order.
+    ///
+    /// The pairs are sorted (ties by input position, so repeated keys keep their
+    /// order) and each run of keys that lands in the same leaf is merged into it
+    /// after a single descent. A leaf that overflows is split once, into as many
+    /// leaves as the run needs, and the new leaves are handed to its parent together.
+    pub fn batch_insert(&mut self, items: Vec<(K, V)>) -> BTreeResult<Vec<Option<V>>> {
+        let (tagged, mut olds) = Self::tag_and_sort(items)?;
+        self.insert_runs(tagged, &mut olds, &mut Scratch::new());
+        Ok(olds)
+    }
+
+    /// Like [`Self::batch_insert`], but first walks the runs without changing
+    /// anything, to allocate every node and buffer the batch can need. If that
+    /// fails, returns `AllocationError` and leaves the tree as it was; the pairs
+    /// are dropped.
+    pub fn try_batch_insert(&mut self, items: Vec<(K, V)>) -> BTreeResult<Vec<Option<V>>> {
+        let (tagged, mut olds) = Self::tag_and_sort(items)?;
+        let mut scratch = Scratch::new();
+        let needs = unsafe { self.batch_needs(&tagged, &mut scratch.path)? };
+        scratch.try_reserve(&needs)?;
+        self.reserve_nodes(needs.leaves, needs.branches)?;
+        self.insert_runs(tagged, &mut olds, &mut scratch);
+        unsafe { self.trim_pool() };
+        Ok(olds)
+    }
+
+    /// Tag each pair with its input position and sort by key, along with a slot per
+    /// pair for the value it replaces.
+    #[allow(clippy::type_complexity)]
+    fn tag_and_sort(items: Vec<(K, V)>) -> BTreeResult<(Vec<Tagged<K, V>>, Vec<Option<V>>)> {
+        let oom = |_| BPlusTreeError::allocation_error("batch buffers", "out of memory");
+        let mut olds = Vec::new();
+        olds.try_reserve_exact(items.len()).map_err(oom)?;
+        olds.resize_with(items.len(), || None);
+        let mut tagged = Vec::new();
+        tagged.try_reserve_exact(items.len()).map_err(oom)?;
+        tagged.extend(items.into_iter().enumerate().map(|(i, (k, v))| (k, v, i)));
+        // Breaking ties by position keeps repeated keys in order without the buffer
+        // a stable sort would allocate.
+        tagged.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(a.2.cmp(&b.2)));
+        Ok((tagged, olds))
+    }
+
+    fn insert_runs(
+        &mut self,
+        tagged: Vec<Tagged<K, V>>,
+        olds: &mut [Option<V>],
+        scratch: &mut Scratch<K, V>,
+    ) {
+        let mut items = tagged.into_iter().peekable();
+        while let Some(first) = items.next() {
+            unsafe {
+                let (leaf, upper) = self.descend_for_run(&first.0, &mut scratch.path);
+                scratch.run.push(first);
+                while let Some(pair) =
+                    items.next_if(|next| upper.is_none_or(|upper| next.0 < *upper))
+                {
+                    scratch.run.push(pair);
+                }
+                self.merge_run(leaf, scratch, olds);
+            }
+        }
+    }
+
+    /// Descend to the leaf for `key`, recording the path, and return the leaf with
+    /// the separator bounding it from above (if any).
+    unsafe fn descend_for_run(
+        &mut self,
+        key: &K,
+        path: &mut Vec<(NonNull<u8>, usize)>,
+    ) -> (NonNull<u8>, Option<*const K>) {
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                let leaf = self.new_leaf();
+                self.root = Some(leaf);
+                leaf
+            }
+        };
+        self.descend_from(root, key, path)
+    }
+
+    unsafe fn descend_from(
+        &self,
+        root: NonNull<u8>,
+        key: &K,
+        path: &mut Vec<(NonNull<u8>, usize)>,
+    ) -> (NonNull<u8>, Option<*const K>) {
+        path.clear();
+        let mut cur = root;
+        let mut upper = None;
+        while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+            let parts = layout::carve_branch::<K>(cur, &self.branch_layout);
+            let (child, idx) = self.child_for_key(cur, key).expect("child must exist");
+            if idx < (*parts.hdr).len as usize {
+                upper = Some(self.sep_key(parts.seps.get(idx)) as *const K);
+            }
+            path.push((cur, idx));
+            cur = child;
+        }
+        (cur, upper)
+    }
+
+    /// Walk the runs of the sorted `pairs` without changing anything, and work out
+    /// what inserting them allocates.
+    ///
+    /// New leaves are exact: each run spreads over as many leaves as its merge
+    /// needs. New branches are bounded: a branch taking `k` new children splits at
+    /// most [`Self::split_bound`] times, and each split is a new child of its parent.
+    unsafe fn batch_needs(
+        &self,
+        pairs: &[Tagged<K, V>],
+        path: &mut Vec<(NonNull<u8>, usize)>,
+    ) -> BTreeResult<BatchNeeds> {
+        let oom = |_| BPlusTreeError::allocation_error("batch buffers", "out of memory");
+        let cap = self.leaf_layout.cap as usize;
+        let mut needs = BatchNeeds::default();
+        if pairs.is_empty() {
+            return Ok(needs);
+        }
+        let Some(root) = self.root else {
+            let fresh = Self::fresh_in(&[], pairs);
+            needs.pieces = fresh.div_ceil(cap);
+            (needs.leaves, needs.run, needs.fresh) = (needs.pieces, pairs.len(), fresh);
+            needs.branches = self.root_growth(needs.pieces - 1);
+            needs.depth = needs.branches;
+            return Ok(needs);
+        };
+
+        let mut depth = 0;
+        let mut cur = root;
+        while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+            let b = layout::carve_branch::<K>(cur, &self.branch_layout);
+            cur = NonNull::new_unchecked(b.children.get(0));
+            depth += 1;
+        }
+        path.try_reserve(depth).map_err(oom)?;
+        // Per depth, the branches taking new children in key order, each with the
+        // number it takes and the index of its parent one level up.
+        let mut levels: Vec<Vec<(NonNull<u8>, usize, usize)>> = Vec::new();
+        levels.try_reserve_exact(depth).map_err(oom)?;
+        levels.resize_with(depth, Vec::new);
+        let mut above_root = 0;
+        let mut rest = pairs;
+        while let Some(first) = rest.first() {
+            let (leaf, upper) = self.descend_from(root, &first.0, path);
+            let end = rest.partition_point(|p| upper.is_none_or(|upper| p.0 < *upper));
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
+            let fresh = Self::fresh_in(keys, &rest[..end]);
+            let pieces = (len + fresh).div_ceil(cap);
+            needs.run = needs.run.max(end);
+            needs.fresh = needs.fresh.max(fresh);
+            needs.pieces = needs.pieces.max(pieces);
+            rest = &rest[end..];
+            let extra = pieces.saturating_sub(1);
+            if extra == 0 {
+                continue;
+            }
+            needs.leaves += extra;
+            if depth == 0 {
+                above_root += extra;
+                continue;
+            }
+            for (d, &(node, _)) in path.iter().enumerate() {
+                if levels[d].last().is_none_or(|last| last.0 != node) {
+                    let parent = d.checked_sub(1).map_or(0, |up| levels[up].len() - 1);
+                    levels[d].try_reserve(1).map_err(oom)?;
+                    levels[d].push((node, 0, parent));
+                }
+            }
+            levels[depth - 1].last_mut().expect("just pushed").1 += extra;
+        }
+
+        while let Some(level) = levels.pop() {
+            for (node, added, parent) in level {
+                let len = (*(node.as_ptr() as *const NodeHdr)).len as usize;
+                let splits = self.split_bound(len, added);
+                needs.branches += splits;
+                match levels.last_mut() {
+                    Some(up) => up[parent].1 += splits,
+                    None => above_root += splits,
+                }
+            }
+        }
+        let grown = self.root_growth(above_root);
+        needs.branches += grown;
+        // Every new root adds a level, so later descents can run deeper.
+        needs.depth = depth + grown;
+        Ok(needs)
+    }
+
+    /// Branches allocated when `extra` new siblings of the root are hung under new
+    /// roots: one new root per round, plus whatever that root splits into.
+    fn root_growth(&self, mut extra: usize) -> usize {
+        let mut branches = 0;
+        while extra > 0 {
+            extra = self.split_bound(1, extra - 1);
+            branches += 1 + extra;
+        }
+        branches
+    }
+
+    /// Most splits a branch with `len` keys can go through while taking `added`
+    /// more. Every node a split leaves behind holds at least half a full node's
+    /// keys, and each split moves one key up, which caps the node count.
+    fn split_bound(&self, len: usize, added: usize) -> usize {
+        let cap = self.branch_layout.cap as usize;
+        let half = cap / 2;
+        if len + added <= cap {
+            0
+        } else {
+            (len + added - half) / (half + 1)
+        }
+    }
+
+    /// Number of distinct keys in the sorted `run` that are not in `keys`.
+    fn fresh_in(keys: &[K], run: &[Tagged<K, V>]) -> usize {
+        let mut fresh = 0;
+        for (i, (key, _, _)) in run.iter().enumerate() {
+            let repeat = i > 0 && run[i - 1].0 == *key;
+            if !repeat && keys.binary_search(key).is_err() {
+                fresh += 1;
+            }
+        }
+        fresh
+    }
+
+    /// Merge the sorted run in `scratch` into `leaf`, whose path is in `scratch`.
+    ///
+    /// Every comparison happens before any pair moves: repeated keys and keys
+    /// already in the leaf only replace values, and the rest are placed by a
+    /// backward merge that spreads over `leaf` and as many new leaves as needed.
+    unsafe fn merge_run(
+        &mut self,
+        leaf: NonNull<u8>,
+        scratch: &mut Scratch<K, V>,
+        olds: &mut [Option<V>],
+    ) {
+        let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+        let len = (*parts.hdr).len as usize;
+        let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
+        let fresh = &mut scratch.fresh;
+        let mut pairs = scratch.run.drain(..).peekable();
+        while let Some((key, mut value, idx)) = pairs.next() {
+            while let Some((_, later, later_idx)) = pairs.next_if(|next| next.0 == key) {
+                olds[later_idx] = Some(mem::replace(&mut value, later));
+            }
+            match self.binary_search_keys(keys, &key) {
+                Ok(pos) => {
+                    let slot = &mut *(parts.vals_ptr.add(pos) as *mut V);
+                    olds[idx] = Some(mem::replace(slot, value));
+                }
+                // Keep the leaf position the pair goes in front of.
+                Err(pos) => fresh.push((key, value, pos)),
+            }
+        }
+        drop(pairs);
+        if fresh.is_empty() {
+            return;
+        }
+
+        let added = fresh.len();
+        for &(node, idx) in &scratch.path {
+            if let Some(counts) = self.counts_of(node) {
+                *counts.add(idx) += added;
+            }
+        }
+        self.len += added;
+
+        let cap = self.leaf_layout.cap as usize;
+        let total = len + added;
+        let pieces = total.div_ceil(cap);
+        let leaves = &mut scratch.leaves;
+        leaves.clear();
+        leaves.push(leaf);
+        let old_next = parts.next.get();
+        for _ in 1..pieces {
+            let next = self.new_leaf();
+            self.link_leaves(*leaves.last().unwrap(), next);
+            leaves.push(next);
+        }
+        if let (Some(next), true) = (NonNull::new(old_next), pieces > 1) {
+            self.link_leaves(*leaves.last().unwrap(), next);
+        }
+
+        // Fill the pieces back to front, so no slot of `leaf` is overwritten
+        // before it has been read.
+        let (base, extra) = (total / pieces, total % pieces);
+        let mut remaining = len;
+        for (p, &piece) in leaves.iter().enumerate().rev() {
+            let size = base + usize::from(p < extra);
+            let dst = layout::carve_leaf::<K, V>(piece, &self.leaf_layout);
+            for slot in (0..size).rev() {
+                if fresh.last().is_some_and(|f| f.2 >= remaining) {
+                    let (key, value, _) = fresh.pop().unwrap();
+                    self.write_kv_at(
+                        dst.keys_ptr as *mut K,
+                        dst.vals_ptr as *mut V,
+                        slot,
+                        key,
+                        value,
+                    );
+                } else {
+                    remaining -= 1;
+                    ptr::copy(parts.keys_ptr.add(remaining), dst.keys_ptr.add(slot), 1);
+                    ptr::copy(parts.vals_ptr.add(remaining), dst.vals_ptr.add(slot), 1);
+                }
+            }
+            (*dst.hdr).len = size as u16;
+        }
+
+        for &piece in &leaves[1..] {
+            scratch.children.push((piece, piece));
+        }
+        self.insert_children_along(scratch);
+    }
+
+    /// Hang the new children in `scratch` (in key order) under the bottom node of
+    /// its path, carrying any branch splits up the path and growing the root as
+    /// often as needed.
+    unsafe fn insert_children_along(&mut self, scratch: &mut Scratch<K, V>) {
+        let mut level = scratch.path.len();
+        while !scratch.children.is_empty() {
+            let node = match level.checked_sub(1) {
+                Some(up) => {
+                    level = up;
+                    scratch.path[up].0
+                }
+                None => {
+                    let (sep, right) = scratch.children.remove(0);
+                    self.grow_root(sep, right);
+                    self.root.expect("grown root")
+                }
+            };
+            self.insert_children_into(node, scratch);
+            mem::swap(&mut scratch.children, &mut scratch.split_off);
+        }
+    }
+
+    /// Insert the children in `scratch` into `node` and whatever it splits into,
+    /// leaving the split-off siblings for the level above in `scratch.split_off`,
+    /// in key order.
+    unsafe fn insert_children_into(&mut self, node: NonNull<u8>, scratch: &mut Scratch<K, V>) {
+        let split_off = &mut scratch.split_off;
+        for (sep, child) in scratch.children.drain(..) {
+            let sep_key = self.sep_key(sep.as_ptr());
+            // The last sibling whose separator does not exceed `sep` covers it.
+            let k = split_off.partition_point(|(s, _)| self.sep_key(s.as_ptr()) <= sep_key);
+            let target = if k == 0 { node } else { split_off[k - 1].1 };
+            let (_, idx) = self
+                .child_for_key(target, sep_key)
+                .expect("child must exist");
+            if let InsertResult::Split {
+                sep_leaf, right, ..
+            } = self.insert_into_branch(target, idx, sep, child, None)
+            {
+                split_off.insert(k, (sep_leaf, right));
+            }
+        }
+    }
+}
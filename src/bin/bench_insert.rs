@@ -211,13 +211,13 @@ where
     }
 
     // Mixed operations on the inserted data
-    for i in 0..get_ops.min(lookups.len()) {
-        black_box(map.get(&lookups[i]));
+    for k in lookups.iter().take(get_ops) {
+        black_box(map.get(k));
     }
 
     // Delete some elements
-    for i in 0..delete_ops.min(lookups.len()) {
-        black_box(map.remove(&lookups[i]));
+    for k in lookups.iter().take(delete_ops) {
+        black_box(map.remove(k));
     }
 
     // Insert remaining elements
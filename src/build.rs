@@ -0,0 +1,216 @@
+use alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+
+use crate::layout;
+use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult, NodeAllocator};
+
+impl<K: Ord, V> BPlusTreeMap<K, V> {
+    /// Build a map with the default budgets from pairs in strictly increasing key
+    /// order, bottom-up and without a single descent.
+    ///
+    /// Leaves are packed to `fill_factor` of their capacity (in `(0, 1]`, clamped up
+    /// to the minimum occupancy); branches are packed full. Input that is not
+    /// strictly increasing is rejected with [`BPlusTreeError::InvalidState`] and
+    /// everything read so far is dropped.
+    pub fn from_sorted_iter<I>(iter: I, fill_factor: f64) -> BTreeResult<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = Self::with_default_budgets();
+        map.bulk_load(iter, fill_factor)?;
+        Ok(map)
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Like [`Self::from_sorted_iter`], but into this map, which must be empty, so
+    /// any node budgets (or counted branches) can be bulk loaded.
+    pub fn bulk_load<I>(&mut self, iter: I, fill_factor: f64) -> BTreeResult<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        if !self.is_empty() {
+            return Err(BPlusTreeError::invalid_state(
+                "bulk load",
+                "the map is not empty",
+            ));
+        }
+        if !(fill_factor > 0.0 && fill_factor <= 1.0) {
+            return Err(BPlusTreeError::invalid_state(
+                "bulk load",
+                "fill factor must lie in (0, 1]",
+            ));
+        }
+        // An empty map may still hold an empty root leaf.
+        self.clear();
+        let exact = self.leaf_layout.cap as f64 * fill_factor;
+        let fill = exact as usize + usize::from((exact as usize as f64) < exact);
+
+        // Each pair is held back until the next one proves it is in order; on the
+        // first violation the input simply ends and the partial tree is discarded.
+        let mut iter = iter.into_iter();
+        let mut pending = iter.next();
+        let mut out_of_order = false;
+        let checked = core::iter::from_fn(|| {
+            let pair = pending.take()?;
+            pending = iter.next();
+            match &pending {
+                Some(next) if next.0 <= pair.0 => {
+                    out_of_order = true;
+                    pending = None;
+                    None
+                }
+                _ => Some(pair),
+            }
+        });
+        unsafe { self.build_from_sorted(checked, fill) };
+        if out_of_order {
+            self.clear();
+            return Err(BPlusTreeError::invalid_state(
+                "bulk load",
+                "keys are not strictly increasing",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build the tree bottom-up from `items`, which must be in strictly ascending key
+    /// order. The tree must be empty and have no root.
+    ///
+    /// Leaves are filled to `leaf_fill` items and branches are packed full, except that
+    /// the last two leaves are evened out and each branch level is split into equally
+    /// sized groups, so every non-root node meets the minimum occupancy.
+    pub(crate) unsafe fn build_from_sorted<I>(&mut self, items: I, leaf_fill: usize)
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        debug_assert!(self.root.is_none() && self.len == 0);
+        let cap = self.leaf_layout.cap as usize;
+        let fill = leaf_fill.clamp(self.min_leaf_len().max(1), cap);
+
+        let mut leaves: Vec<NonNull<u8>> = Vec::new();
+        let mut cur: Option<NonNull<u8>> = None;
+        for (key, value) in items {
+            let leaf = match cur {
+                Some(leaf) if self.leaf_len(leaf) < fill => leaf,
+                _ => {
+                    let leaf = self.new_leaf();
+                    if let Some(prev) = cur {
+                        self.link_leaves(prev, leaf);
+                    }
+                    leaves.push(leaf);
+                    cur = Some(leaf);
+                    leaf
+                }
+            };
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            self.write_kv_at(
+                parts.keys_ptr as *mut K,
+                parts.vals_ptr as *mut V,
+                len,
+                key,
+                value,
+            );
+            (*parts.hdr).len = (len + 1) as u16;
+            self.len += 1;
+        }
+
+        match leaves.len() {
+            0 => return,
+            1 => {
+                self.root = Some(leaves[0]);
+                return;
+            }
+            _ => {}
+        }
+        let last = leaves[leaves.len() - 1];
+        let prev = leaves[leaves.len() - 2];
+        if self.leaf_len(last) < self.min_leaf_len() {
+            if self.leaf_len(prev) + self.leaf_len(last) <= cap {
+                self.merge_leaf_into(prev, last);
+                self.free_leaf_node(last);
+                leaves.pop();
+            } else {
+                self.even_out_leaves(prev, last);
+            }
+        }
+
+        // Each leaf but the first is the separator in front of itself.
+        let mut seps: Vec<NonNull<u8>> = leaves[1..].to_vec();
+        let mut level = leaves;
+        while level.len() > 1 {
+            (level, seps) = self.build_branch_level(level, seps);
+        }
+        self.root = Some(level[0]);
+    }
+
+    /// Group `nodes` (with `seps[i]` between `nodes[i]` and `nodes[i + 1]`) under as
+    /// few branches as possible, spreading the children evenly. Returns the new
+    /// level and the separators between its branches.
+    unsafe fn build_branch_level(
+        &mut self,
+        nodes: Vec<NonNull<u8>>,
+        seps: Vec<NonNull<u8>>,
+    ) -> (Vec<NonNull<u8>>, Vec<NonNull<u8>>) {
+        let fanout = self.branch_layout.cap as usize + 1;
+        let groups = nodes.len().div_ceil(fanout);
+        let (base, extra) = (nodes.len() / groups, nodes.len() % groups);
+
+        let mut parents = Vec::with_capacity(groups);
+        let mut parent_seps = Vec::with_capacity(groups - 1);
+        let mut seps = seps.into_iter();
+        let mut children = nodes.into_iter();
+        for g in 0..groups {
+            let count = base + usize::from(g < extra);
+            if g > 0 {
+                parent_seps.push(seps.next().expect("separator per node boundary"));
+            }
+            let branch = self.new_branch();
+            let b = layout::carve_branch::<K>(branch, &self.branch_layout);
+            let cbase = b.children;
+            for c in 0..count {
+                if c > 0 {
+                    let sep = seps.next().expect("separator per node boundary");
+                    b.seps.set(c - 1, sep.as_ptr());
+                }
+                cbase.set(c, children.next().expect("child per slot").as_ptr());
+                self.recount_child(branch, c);
+            }
+            (*b.hdr).len = (count - 1) as u16;
+            parents.push(branch);
+        }
+        (parents, parent_seps)
+    }
+
+    /// Move items from the tail of `left` to the front of `right` so both hold half.
+    unsafe fn even_out_leaves(&self, left: NonNull<u8>, right: NonNull<u8>) {
+        let l = layout::carve_leaf::<K, V>(left, &self.leaf_layout);
+        let r = layout::carve_leaf::<K, V>(right, &self.leaf_layout);
+        let (l_len, r_len) = ((*l.hdr).len as usize, (*r.hdr).len as usize);
+        let moved = (l_len + r_len) / 2 - r_len;
+        let (lk, lv) = (l.keys_ptr as *mut K, l.vals_ptr as *mut V);
+        let (rk, rv) = (r.keys_ptr as *mut K, r.vals_ptr as *mut V);
+        ptr::copy(rk, rk.add(moved), r_len);
+        ptr::copy(rv, rv.add(moved), r_len);
+        ptr::copy_nonoverlapping(lk.add(l_len - moved), rk, moved);
+        ptr::copy_nonoverlapping(lv.add(l_len - moved), rv, moved);
+        (*l.hdr).len = (l_len - moved) as u16;
+        (*r.hdr).len = (r_len + moved) as u16;
+    }
+
+    /// Make `right` the successor of `left` in the leaf chain.
+    pub(crate) unsafe fn link_leaves(&self, left: NonNull<u8>, right: NonNull<u8>) {
+        let l = layout::carve_leaf::<K, V>(left, &self.leaf_layout);
+        let r = layout::carve_leaf::<K, V>(right, &self.leaf_layout);
+        l.next.set(right.as_ptr());
+        if let Some(prev_link) = r.prev {
+            prev_link.set(left.as_ptr());
+        }
+    }
+
+    #[inline]
+    unsafe fn leaf_len(&self, leaf: NonNull<u8>) -> usize {
+        (*(leaf.as_ptr() as *const crate::NodeHdr)).len as usize
+    }
+}
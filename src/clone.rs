@@ -0,0 +1,162 @@
+use alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+
+use crate::layout;
+use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult, NodeAllocator, NodeHdr, NodeTag};
+
+/// Owns the nodes of a partially built clone. If a `clone()` panics, dropping it
+/// drops the pairs copied so far and frees every node; branches hold no pairs.
+struct CloneGuard<'a, K, V, A: NodeAllocator> {
+    map: &'a BPlusTreeMap<K, V, A>,
+    /// The rootless copy; nodes come from its allocator and node pool.
+    out: &'a mut BPlusTreeMap<K, V, A>,
+    /// The new leaf chain, linked in key order as leaves are created.
+    first_leaf: *mut u8,
+    last_leaf: *mut u8,
+    branches: Vec<NonNull<u8>>,
+}
+
+impl<K, V, A: NodeAllocator> Drop for CloneGuard<'_, K, V, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let mut cur = self.first_leaf;
+            while let Some(leaf) = NonNull::new(cur) {
+                let parts = layout::carve_leaf::<K, V>(leaf, &self.map.leaf_layout);
+                for i in 0..(*parts.hdr).len as usize {
+                    ptr::drop_in_place(parts.keys_ptr.add(i) as *mut K);
+                    ptr::drop_in_place(parts.vals_ptr.add(i) as *mut V);
+                }
+                cur = parts.next.get();
+                self.out.free_leaf(leaf);
+            }
+            for &branch in &self.branches {
+                self.out.free_branch(branch);
+            }
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, A: NodeAllocator> CloneGuard<'_, K, V, A> {
+    /// Copy the subtree under `src` node by node and return its new root.
+    unsafe fn clone_node(&mut self, src: NonNull<u8>) -> NonNull<u8> {
+        match (*(src.as_ptr() as *const NodeHdr)).tag {
+            NodeTag::Leaf => self.clone_leaf(src),
+            NodeTag::Branch => self.clone_branch(src),
+        }
+    }
+
+    unsafe fn clone_leaf(&mut self, src: NonNull<u8>) -> NonNull<u8> {
+        let leaf_layout = &self.map.leaf_layout;
+        let leaf = self.out.new_leaf();
+        let s = layout::carve_leaf::<K, V>(src, leaf_layout);
+        let d = layout::carve_leaf::<K, V>(leaf, leaf_layout);
+        match NonNull::new(self.last_leaf) {
+            Some(prev) => {
+                layout::carve_leaf::<K, V>(prev, leaf_layout)
+                    .next
+                    .set(leaf.as_ptr());
+                if let Some(prev_link) = d.prev {
+                    prev_link.set(prev.as_ptr());
+                }
+            }
+            None => self.first_leaf = leaf.as_ptr(),
+        }
+        self.last_leaf = leaf.as_ptr();
+
+        // The length grows with each pair, so a panicking clone leaves a valid leaf.
+        for i in 0..(*s.hdr).len as usize {
+            let key = (*(s.keys_ptr.add(i) as *const K)).clone();
+            let value = (*(s.vals_ptr.add(i) as *const V)).clone();
+            ptr::write(d.keys_ptr.add(i) as *mut K, key);
+            ptr::write(d.vals_ptr.add(i) as *mut V, value);
+            (*d.hdr).len = (i + 1) as u16;
+        }
+        leaf
+    }
+
+    unsafe fn clone_branch(&mut self, src: NonNull<u8>) -> NonNull<u8> {
+        let branch = self.out.new_branch();
+        self.branches.push(branch);
+        let s = layout::carve_branch::<K>(src, &self.map.branch_layout);
+        let d = layout::carve_branch::<K>(branch, &self.map.branch_layout);
+        for i in 0..=(*s.hdr).len as usize {
+            let before = self.last_leaf;
+            let src_child = NonNull::new_unchecked(s.children.get(i));
+            let child = self.clone_node(src_child);
+            d.children.set(i, child.as_ptr());
+            if let (Some(src_counts), Some(counts)) =
+                (self.map.counts_of(src), self.map.counts_of(branch))
+            {
+                *counts.add(i) = *src_counts.add(i);
+            }
+            if i > 0 {
+                // The separator links to the new child's first leaf.
+                d.seps.set(i - 1, self.map.leaf_layout.next_of(before));
+                (*d.hdr).len = i as u16;
+            }
+        }
+        branch
+    }
+}
+
+impl<K: Clone, V: Clone, A: NodeAllocator + Clone> Clone for BPlusTreeMap<K, V, A> {
+    /// Copy the tree node by node, keeping the node layouts and shape of `self`.
+    /// The copy allocates through a clone of `self`'s allocator.
+    fn clone(&self) -> Self {
+        self.clone_with(self.empty_like(), Vec::new())
+    }
+}
+
+impl<K: Clone, V: Clone, A: NodeAllocator + Clone> BPlusTreeMap<K, V, A> {
+    /// Like `clone`, but allocates every node of the copy before cloning any pair,
+    /// and returns `AllocationError` instead of aborting when memory runs out.
+    pub fn try_clone(&self) -> BTreeResult<Self> {
+        let (leaves, branches) = self
+            .root
+            .map_or((0, 0), |root| unsafe { self.node_counts(root) });
+        let mut branch_list = Vec::new();
+        branch_list
+            .try_reserve_exact(branches)
+            .map_err(|_| BPlusTreeError::allocation_error("branch list", "out of memory"))?;
+        let mut out = self.empty_like();
+        out.reserve_nodes(leaves, branches)?;
+        Ok(self.clone_with(out, branch_list))
+    }
+
+    /// Copy the tree into the empty map `out`, taking nodes from its node pool
+    /// while they last and recording the new branches in `branch_list`.
+    fn clone_with(&self, mut out: Self, branch_list: Vec<NonNull<u8>>) -> Self {
+        let Some(root) = self.root else {
+            return out;
+        };
+        let mut guard = CloneGuard {
+            map: self,
+            out: &mut out,
+            first_leaf: ptr::null_mut(),
+            last_leaf: ptr::null_mut(),
+            branches: branch_list,
+        };
+        let root = unsafe { guard.clone_node(root) };
+        // The clone owns the nodes now.
+        guard.first_leaf = ptr::null_mut();
+        guard.branches.clear();
+        drop(guard);
+        out.root = Some(root);
+        out.len = self.len;
+        unsafe { out.trim_pool() };
+        out
+    }
+
+    /// Number of leaves and branches in the subtree under `node`.
+    unsafe fn node_counts(&self, node: NonNull<u8>) -> (usize, usize) {
+        if (*(node.as_ptr() as *const NodeHdr)).tag == NodeTag::Leaf {
+            return (1, 0);
+        }
+        let b = layout::carve_branch::<K>(node, &self.branch_layout);
+        let children = b.children;
+        (0..=(*b.hdr).len as usize).fold((0, 1), |(leaves, branches), i| {
+            let (l, br) = self.node_counts(NonNull::new_unchecked(children.get(i)));
+            (leaves + l, branches + br)
+        })
+    }
+}
@@ -1,17 +1,17 @@
-use alloc::format;
-use alloc::string::String;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
 use core::ptr::NonNull;
 
 use crate::layout;
-use crate::{BPlusTreeMap, NodeHdr, NodeTag};
+use crate::{BPlusTreeMap, ErrorText, NodeAllocator, NodeHdr, NodeTag};
 
-pub(crate) struct ValidationState<K> {
+pub(crate) struct ValidationState<'a, K> {
     pub(crate) total_items: usize,
     pub(crate) prev_leaf: Option<NonNull<u8>>,
-    pub(crate) prev_key: Option<K>,
+    pub(crate) prev_key: Option<&'a K>,
 }
 
-impl<K, V> BPlusTreeMap<K, V> {
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
     #[inline(always)]
     pub(crate) unsafe fn shift_right(
         &self,
@@ -39,11 +39,6 @@ impl<K, V> BPlusTreeMap<K, V> {
         core::ptr::write(vals_ptr.add(idx), val);
     }
 
-    #[inline(always)]
-    pub(crate) unsafe fn write_key_at(&self, keys_ptr: *mut K, idx: usize, key: K) {
-        core::ptr::write(keys_ptr.add(idx), key);
-    }
-
     #[inline(always)]
     pub(crate) unsafe fn read_kv_at(
         &self,
@@ -56,23 +51,32 @@ impl<K, V> BPlusTreeMap<K, V> {
         (k, v)
     }
 
+    /// The key a separator stands for: the first key of the leaf it links to.
+    #[inline(always)]
+    pub(crate) unsafe fn sep_key<'a>(&self, leaf: *mut u8) -> &'a K {
+        &*(leaf.add(self.leaf_layout.keys_off) as *const K)
+    }
+
+    /// The leftmost leaf under `node`.
     #[inline]
-    pub(crate) unsafe fn key_clone_at(&self, keys_ptr: *const K, idx: usize) -> K
-    where
-        K: Clone,
-    {
-        (*keys_ptr.add(idx)).clone()
+    pub(crate) unsafe fn first_leaf_under(&self, node: NonNull<u8>) -> NonNull<u8> {
+        let mut cur = node;
+        while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+            let b = layout::carve_branch::<K>(cur, &self.branch_layout);
+            cur = NonNull::new_unchecked(b.children.get(0));
+        }
+        cur
     }
 
     /// Centralized binary search for keys in a node.
     /// This function will be optimized for performance in future iterations.
     #[inline(always)]
-    pub(crate) fn binary_search_keys<T: Ord>(
-        &self,
-        keys: &[T],
-        target: &T,
-    ) -> Result<usize, usize> {
-        keys.binary_search(target)
+    pub(crate) fn binary_search_keys<Q>(&self, keys: &[K], target: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        keys.binary_search_by(|k| k.borrow().cmp(target))
     }
 
     /// Safely move a key-value pair from one location to another, ensuring sources are cleared.
@@ -96,26 +100,42 @@ impl<K, V> BPlusTreeMap<K, V> {
     }
 }
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
     #[inline]
-    pub(crate) unsafe fn child_for_key(
+    pub(crate) unsafe fn child_for_key<Q>(
         &self,
         branch: NonNull<u8>,
-        key: &K,
-    ) -> Option<(NonNull<u8>, usize)> {
+        key: &Q,
+    ) -> Option<(NonNull<u8>, usize)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
         let len = (*parts.hdr).len as usize;
-        let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
-        let child_idx = match self.binary_search_keys(keys, key) {
-            Ok(i) => i + 1,
-            Err(i) => i,
-        };
-        let child_ptr = *(parts.children_ptr.add(child_idx) as *const *mut u8);
+        // The first separator above `key` picks the child; one equal to it goes right.
+        let (mut child_idx, mut hi) = (0, len);
+        while child_idx < hi {
+            let mid = child_idx + (hi - child_idx) / 2;
+            match self.sep_key(parts.seps.get(mid)).borrow().cmp(key) {
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => {
+                    child_idx = mid + 1;
+                    break;
+                }
+                Ordering::Less => child_idx = mid + 1,
+            }
+        }
+        let child_ptr = parts.children.get(child_idx);
         NonNull::new(child_ptr).map(|child| (child, child_idx))
     }
 
     #[inline]
-    pub(crate) fn leaf_for_key(&self, key: &K) -> Option<NonNull<u8>> {
+    pub(crate) fn leaf_for_key<Q>(&self, key: &Q) -> Option<NonNull<u8>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let mut cur = self.root?;
         unsafe {
             loop {
@@ -144,7 +164,29 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                     NodeTag::Leaf => return Some(cur),
                     NodeTag::Branch => {
                         let b = layout::carve_branch::<K>(cur, &self.branch_layout);
-                        let child_ptr = *(b.children_ptr as *const *mut u8);
+                        let child_ptr = b.children.get(0);
+                        if child_ptr.is_null() {
+                            return None;
+                        }
+                        cur = NonNull::new_unchecked(child_ptr);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn rightmost_leaf(&self) -> Option<NonNull<u8>> {
+        let mut cur = self.root?;
+        unsafe {
+            loop {
+                let hdr = &*(cur.as_ptr() as *const NodeHdr);
+                match hdr.tag {
+                    NodeTag::Leaf => return Some(cur),
+                    NodeTag::Branch => {
+                        let b = layout::carve_branch::<K>(cur, &self.branch_layout);
+                        let len = (*b.hdr).len as usize;
+                        let child_ptr = b.children.get(len);
                         if child_ptr.is_null() {
                             return None;
                         }
@@ -175,8 +217,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                     break;
                 }
                 count += 1;
-                let next_ptr = (cur.add(self.leaf_layout.next_off)) as *const *mut u8;
-                cur = *next_ptr;
+                cur = self.leaf_layout.next_of(cur);
             }
         }
         count
@@ -186,7 +227,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         self.check_invariants_detailed().is_ok()
     }
 
-    pub fn check_invariants_detailed(&self) -> Result<(), String> {
+    pub fn check_invariants_detailed(&self) -> Result<(), ErrorText> {
         let mut state = ValidationState {
             total_items: 0,
             prev_leaf: None,
@@ -194,33 +235,36 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         };
 
         unsafe {
-            match self.root {
-                None => Ok(()),
-                Some(root) => {
-                    self.validate_node(root, None, None, true, &mut state)?;
-
-                    if let Some(last_leaf) = state.prev_leaf {
-                        let next_ptr =
-                            *(last_leaf.as_ptr().add(self.leaf_layout.next_off) as *const *mut u8);
-                        if !next_ptr.is_null() {
-                            return Err("Tail leaf next pointer should be null".into());
-                        }
-                    }
+            if let Some(root) = self.root {
+                self.validate_node(root, None, None, true, &mut state)?;
 
-                    Ok(())
+                if let Some(last_leaf) = state.prev_leaf {
+                    if !self.leaf_layout.next_of(last_leaf.as_ptr()).is_null() {
+                        return Err("Tail leaf next pointer should be null".into());
+                    }
                 }
             }
         }
+
+        let walked = self.leaf_walk_len();
+        if self.len() != walked || state.total_items != walked {
+            return Err(text!(
+                "Stored length {} does not match {} pairs in the leaves",
+                self.len(),
+                walked
+            ));
+        }
+        Ok(())
     }
 
-    pub(crate) unsafe fn validate_node(
-        &self,
+    pub(crate) unsafe fn validate_node<'a>(
+        &'a self,
         node: NonNull<u8>,
-        lower: Option<&K>,
-        upper: Option<&K>,
+        lower: Option<&'a K>,
+        upper: Option<&'a K>,
         is_root: bool,
-        state: &mut ValidationState<K>,
-    ) -> Result<Option<(K, K)>, String> {
+        state: &mut ValidationState<'a, K>,
+    ) -> Result<Option<(&'a K, &'a K)>, ErrorText> {
         let hdr = &*(node.as_ptr() as *const NodeHdr);
         match hdr.tag {
             NodeTag::Leaf => self.validate_leaf(node, lower, upper, is_root, state),
@@ -228,21 +272,21 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
     }
 
-    pub(crate) unsafe fn validate_leaf(
-        &self,
+    pub(crate) unsafe fn validate_leaf<'a>(
+        &'a self,
         leaf: NonNull<u8>,
-        lower: Option<&K>,
-        upper: Option<&K>,
+        lower: Option<&'a K>,
+        upper: Option<&'a K>,
         is_root: bool,
-        state: &mut ValidationState<K>,
-    ) -> Result<Option<(K, K)>, String> {
+        state: &mut ValidationState<'a, K>,
+    ) -> Result<Option<(&'a K, &'a K)>, ErrorText> {
         let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
         let hdr = &*parts.hdr;
         let len = hdr.len as usize;
         let cap = self.leaf_layout.cap as usize;
 
         if len > cap {
-            return Err(format!("Leaf has {} keys but capacity is {}", len, cap));
+            return Err(text!("Leaf has {} keys but capacity is {}", len, cap));
         }
 
         if len == 0 {
@@ -255,9 +299,10 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
         let min_required = self.min_leaf_len();
         if !is_root && len < min_required {
-            return Err(format!(
+            return Err(text!(
                 "Leaf underfull: has {} keys, minimum is {}",
-                len, min_required
+                len,
+                min_required
             ));
         }
 
@@ -281,21 +326,21 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         if let Some(prev_leaf) = state.prev_leaf {
-            let prev_next = *(prev_leaf.as_ptr().add(self.leaf_layout.next_off) as *const *mut u8);
+            let prev_next = self.leaf_layout.next_of(prev_leaf.as_ptr());
             if prev_next != leaf.as_ptr() {
                 return Err("Leaf next pointer mismatch".into());
             }
         }
 
-        if let Some(prev_ptr) = parts.prev_ptr {
+        if let Some(prev_link) = parts.prev {
             match state.prev_leaf {
                 Some(prev) => {
-                    if *prev_ptr != prev.as_ptr() {
+                    if prev_link.get() != prev.as_ptr() {
                         return Err("Leaf prev pointer mismatch".into());
                     }
                 }
                 None => {
-                    if !(*prev_ptr).is_null() {
+                    if !prev_link.get().is_null() {
                         return Err("First leaf prev pointer should be null".into());
                     }
                 }
@@ -304,38 +349,38 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
         state.prev_leaf = Some(leaf);
 
-        if let Some(prev_key) = &state.prev_key {
+        if let Some(prev_key) = state.prev_key {
             if keys[0] <= *prev_key {
                 return Err("Leaf keys not globally increasing".into());
             }
         }
-        state.prev_key = Some(keys[len - 1].clone());
+        state.prev_key = Some(&keys[len - 1]);
         state.total_items += len;
 
-        Ok(Some((keys[0].clone(), keys[len - 1].clone())))
+        Ok(Some((&keys[0], &keys[len - 1])))
     }
 
-    pub(crate) unsafe fn validate_branch(
-        &self,
+    pub(crate) unsafe fn validate_branch<'a>(
+        &'a self,
         branch: NonNull<u8>,
-        lower: Option<&K>,
-        upper: Option<&K>,
+        lower: Option<&'a K>,
+        upper: Option<&'a K>,
         is_root: bool,
-        state: &mut ValidationState<K>,
-    ) -> Result<Option<(K, K)>, String> {
+        state: &mut ValidationState<'a, K>,
+    ) -> Result<Option<(&'a K, &'a K)>, ErrorText> {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
         let len = (*parts.hdr).len as usize;
         let cap = self.branch_layout.cap as usize;
 
         if len > cap {
-            return Err(format!("Branch has {} keys but capacity is {}", len, cap));
+            return Err(text!("Branch has {} keys but capacity is {}", len, cap));
         }
 
         if len == 0 {
             if !is_root {
                 return Err("Non-root branch has no keys".into());
             }
-            let child_ptr = *(parts.children_ptr as *const *mut u8);
+            let child_ptr = parts.children.get(0);
             if child_ptr.is_null() {
                 return Ok(None);
             }
@@ -343,48 +388,75 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
         let min_required = self.min_branch_len();
         if !is_root && len < min_required {
-            return Err(format!(
+            return Err(text!(
                 "Branch underfull: has {} keys, minimum is {}",
-                len, min_required
+                len,
+                min_required
             ));
         }
 
-        let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
-        for window in keys.windows(2) {
-            if window[0] >= window[1] {
+        // Check where every separator links before reading a key through it.
+        for i in 0..len {
+            let Some(child) = NonNull::new(parts.children.get(i + 1)) else {
+                return Err("Branch child pointer is null".into());
+            };
+            let sep = parts.seps.get(i);
+            if sep != self.first_leaf_under(child).as_ptr() {
+                return Err(
+                    "Separator does not link to the first leaf of its right subtree".into(),
+                );
+            }
+            if (*(sep as *const NodeHdr)).len == 0 {
+                return Err("Separator links to an empty leaf".into());
+            }
+        }
+        let key = |i: usize| -> &'a K { self.sep_key(parts.seps.get(i)) };
+        for i in 1..len {
+            if key(i - 1) >= key(i) {
                 return Err("Branch keys not strictly increasing".into());
             }
         }
 
         if let Some(low) = lower {
-            if len > 0 && keys[0] < *low {
+            if len > 0 && key(0) < low {
                 return Err("Branch keys fall below lower bound".into());
             }
         }
         if let Some(high) = upper {
-            if len > 0 && keys[len - 1] >= *high {
+            if len > 0 && key(len - 1) >= high {
                 return Err("Branch keys exceed upper bound".into());
             }
         }
 
-        let mut subtree_min: Option<K> = None;
-        let mut subtree_max: Option<K> = None;
+        let mut subtree_min: Option<&K> = None;
+        let mut subtree_max: Option<&K> = None;
 
         for i in 0..=len {
-            let child_ptr = *(parts.children_ptr.add(i) as *const *mut u8);
+            let child_ptr = parts.children.get(i);
             let child = match NonNull::new(child_ptr) {
                 Some(child) => child,
                 None => return Err("Branch child pointer is null".into()),
             };
 
-            let lower_bound = if i == 0 { lower } else { Some(&keys[i - 1]) };
-            let upper_bound = if i == len { upper } else { Some(&keys[i]) };
-
-            if let Some((child_min, child_max)) =
-                self.validate_node(child, lower_bound, upper_bound, false, state)?
-            {
+            let lower_bound = if i == 0 { lower } else { Some(key(i - 1)) };
+            let upper_bound = if i == len { upper } else { Some(key(i)) };
+
+            let items_before = state.total_items;
+            let bounds = self.validate_node(child, lower_bound, upper_bound, false, state)?;
+            if let Some(counts) = self.counts_of(branch) {
+                let under = state.total_items - items_before;
+                if *counts.add(i) != under {
+                    return Err(text!(
+                        "Subtree count {} does not match {} pairs under child {}",
+                        *counts.add(i),
+                        under,
+                        i
+                    ));
+                }
+            }
+            if let Some((child_min, child_max)) = bounds {
                 if subtree_min.is_none() {
-                    subtree_min = Some(child_min.clone());
+                    subtree_min = Some(child_min);
                 }
                 subtree_max = Some(child_max);
             }
@@ -398,8 +470,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
     #[inline]
     pub(crate) fn min_leaf_len(&self) -> usize {
+        // Separators read the first key of a leaf, so only the root leaf may empty.
         let cap = self.leaf_layout.cap as usize;
-        cap / 2
+        (cap / 2).max(1)
     }
 
     #[inline]
@@ -0,0 +1,404 @@
+use core::borrow::Borrow;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Bound;
+use core::ptr::{self, NonNull};
+
+use crate::layout::{self, LeafLayout};
+use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult, Global, NodeAllocator, NodeHdr};
+
+/// A position between two items of the leaf chain: just before slot `idx` of
+/// `leaf`, with `idx == len` meaning after its last item. A null `leaf` is the
+/// only gap of a map without a root.
+#[derive(Copy, Clone)]
+struct Gap {
+    leaf: *mut u8,
+    idx: usize,
+}
+
+impl Gap {
+    const NONE: Gap = Gap {
+        leaf: ptr::null_mut(),
+        idx: 0,
+    };
+
+    /// Leaf and slot of the item right after the gap.
+    unsafe fn next_slot<K, V>(self, layout: &LeafLayout) -> Option<(NonNull<u8>, usize)> {
+        let mut leaf = NonNull::new(self.leaf)?;
+        let mut idx = self.idx;
+        loop {
+            let parts = layout::carve_leaf::<K, V>(leaf, layout);
+            if idx < (*parts.hdr).len as usize {
+                return Some((leaf, idx));
+            }
+            leaf = NonNull::new(parts.next.get())?;
+            idx = 0;
+        }
+    }
+
+    /// Leaf and slot of the item right before the gap.
+    unsafe fn prev_slot<K, V>(self, layout: &LeafLayout) -> Option<(NonNull<u8>, usize)> {
+        let mut leaf = NonNull::new(self.leaf)?;
+        let mut idx = self.idx;
+        loop {
+            if idx > 0 {
+                return Some((leaf, idx - 1));
+            }
+            let parts = layout::carve_leaf::<K, V>(leaf, layout);
+            leaf = NonNull::new(parts.prev?.get())?;
+            idx = (*(leaf.as_ptr() as *const NodeHdr)).len as usize;
+        }
+    }
+}
+
+#[inline]
+unsafe fn slot<'b, K, V>(layout: &LeafLayout, leaf: NonNull<u8>, idx: usize) -> (&'b K, &'b V) {
+    let parts = layout::carve_leaf::<K, V>(leaf, layout);
+    (
+        &*(parts.keys_ptr.add(idx) as *const K),
+        &*(parts.vals_ptr.add(idx) as *const V),
+    )
+}
+
+#[inline]
+unsafe fn slot_mut<'b, K, V>(
+    layout: &LeafLayout,
+    leaf: NonNull<u8>,
+    idx: usize,
+) -> (&'b K, &'b mut V) {
+    let parts = layout::carve_leaf::<K, V>(leaf, layout);
+    (
+        &*(parts.keys_ptr.add(idx) as *const K),
+        &mut *(parts.vals_ptr.add(idx) as *mut V),
+    )
+}
+
+/// A read-only cursor sitting in a gap between two items of a [`BPlusTreeMap`].
+///
+/// Created by [`BPlusTreeMap::lower_bound`] and [`BPlusTreeMap::upper_bound`].
+/// Moving steps along the leaf chain, so walking the whole map never descends
+/// from the root again.
+pub struct Cursor<'a, K, V, A: NodeAllocator = Global> {
+    map: &'a BPlusTreeMap<K, V, A>,
+    gap: Gap,
+}
+
+// SAFETY: the gap is a position in the leaves the map reference borrows.
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Send for Cursor<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for Cursor<'_, K, V, A> {}
+
+/// A cursor that can also edit the map at its position.
+///
+/// Created by [`BPlusTreeMap::lower_bound_mut`] and [`BPlusTreeMap::upper_bound_mut`].
+/// Insertions and removals that fit inside the current leaf are done in place;
+/// only edits that split or rebalance leaves go through the root and re-locate
+/// the cursor afterwards.
+pub struct CursorMut<'a, K, V, A: NodeAllocator = Global> {
+    map: &'a mut BPlusTreeMap<K, V, A>,
+    gap: Gap,
+}
+
+// SAFETY: as for `Cursor`, with the map borrowed mutably.
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for CursorMut<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for CursorMut<'_, K, V, A> {}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Cursor in the gap before the first item above `bound`: `Included(k)` stops
+    /// before the first key `>= k`, `Excluded(k)` before the first key `> k`.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Cursor {
+            map: self,
+            gap: self.lower_gap(bound),
+        }
+    }
+
+    /// Cursor in the gap after the last item below `bound`: `Included(k)` stops
+    /// after the last key `<= k`, `Excluded(k)` after the last key `< k`.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Cursor {
+            map: self,
+            gap: self.upper_gap(bound),
+        }
+    }
+
+    /// Mutable counterpart of [`lower_bound`](Self::lower_bound).
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let gap = self.lower_gap(bound);
+        CursorMut { map: self, gap }
+    }
+
+    /// Mutable counterpart of [`upper_bound`](Self::upper_bound).
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let gap = self.upper_gap(bound);
+        CursorMut { map: self, gap }
+    }
+
+    fn lower_gap<Q>(&self, bound: Bound<&Q>) -> Gap
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let pos = match bound {
+            Bound::Unbounded => self.leftmost_leaf().map(|leaf| (leaf.as_ptr(), 0)),
+            Bound::Included(k) => self.leaf_position(k, false),
+            Bound::Excluded(k) => self.leaf_position(k, true),
+        };
+        pos.map_or(Gap::NONE, |(leaf, idx)| Gap { leaf, idx })
+    }
+
+    fn upper_gap<Q>(&self, bound: Bound<&Q>) -> Gap
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let pos = match bound {
+            Bound::Unbounded => self.rightmost_leaf().map(|leaf| unsafe {
+                (
+                    leaf.as_ptr(),
+                    (*(leaf.as_ptr() as *const NodeHdr)).len as usize,
+                )
+            }),
+            Bound::Included(k) => self.leaf_position(k, true),
+            Bound::Excluded(k) => self.leaf_position(k, false),
+        };
+        pos.map_or(Gap::NONE, |(leaf, idx)| Gap { leaf, idx })
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> Cursor<'a, K, V, A> {
+    /// Step over the next item and return it, or `None` at the end of the map.
+    pub fn move_next(&mut self) -> Option<(&'a K, &'a V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.next_slot::<K, V>(&self.map.leaf_layout)?;
+            self.gap = Gap {
+                leaf: leaf.as_ptr(),
+                idx: idx + 1,
+            };
+            Some(slot::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// Step back over the previous item and return it, or `None` at the start.
+    pub fn move_prev(&mut self) -> Option<(&'a K, &'a V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.prev_slot::<K, V>(&self.map.leaf_layout)?;
+            self.gap = Gap {
+                leaf: leaf.as_ptr(),
+                idx,
+            };
+            Some(slot::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// The item after the cursor, without moving.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.next_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(slot::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// The item before the cursor, without moving.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.prev_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(slot::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+}
+
+impl<K, V, A: NodeAllocator> Clone for Cursor<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            map: self.map,
+            gap: self.gap,
+        }
+    }
+}
+
+impl<K, V, A: NodeAllocator> CursorMut<'_, K, V, A> {
+    /// Step over the next item and return it, or `None` at the end of the map.
+    pub fn move_next(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.next_slot::<K, V>(&self.map.leaf_layout)?;
+            self.gap = Gap {
+                leaf: leaf.as_ptr(),
+                idx: idx + 1,
+            };
+            Some(slot_mut::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// Step back over the previous item and return it, or `None` at the start.
+    pub fn move_prev(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.prev_slot::<K, V>(&self.map.leaf_layout)?;
+            self.gap = Gap {
+                leaf: leaf.as_ptr(),
+                idx,
+            };
+            Some(slot_mut::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// The item after the cursor, without moving.
+    pub fn peek_next(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.next_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(slot_mut::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// The item before the cursor, without moving.
+    pub fn peek_prev(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.prev_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(slot_mut::<K, V>(&self.map.leaf_layout, leaf, idx))
+        }
+    }
+
+    /// A read-only view of the cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        Cursor {
+            map: self.map,
+            gap: self.gap,
+        }
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> CursorMut<'_, K, V, A> {
+    /// Insert a pair into the gap, leaving the cursor before it.
+    ///
+    /// Fails without touching the map if `key` does not sort strictly between
+    /// the items around the cursor.
+    pub fn insert_after(&mut self, key: K, value: V) -> BTreeResult<()> {
+        self.insert_in_gap(key, value, false)
+    }
+
+    /// Insert a pair into the gap, leaving the cursor after it.
+    ///
+    /// Fails without touching the map if `key` does not sort strictly between
+    /// the items around the cursor.
+    pub fn insert_before(&mut self, key: K, value: V) -> BTreeResult<()> {
+        self.insert_in_gap(key, value, true)
+    }
+
+    /// Remove and return the item after the cursor; the cursor stays in the gap.
+    pub fn remove_next(&mut self) -> Option<(K, V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.next_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(self.remove_slot(leaf, idx))
+        }
+    }
+
+    /// Remove and return the item before the cursor; the cursor stays in the gap.
+    pub fn remove_prev(&mut self) -> Option<(K, V)> {
+        unsafe {
+            let (leaf, idx) = self.gap.prev_slot::<K, V>(&self.map.leaf_layout)?;
+            Some(self.remove_slot(leaf, idx))
+        }
+    }
+
+    fn insert_in_gap(&mut self, key: K, value: V, move_past: bool) -> BTreeResult<()> {
+        let layout = self.map.leaf_layout;
+        unsafe {
+            let prev = self.gap.prev_slot::<K, V>(&layout);
+            let next = self.gap.next_slot::<K, V>(&layout);
+            let after_prev = prev.is_none_or(|(l, i)| slot::<K, V>(&layout, l, i).0 < &key);
+            let before_next = next.is_none_or(|(l, i)| &key < slot::<K, V>(&layout, l, i).0);
+            if !(after_prev && before_next) {
+                return Err(BPlusTreeError::invalid_state(
+                    "insert at cursor",
+                    "key does not fit between its neighbours",
+                ));
+            }
+
+            // Only a gap strictly inside a leaf (or at the open end of the chain) is
+            // guaranteed to lie within that leaf's separator bounds.
+            if let Some(leaf) = NonNull::new(self.gap.leaf) {
+                let parts = layout::carve_leaf::<K, V>(leaf, &layout);
+                let len = (*parts.hdr).len as usize;
+                let idx = self.gap.idx;
+                let has_prev = parts.prev.is_some_and(|p| !p.get().is_null());
+                let inside = (idx > 0 || !has_prev) && (idx < len || parts.next.get().is_null());
+                if inside && len < layout.cap as usize {
+                    let keys_ptr = parts.keys_ptr;
+                    self.map.insert_into_leaf_slot(parts, idx, len, key, value);
+                    self.map
+                        .adjust_counts_towards(&*(keys_ptr.add(idx) as *const K), true);
+                    self.gap.idx += usize::from(move_past);
+                    return Ok(());
+                }
+            }
+
+            // The bitwise copy only steers the re-location; the owned key lives in the map.
+            let probe = ManuallyDrop::new(ptr::read(&key));
+            self.map.insert(key, value);
+            self.relocate(&probe, move_past);
+        }
+        Ok(())
+    }
+
+    unsafe fn remove_slot(&mut self, leaf: NonNull<u8>, idx: usize) -> (K, V) {
+        let parts = layout::carve_leaf::<K, V>(leaf, &self.map.leaf_layout);
+        let len = (*parts.hdr).len as usize;
+        if self.map.root == Some(leaf) || len > self.map.min_leaf_len() {
+            self.map
+                .adjust_counts_towards(&*(parts.keys_ptr.add(idx) as *const K), false);
+            self.gap = Gap {
+                leaf: leaf.as_ptr(),
+                idx,
+            };
+            return self.map.leaf_remove_at(parts, idx);
+        }
+        let probe = ManuallyDrop::new(ptr::read(parts.keys_ptr.add(idx) as *const K));
+        let pair = self
+            .map
+            .remove_entry(&probe)
+            .expect("cursor slot key must be present");
+        self.relocate(&pair.0, false);
+        pair
+    }
+
+    /// Put the cursor before the first key `>= key` (or `> key` when `after`).
+    fn relocate(&mut self, key: &K, after: bool) {
+        self.gap = self
+            .map
+            .leaf_position(key, after)
+            .map_or(Gap::NONE, |(leaf, idx)| Gap { leaf, idx });
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, A: NodeAllocator> fmt::Debug for Cursor<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("prev", &self.peek_prev())
+            .field("next", &self.peek_next())
+            .finish()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, A: NodeAllocator> fmt::Debug for CursorMut<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorMut")
+            .field("prev", &self.as_cursor().peek_prev())
+            .field("next", &self.as_cursor().peek_next())
+            .finish()
+    }
+}
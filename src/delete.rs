@@ -1,8 +1,25 @@
-use crate::{dealloc_raw, layout, BPlusTreeError, BPlusTreeMap, NodeHdr, NodeTag};
+use crate::{layout, BPlusTreeError, BPlusTreeMap, NodeAllocator, NodeHdr, NodeTag};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+#[cfg(feature = "alloc")]
+use core::ops::Bound;
 use core::ptr::{self, NonNull};
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
-    pub fn remove(&mut self, key: &K) -> Option<V> {
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let root = self.root?;
         let result = unsafe { self.remove_rec(root, key) };
         if result.is_some() {
@@ -23,8 +40,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                     let mut keep_is_leaf = false;
 
                     for i in 0..child_count {
-                        let slot = parts.children_ptr.add(i) as *mut *mut u8;
-                        let child_ptr = *slot;
+                        let child_ptr = parts.children.get(i);
                         if child_ptr.is_null() {
                             continue;
                         }
@@ -33,9 +49,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                         match child_hdr.tag {
                             NodeTag::Leaf => {
                                 let child = NonNull::new_unchecked(child_ptr);
-                                if (*child_hdr).len == 0 {
+                                if child_hdr.len == 0 {
                                     self.free_leaf_node(child);
-                                    *slot = ptr::null_mut();
+                                    parts.children.set(i, ptr::null_mut());
                                     continue;
                                 }
                                 if let Some(existing) = keep_child {
@@ -43,14 +59,14 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                                         return;
                                     }
                                     let existing_hdr = &*(existing.as_ptr() as *const NodeHdr);
-                                    let existing_len = (*existing_hdr).len as usize;
-                                    let child_len = (*child_hdr).len as usize;
+                                    let existing_len = existing_hdr.len as usize;
+                                    let child_len = child_hdr.len as usize;
                                     if existing_len + child_len > self.leaf_layout.cap as usize {
                                         return;
                                     }
                                     self.merge_leaf_into(existing, child);
                                     self.free_leaf_node(child);
-                                    *slot = ptr::null_mut();
+                                    parts.children.set(i, ptr::null_mut());
                                 } else {
                                     keep_child = Some(child);
                                     keep_is_leaf = true;
@@ -83,16 +99,16 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
     unsafe fn make_leaf_root(&self, leaf: NonNull<u8>) {
         let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
-        if let Some(prev_ptr) = parts.prev_ptr {
-            *prev_ptr = ptr::null_mut();
+        if let Some(prev_link) = parts.prev {
+            prev_link.set(ptr::null_mut());
         }
     }
 
-    unsafe fn free_leaf_node(&mut self, leaf: NonNull<u8>) {
+    pub(crate) unsafe fn free_leaf_node(&mut self, leaf: NonNull<u8>) {
         let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
-        let next = *parts.next_ptr;
-        let prev = match parts.prev_ptr {
-            Some(prev_ptr) => *prev_ptr,
+        let next = parts.next.get();
+        let prev = match parts.prev {
+            Some(prev_link) => prev_link.get(),
             None => ptr::null_mut(),
         };
 
@@ -100,20 +116,20 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         if !prev.is_null() {
             let prev_leaf = NonNull::new_unchecked(prev);
             let prev_parts = layout::carve_leaf::<K, V>(prev_leaf, &self.leaf_layout);
-            *prev_parts.next_ptr = next;
+            prev_parts.next.set(next);
         }
 
         if !next.is_null() {
             let next_leaf = NonNull::new_unchecked(next);
             let next_parts = layout::carve_leaf::<K, V>(next_leaf, &self.leaf_layout);
-            if let Some(prev_ptr) = next_parts.prev_ptr {
-                *prev_ptr = prev;
+            if let Some(prev_link) = next_parts.prev {
+                prev_link.set(prev);
             }
         }
 
-        *parts.next_ptr = ptr::null_mut();
-        if let Some(prev_ptr) = parts.prev_ptr {
-            *prev_ptr = ptr::null_mut();
+        parts.next.set(ptr::null_mut());
+        if let Some(prev_link) = parts.prev {
+            prev_link.set(ptr::null_mut());
         }
 
         // NOTE: We do NOT drop keys/values here because:
@@ -122,10 +138,10 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         // 3. Dropping here would cause double-free
         // The only exception is in free_tree_no_drop which handles cleanup differently
 
-        dealloc_raw(leaf, self.leaf_layout.bytes, self.leaf_layout.max_align);
+        self.free_leaf(leaf);
     }
 
-    unsafe fn merge_leaf_into(&self, target: NonNull<u8>, source: NonNull<u8>) {
+    pub(crate) unsafe fn merge_leaf_into(&self, target: NonNull<u8>, source: NonNull<u8>) {
         let target_parts = layout::carve_leaf::<K, V>(target, &self.leaf_layout);
         let source_parts = layout::carve_leaf::<K, V>(source, &self.leaf_layout);
 
@@ -164,9 +180,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             return;
         }
 
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
         let idx = child_idx.min(len);
-        let child_ptr = *children.add(idx);
+        let child_ptr = children.get(idx);
         let Some(_) = NonNull::new(child_ptr) else {
             return;
         };
@@ -185,9 +201,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         branch_len: usize,
     ) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
+        let child_ptr = children.get(child_idx);
         let child = NonNull::new_unchecked(child_ptr);
         let child_parts = layout::carve_leaf::<K, V>(child, &self.leaf_layout);
         let child_len = (*child_parts.hdr).len as usize;
@@ -197,7 +213,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         if child_idx > 0 {
-            let left_ptr = *children.add(child_idx - 1);
+            let left_ptr = children.get(child_idx - 1);
             if let Some(left) = NonNull::new(left_ptr) {
                 let left_hdr = &*(left_ptr as *const NodeHdr);
                 if left_hdr.tag == NodeTag::Leaf {
@@ -212,7 +228,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         if child_idx < branch_len {
-            let right_ptr = *children.add(child_idx + 1);
+            let right_ptr = children.get(child_idx + 1);
             if let Some(right) = NonNull::new(right_ptr) {
                 let right_hdr = &*(right_ptr as *const NodeHdr);
                 if right_hdr.tag == NodeTag::Leaf {
@@ -240,9 +256,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         branch_len: usize,
     ) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
+        let child_ptr = children.get(child_idx);
         let child = NonNull::new_unchecked(child_ptr);
         let child_parts = layout::carve_branch::<K>(child, &self.branch_layout);
         let child_len = (*child_parts.hdr).len as usize;
@@ -252,7 +268,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         if child_idx > 0 {
-            let left_ptr = *children.add(child_idx - 1);
+            let left_ptr = children.get(child_idx - 1);
             if let Some(left) = NonNull::new(left_ptr) {
                 let left_parts = layout::carve_branch::<K>(left, &self.branch_layout);
                 let left_len = (*left_parts.hdr).len as usize;
@@ -264,7 +280,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         if child_idx < branch_len {
-            let right_ptr = *children.add(child_idx + 1);
+            let right_ptr = children.get(child_idx + 1);
             if let Some(right) = NonNull::new(right_ptr) {
                 let right_parts = layout::carve_branch::<K>(right, &self.branch_layout);
                 let right_len = (*right_parts.hdr).len as usize;
@@ -284,10 +300,10 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
 
     unsafe fn borrow_from_left_branch(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let left_ptr = *children.add(child_idx - 1);
-        let child_ptr = *children.add(child_idx);
+        let left_ptr = children.get(child_idx - 1);
+        let child_ptr = children.get(child_idx);
         let left = NonNull::new_unchecked(left_ptr);
         let child = NonNull::new_unchecked(child_ptr);
 
@@ -297,39 +313,48 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         let left_len = (*left_parts.hdr).len as usize;
         let child_len = (*child_parts.hdr).len as usize;
 
-        let sep_slot = (parts.keys_ptr as *mut K).add(child_idx - 1);
-        let parent_key = core::ptr::read(sep_slot);
-
-        let left_keys = left_parts.keys_ptr as *mut K;
-        let left_children = left_parts.children_ptr as *mut *mut u8;
+        let sep_idx = child_idx - 1;
+        let parent_sep = parts.seps.get(sep_idx);
 
-        // CRITICAL FIX: Use safe move operation for the key
-        let borrowed_key = core::ptr::read(left_keys.add(left_len - 1));
-        core::ptr::write_bytes(left_keys.add(left_len - 1), 0, 1); // Clear source key slot
+        let left_seps = left_parts.seps;
+        let left_children = left_parts.children;
 
-        let borrowed_child = *left_children.add(left_len);
+        let borrowed_sep = left_seps.get(left_len - 1);
+        let borrowed_child = left_children.get(left_len);
         (*left_parts.hdr).len = (left_len - 1) as u16;
-        *left_children.add(left_len) = ptr::null_mut();
+        left_children.set(left_len, ptr::null_mut());
 
-        let child_keys = child_parts.keys_ptr as *mut K;
-        let child_children = child_parts.children_ptr as *mut *mut u8;
+        let child_seps = child_parts.seps;
+        let child_children = child_parts.children;
         if child_len > 0 {
-            core::ptr::copy(child_keys, child_keys.add(1), child_len);
+            child_seps.shift(0, 1, child_len);
         }
-        core::ptr::copy(child_children, child_children.add(1), child_len + 1);
-        core::ptr::write(child_keys, parent_key);
-        *child_children.add(0) = borrowed_child;
+        child_children.shift(0, 1, child_len + 1);
+        child_seps.set(0, parent_sep);
+        child_children.set(0, borrowed_child);
         (*child_parts.hdr).len = (child_len + 1) as u16;
 
-        core::ptr::write(sep_slot, borrowed_key);
+        if let (Some(counts), Some(left_counts), Some(child_counts)) = (
+            self.counts_of(branch),
+            self.counts_of(left),
+            self.counts_of(child),
+        ) {
+            let moved = *left_counts.add(left_len);
+            core::ptr::copy(child_counts, child_counts.add(1), child_len + 1);
+            *child_counts = moved;
+            *counts.add(child_idx - 1) -= moved;
+            *counts.add(child_idx) += moved;
+        }
+
+        parts.seps.set(sep_idx, borrowed_sep);
     }
 
     unsafe fn borrow_from_right_branch(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
-        let right_ptr = *children.add(child_idx + 1);
+        let child_ptr = children.get(child_idx);
+        let right_ptr = children.get(child_idx + 1);
         let child = NonNull::new_unchecked(child_ptr);
         let right = NonNull::new_unchecked(right_ptr);
 
@@ -339,43 +364,49 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         let child_len = (*child_parts.hdr).len as usize;
         let right_len = (*right_parts.hdr).len as usize;
 
-        let sep_slot = (parts.keys_ptr as *mut K).add(child_idx);
-        let parent_key = core::ptr::read(sep_slot);
-
-        let right_keys = right_parts.keys_ptr as *mut K;
-        let right_children = right_parts.children_ptr as *mut *mut u8;
+        let sep_idx = child_idx;
+        let parent_sep = parts.seps.get(sep_idx);
 
-        // CRITICAL FIX: Use safe move operation for the key
-        let new_sep = core::ptr::read(right_keys.add(0));
-        core::ptr::write_bytes(right_keys.add(0), 0, 1); // Clear source key slot
+        let right_seps = right_parts.seps;
+        let right_children = right_parts.children;
 
-        let transfer_child = *right_children.add(0);
+        let new_sep = right_seps.get(0);
+        let transfer_child = right_children.get(0);
 
-        let child_keys = child_parts.keys_ptr as *mut K;
-        let child_children = child_parts.children_ptr as *mut *mut u8;
-        core::ptr::write(child_keys.add(child_len), parent_key);
-        *child_children.add(child_len + 1) = transfer_child;
+        let child_seps = child_parts.seps;
+        let child_children = child_parts.children;
+        child_seps.set(child_len, parent_sep);
+        child_children.set(child_len + 1, transfer_child);
         (*child_parts.hdr).len = (child_len + 1) as u16;
 
+        if let (Some(counts), Some(child_counts), Some(right_counts)) = (
+            self.counts_of(branch),
+            self.counts_of(child),
+            self.counts_of(right),
+        ) {
+            let moved = *right_counts;
+            core::ptr::copy(right_counts.add(1), right_counts, right_len);
+            *child_counts.add(child_len + 1) = moved;
+            *counts.add(child_idx) += moved;
+            *counts.add(child_idx + 1) -= moved;
+        }
+
         if right_len > 1 {
-            core::ptr::copy(right_keys.add(1), right_keys, right_len - 1);
-            // Clear the last key slot after shifting
-            core::ptr::write_bytes(right_keys.add(right_len - 1), 0, 1);
+            right_seps.shift(1, 0, right_len - 1);
         }
-        core::ptr::copy(right_children.add(1), right_children, right_len);
-        *right_children.add(right_len) = ptr::null_mut();
+        right_children.shift(1, 0, right_len);
+        right_children.set(right_len, ptr::null_mut());
         (*right_parts.hdr).len = (right_len - 1) as u16;
 
-        core::ptr::write(sep_slot, new_sep);
+        parts.seps.set(sep_idx, new_sep);
     }
 
     unsafe fn merge_branch_with_left(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let left_ptr = *children.add(child_idx - 1);
-        let child_ptr = *children.add(child_idx);
+        let left_ptr = children.get(child_idx - 1);
+        let child_ptr = children.get(child_idx);
         let left = NonNull::new_unchecked(left_ptr);
         let child = NonNull::new_unchecked(child_ptr);
 
@@ -396,38 +427,40 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             );
         }
 
-        let sep_slot = keys.add(child_idx - 1);
-        let sep_key = core::ptr::read(sep_slot);
-
-        let left_keys = left_parts.keys_ptr as *mut K;
-        let left_children = left_parts.children_ptr as *mut *mut u8;
-        let child_keys = child_parts.keys_ptr as *mut K;
-        let child_children = child_parts.children_ptr as *mut *mut u8;
+        let left_seps = left_parts.seps;
+        let left_children = left_parts.children;
+        let child_children = child_parts.children;
 
-        core::ptr::write(left_keys.add(left_len), sep_key);
-        for i in 0..child_len {
-            core::ptr::write(
-                left_keys.add(left_len + 1 + i),
-                core::ptr::read(child_keys.add(i)),
-            );
-        }
+        left_seps.set(left_len, parts.seps.get(child_idx - 1));
+        child_parts
+            .seps
+            .copy_to(0, left_seps, left_len + 1, child_len);
         for i in 0..=child_len {
-            *left_children.add(left_len + 1 + i) = *child_children.add(i);
+            left_children.set(left_len + 1 + i, child_children.get(i));
+        }
+        if let (Some(left_counts), Some(child_counts)) =
+            (self.counts_of(left), self.counts_of(child))
+        {
+            core::ptr::copy_nonoverlapping(
+                child_counts,
+                left_counts.add(left_len + 1),
+                child_len + 1,
+            );
         }
         (*left_parts.hdr).len = (left_len + 1 + child_len) as u16;
         (*child_parts.hdr).len = 0;
 
         self.free_branch_node(child);
         self.collapse_branch_entry(branch, child_idx - 1);
+        self.recount_child(branch, child_idx - 1);
     }
 
     unsafe fn merge_branch_with_right(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
-        let right_ptr = *children.add(child_idx + 1);
+        let child_ptr = children.get(child_idx);
+        let right_ptr = children.get(child_idx + 1);
         let child = NonNull::new_unchecked(child_ptr);
         let right = NonNull::new_unchecked(right_ptr);
 
@@ -448,41 +481,37 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             );
         }
 
-        let sep_slot = keys.add(child_idx);
-        let sep_key = core::ptr::read(sep_slot);
+        let child_seps = child_parts.seps;
+        let child_children = child_parts.children;
+        let right_children = right_parts.children;
 
-        let child_keys = child_parts.keys_ptr as *mut K;
-        let child_children = child_parts.children_ptr as *mut *mut u8;
-        let right_keys = right_parts.keys_ptr as *mut K;
-        let right_children = right_parts.children_ptr as *mut *mut u8;
-
-        core::ptr::write(child_keys.add(child_len), sep_key);
-        for i in 0..right_len {
-            core::ptr::write(
-                child_keys.add(child_len + 1 + i),
-                core::ptr::read(right_keys.add(i)),
-            );
-        }
+        child_seps.set(child_len, parts.seps.get(child_idx));
+        right_parts
+            .seps
+            .copy_to(0, child_seps, child_len + 1, right_len);
         for i in 0..=right_len {
-            *child_children.add(child_len + 1 + i) = *right_children.add(i);
+            child_children.set(child_len + 1 + i, right_children.get(i));
+        }
+        if let (Some(child_counts), Some(right_counts)) =
+            (self.counts_of(child), self.counts_of(right))
+        {
+            core::ptr::copy_nonoverlapping(
+                right_counts,
+                child_counts.add(child_len + 1),
+                right_len + 1,
+            );
         }
         (*child_parts.hdr).len = (child_len + 1 + right_len) as u16;
         (*right_parts.hdr).len = 0;
 
         self.free_branch_node(right);
         self.collapse_branch_entry(branch, child_idx);
+        self.recount_child(branch, child_idx);
     }
 
     unsafe fn free_branch_node(&mut self, node: NonNull<u8>) {
-        let parts = layout::carve_branch::<K>(node, &self.branch_layout);
-        let len = (*parts.hdr).len as usize;
-
-        // Drop all separator keys before deallocating
-        for i in 0..len {
-            ptr::drop_in_place((parts.keys_ptr as *mut K).add(i));
-        }
-
-        dealloc_raw(node, self.branch_layout.bytes, self.branch_layout.max_align);
+        // Separators only link to leaves, so there is nothing to drop.
+        self.free_branch(node);
     }
 
     unsafe fn collapse_branch_entry(&mut self, branch: NonNull<u8>, key_idx: usize) {
@@ -492,33 +521,26 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             return;
         }
 
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let seps = parts.seps;
+        let children = parts.children;
 
-        // Note: The key at key_idx has already been ptr::read out by the caller,
-        // so we must not drop it here. We just shift the remaining keys.
+        // The separator at key_idx has already been moved down by the caller.
         if key_idx < len - 1 {
-            core::ptr::copy(keys.add(key_idx + 1), keys.add(key_idx), len - key_idx - 1);
+            seps.shift(key_idx + 1, key_idx, len - key_idx - 1);
         }
-        // After shifting, the last key slot (at len-1) now contains a duplicate.
-        // We must not drop it, so we'll rely on the length being decremented.
 
-        core::ptr::copy(
-            children.add(key_idx + 2),
-            children.add(key_idx + 1),
-            len - key_idx,
-        );
-        *children.add(len) = ptr::null_mut();
+        children.shift(key_idx + 2, key_idx + 1, len - key_idx);
+        children.set(len, ptr::null_mut());
+        self.shift_counts_left(branch, key_idx + 1, len);
         (*parts.hdr).len = (len - 1) as u16;
     }
 
     unsafe fn borrow_from_left_leaf(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let left_ptr = *children.add(child_idx - 1);
-        let child_ptr = *children.add(child_idx);
+        let left_ptr = children.get(child_idx - 1);
+        let child_ptr = children.get(child_idx);
         let left = NonNull::new_unchecked(left_ptr);
         let child = NonNull::new_unchecked(child_ptr);
 
@@ -547,24 +569,21 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             0,
         );
 
-        // Update lengths after the move
+        // Update lengths after the move. The separator links to the child leaf
+        // and so already reads its new first key.
         (*left_parts.hdr).len = (left_len - 1) as u16;
         (*child_parts.hdr).len = (child_len + 1) as u16;
 
-        let new_sep = self.key_clone_at(child_parts.keys_ptr as *const K, 0);
-        let sep_slot = keys.add(child_idx - 1);
-        let old_sep = core::ptr::read(sep_slot);
-        drop(old_sep);
-        core::ptr::write(sep_slot, new_sep);
+        self.recount_child(branch, child_idx - 1);
+        self.recount_child(branch, child_idx);
     }
 
     unsafe fn borrow_from_right_leaf(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
-        let right_ptr = *children.add(child_idx + 1);
+        let child_ptr = children.get(child_idx);
+        let right_ptr = children.get(child_idx + 1);
         let child = NonNull::new_unchecked(child_ptr);
         let right = NonNull::new_unchecked(right_ptr);
 
@@ -602,42 +621,42 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             core::ptr::write_bytes(right_parts.keys_ptr.add(right_len - 1), 0, 1);
             core::ptr::write_bytes(right_parts.vals_ptr.add(right_len - 1), 0, 1);
         }
-        // If right_len == 1, we've already transferred the only item, so nothing to drop
+        // If right_len == 1, we've already transferred the only item, so nothing to drop.
+        // The separator links to the right leaf and so already reads its new first key.
         (*right_parts.hdr).len = (right_len - 1) as u16;
 
-        let new_sep = self.key_clone_at(right_parts.keys_ptr as *const K, 0);
-        let sep_slot = keys.add(child_idx);
-        let old_sep = core::ptr::read(sep_slot);
-        drop(old_sep);
-        core::ptr::write(sep_slot, new_sep);
+        self.recount_child(branch, child_idx);
+        self.recount_child(branch, child_idx + 1);
     }
 
     unsafe fn merge_leaf_with_left(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let left_ptr = *children.add(child_idx - 1);
-        let child_ptr = *children.add(child_idx);
+        let left_ptr = children.get(child_idx - 1);
+        let child_ptr = children.get(child_idx);
         let left = NonNull::new_unchecked(left_ptr);
         let child = NonNull::new_unchecked(child_ptr);
 
         self.merge_leaf_into(left, child);
         self.free_leaf_node(child);
         self.remove_branch_entry(branch, child_idx - 1);
+        self.recount_child(branch, child_idx - 1);
     }
 
     unsafe fn merge_leaf_with_right(&mut self, branch: NonNull<u8>, child_idx: usize) {
         let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
-        let children = parts.children_ptr as *mut *mut u8;
+        let children = parts.children;
 
-        let child_ptr = *children.add(child_idx);
-        let right_ptr = *children.add(child_idx + 1);
+        let child_ptr = children.get(child_idx);
+        let right_ptr = children.get(child_idx + 1);
         let child = NonNull::new_unchecked(child_ptr);
         let right = NonNull::new_unchecked(right_ptr);
 
         self.merge_leaf_into(child, right);
         self.free_leaf_node(right);
         self.remove_branch_entry(branch, child_idx);
+        self.recount_child(branch, child_idx);
     }
 
     unsafe fn remove_branch_entry(&mut self, branch: NonNull<u8>, key_idx: usize) {
@@ -647,25 +666,219 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             return;
         }
 
-        let keys = parts.keys_ptr as *mut K;
-        let children = parts.children_ptr as *mut *mut u8;
+        let seps = parts.seps;
+        let children = parts.children;
 
-        let removed = core::ptr::read(keys.add(key_idx));
-        drop(removed);
         if key_idx < len - 1 {
-            core::ptr::copy(keys.add(key_idx + 1), keys.add(key_idx), len - key_idx - 1);
+            seps.shift(key_idx + 1, key_idx, len - key_idx - 1);
         }
 
-        core::ptr::copy(
-            children.add(key_idx + 2),
-            children.add(key_idx + 1),
-            len - key_idx,
-        );
-        *children.add(len) = ptr::null_mut();
+        children.shift(key_idx + 2, key_idx + 1, len - key_idx);
+        children.set(len, ptr::null_mut());
+        self.shift_counts_left(branch, key_idx + 1, len);
         (*parts.hdr).len = (len - 1) as u16;
     }
 
-    unsafe fn remove_rec(&mut self, node: NonNull<u8>, key: &K) -> Option<V> {
+    /// Child indices taken at each branch level when descending towards `bound`.
+    /// An unbounded start follows the first child, an unbounded end the last one.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn boundary_path(&self, bound: Bound<&K>, is_end: bool) -> Vec<usize> {
+        let mut path = Vec::new();
+        let Some(mut cur) = self.root else {
+            return path;
+        };
+        unsafe {
+            while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+                let parts = layout::carve_branch::<K>(cur, &self.branch_layout);
+                let len = (*parts.hdr).len as usize;
+                let idx = match bound {
+                    Bound::Included(k) | Bound::Excluded(k) => {
+                        self.child_for_key(cur, k).expect("child must exist").1
+                    }
+                    Bound::Unbounded if is_end => len,
+                    Bound::Unbounded => 0,
+                };
+                path.push(idx);
+                cur = NonNull::new_unchecked(parts.children.get(idx));
+            }
+        }
+        path
+    }
+
+    /// Repair the tree after leaves between two boundary paths lost items in place.
+    ///
+    /// Every touched subtree is visited once, bottom-up: emptied subtrees are freed,
+    /// then underfull children are fixed with the regular borrow/merge steps, and
+    /// finally the root is collapsed. This replaces one rebalance per removed key.
+    #[cfg(feature = "alloc")]
+    pub(crate) unsafe fn rebalance_between(&mut self, lo_path: &[usize], hi_path: &[usize]) {
+        let Some(root) = self.root else {
+            return;
+        };
+        if !self.sweep_node(root, 0, true, true, lo_path, hi_path) {
+            if (*(root.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+                self.free_empty_subtree(root);
+                self.root = None;
+            }
+            return;
+        }
+        loop {
+            let before = self.root;
+            self.check_root_collapse();
+            if self.root == before {
+                break;
+            }
+        }
+    }
+
+    /// Sweep one node of the touched region. Returns false if its subtree is empty,
+    /// in which case the node is left for the caller to free.
+    #[cfg(feature = "alloc")]
+    unsafe fn sweep_node(
+        &mut self,
+        node: NonNull<u8>,
+        depth: usize,
+        on_lo: bool,
+        on_hi: bool,
+        lo_path: &[usize],
+        hi_path: &[usize],
+    ) -> bool {
+        let hdr = &*(node.as_ptr() as *const NodeHdr);
+        if hdr.tag == NodeTag::Leaf {
+            return hdr.len > 0;
+        }
+
+        let parts = layout::carve_branch::<K>(node, &self.branch_layout);
+        let children = parts.children;
+        let lo = if on_lo { lo_path[depth] } else { 0 };
+        let hi = if on_hi {
+            hi_path[depth]
+        } else {
+            (*parts.hdr).len as usize
+        };
+
+        // Right to left, so removing an emptied child never shifts one still to visit.
+        let mut removed = 0usize;
+        for c in (lo..=hi).rev() {
+            let child = NonNull::new_unchecked(children.get(c));
+            let sub_lo = on_lo && c == lo;
+            let sub_hi = on_hi && c == hi;
+            if self.sweep_node(child, depth + 1, sub_lo, sub_hi, lo_path, hi_path) {
+                continue;
+            }
+            self.free_empty_subtree(child);
+            if (*parts.hdr).len == 0 {
+                children.set(0, ptr::null_mut());
+                return false;
+            }
+            if c > 0 {
+                self.remove_branch_entry(node, c - 1);
+            } else {
+                self.remove_first_branch_entry(node);
+            }
+            removed += 1;
+        }
+
+        // Children emptied at the edge of the range leave nothing to fix there.
+        if hi >= lo + removed {
+            let hi = hi - removed;
+            // Surviving children may have lost their first leaf, and pairs; relink
+            // their separators before anything reads through them.
+            for c in lo.max(1)..=hi {
+                let first = self.first_leaf_under(NonNull::new_unchecked(children.get(c)));
+                parts.seps.set(c - 1, first.as_ptr());
+            }
+            for c in lo..=hi {
+                self.recount_child(node, c);
+            }
+            self.fix_children(node, lo, hi);
+        }
+        true
+    }
+
+    /// Fix underfull children `lo..=hi` of `branch`, left to right.
+    ///
+    /// A branch child may be left with a single underfull child of its own (nothing
+    /// to borrow from inside it), so once it has been merged or refilled from a
+    /// sibling its own children are fixed in turn.
+    #[cfg(feature = "alloc")]
+    unsafe fn fix_children(&mut self, branch: NonNull<u8>, lo: usize, hi: usize) {
+        let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
+        let children = parts.children;
+        let (mut i, mut hi) = (lo, hi);
+        loop {
+            let len = (*parts.hdr).len as usize;
+            if len == 0 || i > hi.min(len) {
+                break;
+            }
+            if !self.child_underfull(NonNull::new_unchecked(children.get(i))) {
+                i += 1;
+                continue;
+            }
+            self.fix_branch_child(branch, i);
+            if ((*parts.hdr).len as usize) < len {
+                // Merged: the survivor sits at i - 1 (or i when i == 0) and may need more.
+                hi = hi.saturating_sub(1);
+                i = i.saturating_sub(1);
+            }
+            let child = NonNull::new_unchecked(children.get(i));
+            let child_hdr = &*(child.as_ptr() as *const NodeHdr);
+            if child_hdr.tag == NodeTag::Branch {
+                self.fix_children(child, 0, child_hdr.len as usize);
+            }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    unsafe fn child_underfull(&self, child: NonNull<u8>) -> bool {
+        let hdr = &*(child.as_ptr() as *const NodeHdr);
+        match hdr.tag {
+            NodeTag::Leaf => (hdr.len as usize) < self.min_leaf_len(),
+            NodeTag::Branch => (hdr.len as usize) < self.min_branch_len(),
+        }
+    }
+
+    /// Free a subtree whose leaves hold no items, unlinking its leaves from the chain.
+    #[cfg(feature = "alloc")]
+    unsafe fn free_empty_subtree(&mut self, node: NonNull<u8>) {
+        let hdr = &*(node.as_ptr() as *const NodeHdr);
+        match hdr.tag {
+            NodeTag::Leaf => self.free_leaf_node(node),
+            NodeTag::Branch => {
+                let parts = layout::carve_branch::<K>(node, &self.branch_layout);
+                let len = (*parts.hdr).len as usize;
+                for i in 0..=len {
+                    let child_ptr = parts.children.get(i);
+                    if let Some(child) = NonNull::new(child_ptr) {
+                        self.free_empty_subtree(child);
+                    }
+                }
+                self.free_branch_node(node);
+            }
+        }
+    }
+
+    /// Remove the first separator together with the first child pointer.
+    #[cfg(feature = "alloc")]
+    unsafe fn remove_first_branch_entry(&mut self, branch: NonNull<u8>) {
+        let parts = layout::carve_branch::<K>(branch, &self.branch_layout);
+        let len = (*parts.hdr).len as usize;
+        let seps = parts.seps;
+        let children = parts.children;
+
+        seps.shift(1, 0, len - 1);
+        children.shift(1, 0, len);
+        children.set(len, ptr::null_mut());
+        self.shift_counts_left(branch, 0, len);
+        (*parts.hdr).len = (len - 1) as u16;
+    }
+
+    unsafe fn remove_rec<Q>(&mut self, node: NonNull<u8>, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let hdr = &*(node.as_ptr() as *const NodeHdr);
         match hdr.tag {
             NodeTag::Leaf => self.leaf_remove(node, key),
@@ -673,6 +886,9 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                 let (child, idx) = self.child_for_key(node, key)?;
                 let result = self.remove_rec(child, key);
                 if result.is_some() {
+                    if let Some(counts) = self.counts_of(node) {
+                        *counts.add(idx) -= 1;
+                    }
                     self.fix_branch_child(node, idx);
                 }
                 result
@@ -680,15 +896,28 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
     }
 
-    unsafe fn leaf_remove(&mut self, leaf: NonNull<u8>, key: &K) -> Option<V> {
+    unsafe fn leaf_remove<Q>(&mut self, leaf: NonNull<u8>, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
         let len = (*parts.hdr).len as usize;
         let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
         let idx = self.binary_search_keys(keys, key).ok()?;
+        Some(self.leaf_remove_at(parts, idx))
+    }
+
+    /// Move the pair at `idx` out of a leaf and close the gap. No rebalancing.
+    pub(crate) unsafe fn leaf_remove_at(
+        &mut self,
+        parts: layout::LeafParts<K, V>,
+        idx: usize,
+    ) -> (K, V) {
+        let len = (*parts.hdr).len as usize;
 
         // Read the key and value (transferring ownership)
-        let removed_key = core::ptr::read((parts.keys_ptr as *const K).add(idx));
-        let value = core::ptr::read(parts.vals_ptr.add(idx) as *const V);
+        let removed = self.read_kv_at(parts.keys_ptr as *const K, parts.vals_ptr as *const V, idx);
 
         // Shift remaining elements
         if idx < len - 1 {
@@ -705,11 +934,8 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         }
 
         (*parts.hdr).len = (len - 1) as u16;
-
-        // Drop the removed key (value is returned to caller)
-        drop(removed_key);
-
-        Some(value)
+        self.len -= 1;
+        removed
     }
 
     pub fn remove_item(&mut self, key: &K) -> Result<V, BPlusTreeError> {
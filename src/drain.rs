@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+use core::ptr::{self, NonNull};
+
+use crate::iterate::{bounds_are_empty, LeafRange};
+use crate::layout;
+use crate::{BPlusTreeMap, Global, NodeAllocator, NodeHdr};
+
+/// Owning iterator returned by [`BPlusTreeMap::drain`].
+///
+/// Pairs are moved out of their leaves as they are yielded, from either end. When
+/// the iterator is dropped, the pairs left in the range are dropped, the two
+/// boundary leaves are trimmed, the leaves in between are emptied, and a single
+/// sweep between the two boundary paths frees the emptied subtrees and repairs
+/// the rest.
+///
+/// Like [`Range`](crate::Range), it only knows its length on a counted map or
+/// over the whole map; otherwise `size_hint` has no upper bound.
+///
+/// The map is detached from its root while the iterator is alive, like with
+/// [`ExtractIf`](crate::ExtractIf); leaking the iterator leaks the map's contents
+/// and leaves it empty.
+pub struct Drain<'a, K: Ord, V, A: NodeAllocator = Global> {
+    map: &'a mut BPlusTreeMap<K, V, A>,
+    /// The map's root and length, reattached on drop.
+    root: Option<NonNull<u8>>,
+    len: usize,
+    /// Slots not yet yielded, and how many there are when that is known.
+    range: LeafRange,
+    remaining: Option<usize>,
+    /// The whole drained range: kept pairs end before `start` and resume at `end`.
+    start: (*mut u8, usize),
+    end: (*mut u8, usize),
+    lo_path: Vec<usize>,
+    hi_path: Vec<usize>,
+    repair: bool,
+}
+
+// SAFETY: the iterator is a `&mut` borrow of the map that also holds its
+// detached root, and owns the pairs it has not yielded yet.
+unsafe impl<K: Ord + Send, V: Send, A: NodeAllocator + Send> Send for Drain<'_, K, V, A> {}
+unsafe impl<K: Ord + Sync, V: Sync, A: NodeAllocator + Sync> Sync for Drain<'_, K, V, A> {}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Remove the pairs in `range`, yielding them in key order from either end.
+    /// Pairs not consumed are dropped together with the iterator.
+    pub fn drain<R: RangeBounds<K>>(&mut self, range: R) -> Drain<'_, K, V, A> {
+        let (start, end) = (range.start_bound(), range.end_bound());
+        let slots = self.leaf_range(start, end);
+        let repair = !bounds_are_empty(start, end) && self.root.is_some();
+        let (lo_path, hi_path) = if repair {
+            (
+                self.boundary_path(start, false),
+                self.boundary_path(end, true),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let remaining = if slots.is_empty() {
+            Some(0)
+        } else if self.is_counted() {
+            Some(self.bound_rank(end, true) - self.bound_rank(start, false))
+        } else if let (Bound::Unbounded, Bound::Unbounded) = (start, end) {
+            Some(self.len)
+        } else {
+            None
+        };
+        let root = self.root.take();
+        let len = mem::take(&mut self.len);
+        Drain {
+            map: self,
+            root,
+            len,
+            range: slots,
+            remaining,
+            start: slots.front(),
+            end: slots.back(),
+            lo_path,
+            hi_path,
+            repair,
+        }
+    }
+
+    /// Remove every pair in `range` and return how many there were.
+    ///
+    /// Only the two boundary leaves are trimmed; the leaves and subtrees between
+    /// them are emptied and freed whole by one repair pass.
+    pub fn remove_range<R: RangeBounds<K>>(&mut self, range: R) -> usize {
+        let before = self.len;
+        drop(self.drain(range));
+        before - self.len
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> Drain<'_, K, V, A> {
+    #[inline]
+    unsafe fn read_at(&self, (leaf, idx): (*mut u8, usize)) -> (K, V) {
+        (
+            ptr::read(self.range.key_at::<K>(leaf, idx)),
+            ptr::read(self.range.val_at::<V>(leaf, idx)),
+        )
+    }
+
+    #[inline]
+    unsafe fn next_leaf(&self, leaf: *mut u8) -> *mut u8 {
+        self.map.leaf_layout.next_of(leaf)
+    }
+
+    /// Close the gap the drained range left in the leaf chain: the first leaf keeps
+    /// its prefix, the last one its suffix, and the leaves in between are emptied.
+    unsafe fn close_gap(&mut self) {
+        let ((first, from), (last, to)) = (self.start, self.end);
+        if first.is_null() || (first, from) == (last, to) {
+            return;
+        }
+        let parts = layout::carve_leaf::<K, V>(NonNull::new_unchecked(last), &self.map.leaf_layout);
+        let tail = (*parts.hdr).len as usize - to;
+        let dest = if first == last { from } else { 0 };
+        if tail > 0 && dest != to {
+            ptr::copy(parts.keys_ptr.add(to), parts.keys_ptr.add(dest), tail);
+            ptr::copy(parts.vals_ptr.add(to), parts.vals_ptr.add(dest), tail);
+        }
+        (*parts.hdr).len = (dest + tail) as u16;
+        if first == last {
+            return;
+        }
+        (*(first as *mut NodeHdr)).len = from as u16;
+        let mut leaf = self.next_leaf(first);
+        while leaf != last {
+            (*(leaf as *mut NodeHdr)).len = 0;
+            leaf = self.next_leaf(leaf);
+        }
+    }
+
+    /// Reattach the root and repair the tree around the closed gap.
+    unsafe fn finish(&mut self) {
+        self.close_gap();
+        self.start = (ptr::null_mut(), 0);
+        self.map.root = self.root.take();
+        self.map.len = self.len;
+        if mem::take(&mut self.repair) {
+            self.map.rebalance_between(&self.lo_path, &self.hi_path);
+        }
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> Iterator for Drain<'_, K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        unsafe {
+            let slot = self.range.next_slot()?;
+            self.len -= 1;
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= 1;
+            }
+            Some(self.read_at(slot))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining {
+            Some(remaining) => (remaining, Some(remaining)),
+            None => (0, None),
+        }
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> DoubleEndedIterator for Drain<'_, K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        unsafe {
+            let slot = self.range.next_back_slot()?;
+            self.len -= 1;
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= 1;
+            }
+            Some(self.read_at(slot))
+        }
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> Drop for Drain<'_, K, V, A> {
+    fn drop(&mut self) {
+        // Keep draining and still repair the map even if dropping a K or V panics.
+        struct DropGuard<'a, 'b, K: Ord, V, A: NodeAllocator>(&'a mut Drain<'b, K, V, A>);
+
+        impl<K: Ord, V, A: NodeAllocator> Drop for DropGuard<'_, '_, K, V, A> {
+            fn drop(&mut self) {
+                for pair in self.0.by_ref() {
+                    drop(pair);
+                }
+                unsafe { self.0.finish() };
+            }
+        }
+
+        while let Some(pair) = self.next() {
+            let guard = DropGuard(self);
+            drop(pair);
+            mem::forget(guard);
+        }
+        unsafe { self.finish() };
+    }
+}
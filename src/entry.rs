@@ -0,0 +1,291 @@
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+
+use crate::insert::InsertResult;
+use crate::layout;
+use crate::{BPlusTreeMap, Global, NodeAllocator, NodeHdr};
+
+/// A view into a single entry of a [`BPlusTreeMap`], which may be vacant or occupied.
+///
+/// Constructed by [`BPlusTreeMap::entry`]. The leaf located by the initial descent is
+/// remembered, so inserting into a vacant entry or updating an occupied one does not
+/// walk down from the root again unless the leaf has to split.
+pub enum Entry<'a, K, V, A: NodeAllocator = Global> {
+    Vacant(VacantEntry<'a, K, V, A>),
+    Occupied(OccupiedEntry<'a, K, V, A>),
+}
+
+/// A vacant entry: the key is not present, `leaf`/`idx` is where it belongs.
+pub struct VacantEntry<'a, K, V, A: NodeAllocator = Global> {
+    key: K,
+    map: &'a mut BPlusTreeMap<K, V, A>,
+    leaf: Option<NonNull<u8>>,
+    idx: usize,
+}
+
+/// An occupied entry: `leaf`/`idx` holds the matching key and its value.
+pub struct OccupiedEntry<'a, K, V, A: NodeAllocator = Global> {
+    map: &'a mut BPlusTreeMap<K, V, A>,
+    leaf: NonNull<u8>,
+    idx: usize,
+}
+
+// SAFETY: an entry is a `&mut` borrow of the map plus a position inside it.
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for VacantEntry<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for VacantEntry<'_, K, V, A> {}
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for OccupiedEntry<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for OccupiedEntry<'_, K, V, A> {}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        let Some(leaf) = self.leaf_for_key(&key) else {
+            return Entry::Vacant(VacantEntry {
+                key,
+                map: self,
+                leaf: None,
+                idx: 0,
+            });
+        };
+        let found = unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
+            self.binary_search_keys(keys, &key)
+        };
+        match found {
+            Ok(idx) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                leaf,
+                idx,
+            }),
+            Err(idx) => Entry::Vacant(VacantEntry {
+                key,
+                map: self,
+                leaf: Some(leaf),
+                idx,
+            }),
+        }
+    }
+
+    /// Entry for the smallest key, or `None` if the map is empty.
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, A>> {
+        let leaf = self.leftmost_leaf()?;
+        let len = unsafe { (*(leaf.as_ptr() as *const NodeHdr)).len as usize };
+        if len == 0 {
+            return None;
+        }
+        Some(OccupiedEntry {
+            map: self,
+            leaf,
+            idx: 0,
+        })
+    }
+
+    /// Entry for the largest key, or `None` if the map is empty.
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, A>> {
+        let leaf = self.rightmost_leaf()?;
+        let len = unsafe { (*(leaf.as_ptr() as *const NodeHdr)).len as usize };
+        if len == 0 {
+            return None;
+        }
+        Some(OccupiedEntry {
+            map: self,
+            leaf,
+            idx: len - 1,
+        })
+    }
+
+    /// Remove and return the pair with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.first_entry().map(OccupiedEntry::remove_entry)
+    }
+
+    /// Remove and return the pair with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.last_entry().map(OccupiedEntry::remove_entry)
+    }
+}
+
+impl<'a, K: Ord, V, A: NodeAllocator> Entry<'a, K, V, A> {
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let value = default(e.key());
+                e.insert(value)
+            }
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Ord, V: Default, A: NodeAllocator> Entry<'a, K, V, A> {
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K: Ord, V, A: NodeAllocator> VacantEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Insert `value` at the remembered leaf slot. A full leaf is split through the
+    /// regular `insert_rec` path so separators propagate exactly as for `insert`.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry {
+            key,
+            map,
+            leaf,
+            idx,
+        } = self;
+        unsafe {
+            let leaf = match leaf {
+                Some(leaf) => leaf,
+                None => {
+                    let leaf = map.new_leaf();
+                    map.root = Some(leaf);
+                    leaf
+                }
+            };
+            let parts = layout::carve_leaf::<K, V>(leaf, &map.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            let (next, keys_ptr, vals_ptr) = (parts.next, parts.keys_ptr, parts.vals_ptr);
+            if len < map.leaf_layout.cap as usize {
+                map.insert_into_leaf_slot(parts, idx, len, key, value);
+                map.adjust_counts_towards(&*(keys_ptr.add(idx) as *const K), true);
+                return &mut *(vals_ptr.add(idx) as *mut V);
+            }
+
+            let root = map.root.expect("vacant entry leaf implies a root");
+            if let InsertResult::Split {
+                sep_leaf, right, ..
+            } = map.insert_rec(root, key, value)
+            {
+                map.grow_root(sep_leaf, right);
+            }
+
+            // The leaf split deterministically around `leaf_split_left_count`.
+            let left_count = map.leaf_split_left_count();
+            let (target, slot) = if idx < left_count {
+                (leaf, idx)
+            } else {
+                (NonNull::new_unchecked(next.get()), idx - left_count)
+            };
+            let tparts = layout::carve_leaf::<K, V>(target, &map.leaf_layout);
+            &mut *(tparts.vals_ptr.add(slot) as *mut V)
+        }
+    }
+}
+
+impl<'a, K: Ord, V, A: NodeAllocator> OccupiedEntry<'a, K, V, A> {
+    #[inline]
+    fn parts(&self) -> layout::LeafParts<K, V> {
+        unsafe { layout::carve_leaf::<K, V>(self.leaf, &self.map.leaf_layout) }
+    }
+
+    pub fn key(&self) -> &K {
+        unsafe { &*(self.parts().keys_ptr.add(self.idx) as *const K) }
+    }
+
+    pub fn get(&self) -> &V {
+        unsafe { &*(self.parts().vals_ptr.add(self.idx) as *const V) }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut *(self.parts().vals_ptr.add(self.idx) as *mut V) }
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut *(self.parts().vals_ptr.add(self.idx) as *mut V) }
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Remove the entry. Leaves that stay at or above the minimum fill are edited in
+    /// place; otherwise the removal goes through `remove_entry` so the leaf is rebalanced.
+    pub fn remove_entry(self) -> (K, V) {
+        let parts = self.parts();
+        unsafe {
+            let len = (*parts.hdr).len as usize;
+            if self.map.root == Some(self.leaf) || len > self.map.min_leaf_len() {
+                self.map
+                    .adjust_counts_towards(&*(parts.keys_ptr.add(self.idx) as *const K), false);
+                return self.map.leaf_remove_at(parts, self.idx);
+            }
+            // A bitwise copy of the key steers the descent; the owned key comes back
+            // out of the leaf, so the copy must never be dropped.
+            let key = ManuallyDrop::new(core::ptr::read(parts.keys_ptr.add(self.idx) as *const K));
+            self.map
+                .remove_entry(&key)
+                .expect("occupied entry key must be present")
+        }
+    }
+}
+
+impl<K: fmt::Debug + Ord, V: fmt::Debug, A: NodeAllocator> fmt::Debug for Entry<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Vacant(e) => f.debug_tuple("Entry").field(e).finish(),
+            Entry::Occupied(e) => f.debug_tuple("Entry").field(e).finish(),
+        }
+    }
+}
+
+impl<K: fmt::Debug + Ord, V, A: NodeAllocator> fmt::Debug for VacantEntry<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntry").field(self.key()).finish()
+    }
+}
+
+impl<K: fmt::Debug + Ord, V: fmt::Debug, A: NodeAllocator> fmt::Debug
+    for OccupiedEntry<'_, K, V, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
@@ -0,0 +1,225 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+use core::ptr::{self, NonNull};
+
+use crate::iterate::{below_end, bounds_are_empty};
+use crate::layout;
+use crate::{BPlusTreeMap, Global, NodeAllocator};
+
+/// Iterator returned by [`BPlusTreeMap::extract_if`].
+///
+/// Leaves are compacted in place during a single left-to-right pass: kept pairs
+/// slide down over the slots of extracted ones. Underfull leaves and branches are
+/// repaired once, when the iterator is dropped, by a sweep over the subtrees
+/// between the two boundary paths. Pairs not yet visited at that point are kept.
+///
+/// The map is detached from its root while the iterator is alive, since emptied
+/// leaves stay linked as separators until that sweep; leaking the iterator leaks
+/// the map's contents and leaves it empty.
+pub struct ExtractIf<'a, K, V, R, F, A: NodeAllocator = Global>
+where
+    K: Ord,
+    R: RangeBounds<K>,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    map: &'a mut BPlusTreeMap<K, V, A>,
+    /// The map's root and length, reattached on drop.
+    root: Option<NonNull<u8>>,
+    len: usize,
+    range: R,
+    pred: F,
+    /// Leaf being compacted, or null once the pass is over.
+    leaf: *mut u8,
+    /// Next slot to examine.
+    read: usize,
+    /// Next free slot for a kept pair; the leaf header length tracks it.
+    write: usize,
+    /// Length of `leaf` before compaction started.
+    leaf_len: usize,
+    lo_path: Vec<usize>,
+    hi_path: Vec<usize>,
+    repair: bool,
+}
+
+// SAFETY: the iterator is a `&mut` borrow of the map that also holds its
+// detached root, plus the caller's range and predicate.
+unsafe impl<K, V, R, F, A: NodeAllocator + Send> Send for ExtractIf<'_, K, V, R, F, A>
+where
+    K: Ord + Send,
+    V: Send,
+    R: RangeBounds<K> + Send,
+    F: FnMut(&K, &mut V) -> bool + Send,
+{
+}
+unsafe impl<K, V, R, F, A: NodeAllocator + Sync> Sync for ExtractIf<'_, K, V, R, F, A>
+where
+    K: Ord + Sync,
+    V: Sync,
+    R: RangeBounds<K> + Sync,
+    F: FnMut(&K, &mut V) -> bool + Sync,
+{
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Remove and yield every pair in `range` for which `pred` returns true.
+    pub fn extract_if<R, F>(&mut self, range: R, pred: F) -> ExtractIf<'_, K, V, R, F, A>
+    where
+        R: RangeBounds<K>,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let (start, end) = (range.start_bound(), range.end_bound());
+        let position = if bounds_are_empty(start, end) {
+            None
+        } else {
+            match start {
+                Bound::Unbounded => self.leftmost_leaf().map(|leaf| (leaf.as_ptr(), 0)),
+                Bound::Included(s) => self.leaf_position(s, false),
+                Bound::Excluded(s) => self.leaf_position(s, true),
+            }
+        };
+        let (lo_path, hi_path) = match position {
+            Some(_) => (
+                self.boundary_path(start, false),
+                self.boundary_path(end, true),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        let (leaf, idx) = position.unwrap_or((ptr::null_mut(), 0));
+        let root = self.root.take();
+        let len = mem::take(&mut self.len);
+        let mut iter = ExtractIf {
+            map: self,
+            root,
+            len,
+            range,
+            pred,
+            leaf: ptr::null_mut(),
+            read: 0,
+            write: 0,
+            leaf_len: 0,
+            lo_path,
+            hi_path,
+            repair: position.is_some(),
+        };
+        unsafe { iter.enter_leaf(leaf, idx) };
+        iter
+    }
+
+    /// Keep only the pairs for which `f` returns true, rebalancing once at the end.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.extract_if(.., |k, v| !f(k, v)).for_each(drop);
+    }
+}
+
+impl<K, V, R, F, A: NodeAllocator> ExtractIf<'_, K, V, R, F, A>
+where
+    K: Ord,
+    R: RangeBounds<K>,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    /// Start compacting `leaf` from slot `start`. The header length is cut to the
+    /// kept prefix so that a leaked iterator leaks pairs instead of double-dropping.
+    unsafe fn enter_leaf(&mut self, leaf: *mut u8, start: usize) {
+        self.leaf = leaf;
+        self.read = start;
+        self.write = start;
+        if let Some(leaf) = NonNull::new(leaf) {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.map.leaf_layout);
+            self.leaf_len = (*parts.hdr).len as usize;
+            (*parts.hdr).len = start as u16;
+        }
+    }
+
+    /// Slide the unvisited tail of the current leaf down and end the pass.
+    unsafe fn finish(&mut self) {
+        if let Some(leaf) = NonNull::new(self.leaf) {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.map.leaf_layout);
+            let tail = self.leaf_len - self.read;
+            if tail > 0 && self.read != self.write {
+                ptr::copy(
+                    parts.keys_ptr.add(self.read),
+                    parts.keys_ptr.add(self.write),
+                    tail,
+                );
+                ptr::copy(
+                    parts.vals_ptr.add(self.read),
+                    parts.vals_ptr.add(self.write),
+                    tail,
+                );
+            }
+            (*parts.hdr).len = (self.write + tail) as u16;
+        }
+        self.leaf = ptr::null_mut();
+    }
+}
+
+impl<K, V, R, F, A: NodeAllocator> Iterator for ExtractIf<'_, K, V, R, F, A>
+where
+    K: Ord,
+    R: RangeBounds<K>,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        unsafe {
+            while let Some(leaf) = NonNull::new(self.leaf) {
+                let parts = layout::carve_leaf::<K, V>(leaf, &self.map.leaf_layout);
+                if self.read == self.leaf_len {
+                    self.enter_leaf(parts.next.get(), 0);
+                    continue;
+                }
+                let key = &*(parts.keys_ptr.add(self.read) as *const K);
+                if !below_end(self.range.end_bound(), key) {
+                    self.finish();
+                    return None;
+                }
+                let val = &mut *(parts.vals_ptr.add(self.read) as *mut V);
+                if (self.pred)(key, val) {
+                    let pair = self.map.read_kv_at(
+                        parts.keys_ptr as *const K,
+                        parts.vals_ptr as *const V,
+                        self.read,
+                    );
+                    self.read += 1;
+                    self.len -= 1;
+                    return Some(pair);
+                }
+                if self.read != self.write {
+                    self.map.move_kv_at(
+                        parts.keys_ptr as *mut K,
+                        parts.vals_ptr as *mut V,
+                        self.read,
+                        parts.keys_ptr as *mut K,
+                        parts.vals_ptr as *mut V,
+                        self.write,
+                    );
+                }
+                self.read += 1;
+                self.write += 1;
+                (*parts.hdr).len = self.write as u16;
+            }
+            None
+        }
+    }
+}
+
+impl<K, V, R, F, A: NodeAllocator> Drop for ExtractIf<'_, K, V, R, F, A>
+where
+    K: Ord,
+    R: RangeBounds<K>,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    fn drop(&mut self) {
+        unsafe {
+            self.finish();
+            self.map.root = self.root.take();
+            self.map.len = self.len;
+            if self.repair {
+                self.repair = false;
+                self.map.rebalance_between(&self.lo_path, &self.hi_path);
+            }
+        }
+    }
+}
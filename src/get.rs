@@ -1,15 +1,27 @@
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+use core::borrow::Borrow;
 
 use crate::layout;
-use crate::{BPlusTreeError, BPlusTreeMap, BTreeResult};
+#[cfg(feature = "alloc")]
+use crate::BTreeResult;
+use crate::{BPlusTreeError, BPlusTreeMap, NodeAllocator};
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
-    pub fn get(&self, key: &K) -> Option<&V> {
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let (parts, idx) = self.leaf_search(key)?;
         unsafe { Some(&*(parts.vals_ptr.add(idx) as *const V)) }
     }
 
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let (parts, idx) = self.leaf_search(key)?;
         unsafe { Some(&mut *(parts.vals_ptr.add(idx) as *mut V)) }
     }
@@ -18,7 +30,11 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         self.get(key).ok_or(BPlusTreeError::KeyNotFound)
     }
 
-    pub fn contains_key(&self, key: &K) -> bool {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         self.get(key).is_some()
     }
 
@@ -26,6 +42,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         self.get(key).unwrap_or(default)
     }
 
+    #[cfg(feature = "alloc")]
     pub fn get_many<'a>(&'a self, keys: &'a [K]) -> BTreeResult<Vec<&'a V>> {
         let mut out = Vec::with_capacity(keys.len());
         for k in keys {
@@ -37,7 +54,11 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         Ok(out)
     }
 
-    pub(crate) fn leaf_search(&self, key: &K) -> Option<(layout::LeafParts<K, V>, usize)> {
+    pub(crate) fn leaf_search<Q>(&self, key: &Q) -> Option<(layout::LeafParts<K, V>, usize)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
         let leaf = self.leaf_for_key(key)?;
         unsafe {
             let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
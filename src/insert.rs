@@ -1,24 +1,23 @@
-use alloc::vec::Vec;
-
 use core::ptr::NonNull;
 
 use crate::layout;
-use crate::{alloc_branch_block, alloc_leaf_block, BPlusTreeMap, BTreeResult, NodeHdr, NodeTag};
+use crate::{BPlusTreeMap, BTreeResult, NodeAllocator, NodeHdr, NodeTag};
 
-pub(crate) enum InsertResult<K, V> {
+pub(crate) enum InsertResult<V> {
     NoSplit(Option<V>),
     Split {
-        sep_key: K,
+        /// Leaf holding the first key of `right`'s subtree.
+        sep_leaf: NonNull<u8>,
         right: NonNull<u8>,
         old_value: Option<V>,
     },
 }
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         let root = match self.root {
             Some(p) => p,
-            None => unsafe { alloc_leaf_block(&self.leaf_layout).expect("alloc leaf") },
+            None => unsafe { self.new_leaf() },
         };
         if self.root.is_none() {
             self.root = Some(root);
@@ -27,245 +26,269 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         match res {
             InsertResult::NoSplit(old) => old,
             InsertResult::Split {
-                sep_key,
+                sep_leaf,
                 right,
                 old_value,
             } => {
-                unsafe {
-                    let branch =
-                        alloc_branch_block(&self.branch_layout).expect("alloc new root branch");
-                    let b = layout::carve_branch::<K>(branch, &self.branch_layout);
-                    let bhdr = &mut *b.hdr;
-                    bhdr.len = 1;
-                    self.write_key_at(b.keys_ptr as *mut K, 0, sep_key);
-                    let c0 = b.children_ptr as *mut *mut u8;
-                    let c1 = c0.add(1);
-                    *c0 = root.as_ptr();
-                    *c1 = right.as_ptr();
-                    self.root = Some(branch);
-                }
+                unsafe { self.grow_root(sep_leaf, right) };
                 old_value
             }
         }
     }
 
-    pub fn batch_insert(&mut self, items: Vec<(K, V)>) -> BTreeResult<Vec<Option<V>>> {
-        let mut old_vals = Vec::with_capacity(items.len());
-        for (k, v) in items {
-            old_vals.push(self.insert(k, v));
+    /// Like [`Self::insert`], but allocates every node the insert's split chain
+    /// needs before touching the tree. If that fails, returns `AllocationError` and
+    /// leaves the tree as it was; `key` and `value` are dropped.
+    pub fn try_insert(&mut self, key: K, value: V) -> BTreeResult<Option<V>> {
+        let (leaves, branches) = unsafe { self.insert_needs(&key) };
+        self.reserve_nodes(leaves, branches)?;
+        let old = self.insert(key, value);
+        unsafe { self.trim_pool() };
+        Ok(old)
+    }
+
+    /// Number of leaves and branches that inserting `key` allocates: a leaf when
+    /// the target leaf is full, a branch for each full ancestor the split climbs
+    /// through, and a new root if it climbs through all of them.
+    unsafe fn insert_needs(&self, key: &K) -> (usize, usize) {
+        let Some(mut cur) = self.root else {
+            return (1, 0);
+        };
+        let branch_cap = self.branch_layout.cap as usize;
+        let (mut full_above, mut all_full) = (0, true);
+        while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+            if (*(cur.as_ptr() as *const NodeHdr)).len as usize == branch_cap {
+                full_above += 1;
+            } else {
+                (full_above, all_full) = (0, false);
+            }
+            cur = self.child_for_key(cur, key).expect("child must exist").0;
+        }
+        let leaf = layout::carve_leaf::<K, V>(cur, &self.leaf_layout);
+        let len = (*leaf.hdr).len as usize;
+        let keys = core::slice::from_raw_parts(leaf.keys_ptr as *const K, len);
+        if len < self.leaf_layout.cap as usize || self.binary_search_keys(keys, key).is_ok() {
+            return (0, 0);
         }
-        Ok(old_vals)
+        (1, full_above + usize::from(all_full))
+    }
+
+    /// Replace the root with a new branch over the old root and its split-off sibling.
+    pub(crate) unsafe fn grow_root(&mut self, sep_leaf: NonNull<u8>, right: NonNull<u8>) {
+        let root = self.root.expect("grow_root requires a root");
+        let branch = self.new_branch();
+        let b = layout::carve_branch::<K>(branch, &self.branch_layout);
+        let bhdr = &mut *b.hdr;
+        bhdr.len = 1;
+        b.seps.set(0, sep_leaf.as_ptr());
+        b.children.set(0, root.as_ptr());
+        b.children.set(1, right.as_ptr());
+        self.recount_children(branch);
+        self.root = Some(branch);
     }
 
-    unsafe fn insert_rec(&mut self, node: NonNull<u8>, key: K, value: V) -> InsertResult<K, V> {
+    pub(crate) unsafe fn insert_rec(
+        &mut self,
+        node: NonNull<u8>,
+        key: K,
+        value: V,
+    ) -> InsertResult<V> {
         let hdr = &*(node.as_ptr() as *const NodeHdr);
         match hdr.tag {
             NodeTag::Leaf => self.leaf_insert_or_split(node, key, value),
             NodeTag::Branch => {
                 let (child, child_idx) = self.child_for_key(node, &key).expect("child must exist");
                 match self.insert_rec(child, key, value) {
-                    InsertResult::NoSplit(old) => InsertResult::NoSplit(old),
+                    InsertResult::NoSplit(old) => {
+                        if let (None, Some(counts)) = (&old, self.counts_of(node)) {
+                            *counts.add(child_idx) += 1;
+                        }
+                        InsertResult::NoSplit(old)
+                    }
                     InsertResult::Split {
-                        sep_key,
+                        sep_leaf,
                         right,
                         old_value,
-                    } => {
-                        let b = layout::carve_branch::<K>(node, &self.branch_layout);
-                        let cur_len = (*b.hdr).len as usize;
-                        let cap = self.branch_layout.cap as usize;
-                        if cur_len < cap {
-                            core::ptr::copy(
-                                b.keys_ptr.add(child_idx) as *mut K,
-                                b.keys_ptr.add(child_idx + 1) as *mut K,
-                                cur_len - child_idx,
-                            );
-                            self.write_key_at(b.keys_ptr as *mut K, child_idx, sep_key);
-                            let cbase = b.children_ptr as *mut *mut u8;
-                            core::ptr::copy(
-                                cbase.add(child_idx + 1),
-                                cbase.add(child_idx + 2),
-                                cur_len - child_idx,
-                            );
-                            *cbase.add(child_idx + 1) = right.as_ptr();
-                            (*b.hdr).len = (cur_len + 1) as u16;
-                            InsertResult::NoSplit(old_value)
-                        } else {
-                            self.branch_insert_and_split(node, child_idx, sep_key, right, old_value)
-                        }
-                    }
+                    } => self.insert_into_branch(node, child_idx, sep_leaf, right, old_value),
                 }
             }
         }
     }
 
+    /// Insert `sep_leaf` at `child_idx` and `right` just after child `child_idx`,
+    /// splitting the branch when it is full. Counts of the affected children are
+    /// recomputed from the children themselves.
+    pub(crate) unsafe fn insert_into_branch(
+        &mut self,
+        node: NonNull<u8>,
+        child_idx: usize,
+        sep_leaf: NonNull<u8>,
+        right: NonNull<u8>,
+        old_value: Option<V>,
+    ) -> InsertResult<V> {
+        let b = layout::carve_branch::<K>(node, &self.branch_layout);
+        let cur_len = (*b.hdr).len as usize;
+        let cap = self.branch_layout.cap as usize;
+        if cur_len < cap {
+            b.seps.shift(child_idx, child_idx + 1, cur_len - child_idx);
+            b.seps.set(child_idx, sep_leaf.as_ptr());
+            let cbase = b.children;
+            cbase.shift(child_idx + 1, child_idx + 2, cur_len - child_idx);
+            cbase.set(child_idx + 1, right.as_ptr());
+            self.shift_counts_right(node, child_idx + 1, cur_len);
+            (*b.hdr).len = (cur_len + 1) as u16;
+            self.recount_child(node, child_idx);
+            self.recount_child(node, child_idx + 1);
+            InsertResult::NoSplit(old_value)
+        } else {
+            let res = self.branch_insert_and_split(node, child_idx, sep_leaf, right, old_value);
+            if let InsertResult::Split { right, .. } = &res {
+                // Rare enough that recounting both halves in full is fine.
+                self.recount_children(node);
+                self.recount_children(*right);
+            }
+            res
+        }
+    }
+
     unsafe fn branch_insert_and_split(
         &mut self,
         node: NonNull<u8>,
         insert_idx: usize,
-        ins_key: K,
+        ins_sep: NonNull<u8>,
         ins_right: NonNull<u8>,
         old_value: Option<V>,
-    ) -> InsertResult<K, V> {
+    ) -> InsertResult<V> {
         let b = layout::carve_branch::<K>(node, &self.branch_layout);
         let len = (*b.hdr).len as usize;
-        let total_keys = len + 1;
-        let pm = total_keys / 2; // number of keys that remain on the left after split
+        let total_seps = len + 1;
+        let pm = total_seps / 2; // number of separators that remain on the left after split
 
         // Allocate the new right branch
-        let right_node = alloc_branch_block(&self.branch_layout).expect("alloc right branch");
+        let right_node = self.new_branch();
         let rb = layout::carve_branch::<K>(right_node, &self.branch_layout);
 
-        let cbase_src = b.children_ptr as *const *mut u8;
-        let cbase_dst = rb.children_ptr as *mut *mut u8;
+        let cbase_src = b.children;
+        let cbase_dst = rb.children;
 
         if insert_idx < pm {
-            // Promote original key at pm-1
-            let promote = core::ptr::read((b.keys_ptr as *const K).add(pm - 1));
-
-            // Move keys [pm .. len) to right; clear source
-            let keys_move = len - pm;
-            if keys_move > 0 {
-                core::ptr::copy_nonoverlapping(
-                    (b.keys_ptr as *const K).add(pm),
-                    rb.keys_ptr as *mut K,
-                    keys_move,
-                );
-                core::ptr::write_bytes((b.keys_ptr as *mut K).add(pm), 0, keys_move);
+            // Promote original separator at pm-1
+            let promote = NonNull::new_unchecked(b.seps.get(pm - 1));
+
+            // Move separators [pm .. len) to right; clear source
+            let seps_move = len - pm;
+            if seps_move > 0 {
+                b.seps.copy_to(pm, rb.seps, 0, seps_move);
+                b.seps.clear(pm, seps_move);
             }
-            (*rb.hdr).len = keys_move as u16;
+            (*rb.hdr).len = seps_move as u16;
 
             // Move children [pm .. len] to right; clear source
             let cnt = (len + 1) - pm;
-            core::ptr::copy_nonoverlapping(cbase_src.add(pm), cbase_dst, cnt);
-            core::ptr::write_bytes((b.children_ptr as *mut *mut u8).add(pm), 0, cnt);
+            cbase_src.copy_to(pm, cbase_dst, 0, cnt);
+            cbase_src.clear(pm, cnt);
 
-            // Insert ins_key into left at insert_idx; shift keys and children
+            // Insert ins_sep into left at insert_idx; shift separators and children
             let left_keep = pm - 1;
             let to_shift = left_keep.saturating_sub(insert_idx);
             if to_shift > 0 {
-                core::ptr::copy(
-                    (b.keys_ptr as *mut K).add(insert_idx),
-                    (b.keys_ptr as *mut K).add(insert_idx + 1),
-                    to_shift,
-                );
+                b.seps.shift(insert_idx, insert_idx + 1, to_shift);
             }
-            self.write_key_at(b.keys_ptr as *mut K, insert_idx, ins_key);
+            b.seps.set(insert_idx, ins_sep.as_ptr());
             (*b.hdr).len = pm as u16;
 
-            let cbase_mut = b.children_ptr as *mut *mut u8;
+            let cbase_mut = b.children;
             let to_shift_c = (left_keep + 1).saturating_sub(insert_idx + 1);
             if to_shift_c > 0 {
-                core::ptr::copy(
-                    cbase_mut.add(insert_idx + 1),
-                    cbase_mut.add(insert_idx + 2),
-                    to_shift_c,
-                );
+                cbase_mut.shift(insert_idx + 1, insert_idx + 2, to_shift_c);
             }
-            *cbase_mut.add(insert_idx + 1) = ins_right.as_ptr();
+            cbase_mut.set(insert_idx + 1, ins_right.as_ptr());
 
             InsertResult::Split {
-                sep_key: promote,
+                sep_leaf: promote,
                 right: right_node,
                 old_value,
             }
         } else if insert_idx == pm {
-            // Promote the inserted key; do not store it in either child
-            let promote = ins_key;
-
-            // Move keys [pm .. len) to right; clear source
-            let keys_move = len - pm;
-            if keys_move > 0 {
-                core::ptr::copy_nonoverlapping(
-                    (b.keys_ptr as *const K).add(pm),
-                    rb.keys_ptr as *mut K,
-                    keys_move,
-                );
-                core::ptr::write_bytes((b.keys_ptr as *mut K).add(pm), 0, keys_move);
+            // Promote the inserted separator; do not store it in either child
+            let promote = ins_sep;
+
+            // Move separators [pm .. len) to right; clear source
+            let seps_move = len - pm;
+            if seps_move > 0 {
+                b.seps.copy_to(pm, rb.seps, 0, seps_move);
+                b.seps.clear(pm, seps_move);
             }
-            (*rb.hdr).len = keys_move as u16;
+            (*rb.hdr).len = seps_move as u16;
 
             // Right children: first is ins_right, then originals [pm+1 .. len]
-            *cbase_dst.add(0) = ins_right.as_ptr();
+            cbase_dst.set(0, ins_right.as_ptr());
             let cnt = len - pm;
             if cnt > 0 {
-                core::ptr::copy_nonoverlapping(cbase_src.add(pm + 1), cbase_dst.add(1), cnt);
-                core::ptr::write_bytes((b.children_ptr as *mut *mut u8).add(pm + 1), 0, cnt);
+                cbase_src.copy_to(pm + 1, cbase_dst, 1, cnt);
+                cbase_src.clear(pm + 1, cnt);
             }
 
             (*b.hdr).len = pm as u16;
             InsertResult::Split {
-                sep_key: promote,
+                sep_leaf: promote,
                 right: right_node,
                 old_value,
             }
         } else {
             // insert_idx > pm
-            // Promote original key at pm
-            let promote = core::ptr::read((b.keys_ptr as *const K).add(pm));
-
-            // Move keys [pm+1 .. len) to right; clear source
-            let keys_move = len.saturating_sub(pm + 1);
-            if keys_move > 0 {
-                core::ptr::copy_nonoverlapping(
-                    (b.keys_ptr as *const K).add(pm + 1),
-                    rb.keys_ptr as *mut K,
-                    keys_move,
-                );
-                core::ptr::write_bytes((b.keys_ptr as *mut K).add(pm + 1), 0, keys_move);
+            // Promote original separator at pm
+            let promote = NonNull::new_unchecked(b.seps.get(pm));
+
+            // Move separators [pm+1 .. len) to right; clear source
+            let seps_move = len.saturating_sub(pm + 1);
+            if seps_move > 0 {
+                b.seps.copy_to(pm + 1, rb.seps, 0, seps_move);
+                b.seps.clear(pm + 1, seps_move);
             }
-            (*rb.hdr).len = keys_move as u16;
+            (*rb.hdr).len = seps_move as u16;
 
             // Children to right: chunk1 [pm+1 .. insert_idx], then ins_right, then chunk2 [insert_idx+1 .. len]
             let first_count = insert_idx - pm;
             if first_count > 0 {
-                core::ptr::copy_nonoverlapping(cbase_src.add(pm + 1), cbase_dst, first_count);
-                core::ptr::write_bytes(
-                    (b.children_ptr as *mut *mut u8).add(pm + 1),
-                    0,
-                    first_count,
-                );
+                cbase_src.copy_to(pm + 1, cbase_dst, 0, first_count);
+                cbase_src.clear(pm + 1, first_count);
             }
-            *cbase_dst.add(first_count) = ins_right.as_ptr();
+            cbase_dst.set(first_count, ins_right.as_ptr());
             let second_count = len - insert_idx;
             if second_count > 0 {
-                core::ptr::copy_nonoverlapping(
-                    cbase_src.add(insert_idx + 1),
-                    cbase_dst.add(first_count + 1),
-                    second_count,
-                );
-                core::ptr::write_bytes(
-                    (b.children_ptr as *mut *mut u8).add(insert_idx + 1),
-                    0,
-                    second_count,
-                );
+                cbase_src.copy_to(insert_idx + 1, cbase_dst, first_count + 1, second_count);
+                cbase_src.clear(insert_idx + 1, second_count);
             }
 
-            // Insert ins_key into right at position relative to right start
+            // Insert ins_sep into right at position relative to right start
             let right_insert = insert_idx - (pm + 1);
-            let rkeys = rb.keys_ptr as *mut K;
+            let rseps = rb.seps;
             let current_right_len = (*rb.hdr).len as usize;
             let to_shift = current_right_len.saturating_sub(right_insert);
             if to_shift > 0 {
-                core::ptr::copy(
-                    rkeys.add(right_insert),
-                    rkeys.add(right_insert + 1),
-                    to_shift,
-                );
+                rseps.shift(right_insert, right_insert + 1, to_shift);
             }
-            self.write_key_at(rkeys, right_insert, ins_key);
+            rseps.set(right_insert, ins_sep.as_ptr());
             (*rb.hdr).len = (current_right_len + 1) as u16;
             (*b.hdr).len = pm as u16;
 
             InsertResult::Split {
-                sep_key: promote,
+                sep_leaf: promote,
                 right: right_node,
                 old_value,
             }
         }
     }
 
+    /// Number of items left in the original leaf when a full leaf splits on insert.
+    #[inline]
+    pub(crate) fn leaf_split_left_count(&self) -> usize {
+        (self.leaf_layout.cap as usize).div_ceil(2)
+    }
+
     #[inline(always)]
-    unsafe fn insert_into_leaf_slot(
+    pub(crate) unsafe fn insert_into_leaf_slot(
         &mut self,
         parts: layout::LeafParts<K, V>,
         idx: usize,
@@ -287,6 +310,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
             value,
         );
         (*parts.hdr).len = (cur_len + 1) as u16;
+        self.len += 1;
     }
     #[inline(always)]
     unsafe fn shift_and_write(
@@ -307,7 +331,7 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
         leaf: NonNull<u8>,
         key: K,
         value: V,
-    ) -> InsertResult<K, V> {
+    ) -> InsertResult<V> {
         let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
         let hdr = &mut *parts.hdr;
         let len = hdr.len as usize;
@@ -326,14 +350,14 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                 } else {
                     // Zero-allocation in-place split: move upper half to right, insert new item, clear moved slots
                     let total_items = len + 1;
-                    let left_count = total_items / 2;
+                    let left_count = self.leaf_split_left_count();
                     let right_count = total_items - left_count;
 
                     // Determine insertion position (idx from Err was computed above as `idx`)
                     let insert_pos = idx;
 
                     // Allocate right node and carve
-                    let right = alloc_leaf_block(&self.leaf_layout).expect("alloc right leaf");
+                    let right = self.new_leaf();
                     let r = layout::carve_leaf::<K, V>(right, &self.leaf_layout);
 
                     // Decide how many existing items remain on the left before insertion
@@ -401,25 +425,24 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                         (*r.hdr).len = (right_len + 1) as u16; // equals right_count
                     }
 
+                    self.len += 1;
+
                     // Link leaf siblings
-                    let left_next = parts.next_ptr;
-                    let old_next = *left_next;
-                    *left_next = right.as_ptr();
-                    if let Some(prev_ptr) = r.prev_ptr {
-                        *prev_ptr = leaf.as_ptr();
+                    let old_next = parts.next.get();
+                    parts.next.set(right.as_ptr());
+                    if let Some(prev_link) = r.prev {
+                        prev_link.set(leaf.as_ptr());
                     }
-                    let rnext = r.next_ptr;
-                    *rnext = old_next;
-                    if !old_next.is_null() {
-                        if let Some(prev_off) = self.leaf_layout.prev_off {
-                            let on_prev = (old_next.add(prev_off)) as *mut *mut u8;
-                            *on_prev = right.as_ptr();
+                    r.next.set(old_next);
+                    if let Some(old_next) = NonNull::new(old_next) {
+                        let n = layout::carve_leaf::<K, V>(old_next, &self.leaf_layout);
+                        if let Some(prev_link) = n.prev {
+                            prev_link.set(right.as_ptr());
                         }
                     }
 
-                    let sep = self.key_clone_at(r.keys_ptr as *const K, 0);
                     InsertResult::Split {
-                        sep_key: sep,
+                        sep_leaf: right,
                         right,
                         old_value: None,
                     }
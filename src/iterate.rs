@@ -1,178 +1,913 @@
-use alloc::vec::IntoIter;
-use alloc::vec::Vec;
+use core::borrow::Borrow;
 use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
 use core::ops::{Bound, RangeBounds};
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
 
-use crate::layout;
-use crate::{BPlusTreeMap, NodeHdr, NodeTag};
+use crate::layout::{self, BranchLayout, LeafLayout};
+use crate::node_alloc::layout_for;
+use crate::order::slot_for_rank;
+use crate::{BPlusTreeMap, Global, NodeAllocator, NodeHdr};
 
-pub struct Items<'a, K, V> {
-    pub(crate) inner: IntoIter<(&'a K, &'a V)>,
+/// Pair of cursors over the doubly-linked leaf chain.
+///
+/// `front` points at the next slot to yield and `back` one past the last slot.
+/// Both are kept normalized (a slot index equal to the leaf length is moved to
+/// slot 0 of the next leaf), so the range is exhausted exactly when they meet.
+#[derive(Copy, Clone)]
+pub(crate) struct LeafRange {
+    front: (*mut u8, usize),
+    back: (*mut u8, usize),
+    layout: LeafLayout,
 }
 
-impl<'a, K, V> Iterator for Items<'a, K, V> {
+impl LeafRange {
+    pub(crate) fn empty(layout: LeafLayout) -> Self {
+        Self {
+            front: (ptr::null_mut(), 0),
+            back: (ptr::null_mut(), 0),
+            layout,
+        }
+    }
+
+    /// Build a range between two (possibly unnormalized) leaf positions.
+    pub(crate) unsafe fn new(
+        front: (*mut u8, usize),
+        back: (*mut u8, usize),
+        layout: LeafLayout,
+    ) -> Self {
+        let mut range = Self::empty(layout);
+        range.front = range.normalize(front.0, front.1);
+        range.back = range.normalize(back.0, back.1);
+        range
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.front == self.back
+    }
+
+    #[inline(always)]
+    unsafe fn leaf_len(&self, leaf: *mut u8) -> usize {
+        (*(leaf as *const NodeHdr)).len as usize
+    }
+
+    #[inline(always)]
+    unsafe fn next_leaf(&self, leaf: *mut u8) -> *mut u8 {
+        self.layout.next_of(leaf)
+    }
+
+    #[inline(always)]
+    unsafe fn prev_leaf(&self, leaf: *mut u8) -> *mut u8 {
+        self.layout.prev_of(leaf)
+    }
+
+    unsafe fn normalize(&self, mut leaf: *mut u8, mut idx: usize) -> (*mut u8, usize) {
+        if leaf.is_null() {
+            return (leaf, 0);
+        }
+        while idx >= self.leaf_len(leaf) {
+            let next = self.next_leaf(leaf);
+            if next.is_null() {
+                break;
+            }
+            leaf = next;
+            idx = 0;
+        }
+        (leaf, idx)
+    }
+
+    /// Yield the front slot and advance past it.
+    #[inline]
+    pub(crate) unsafe fn next_slot(&mut self) -> Option<(*mut u8, usize)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (leaf, idx) = self.front;
+        self.front = self.normalize(leaf, idx + 1);
+        Some((leaf, idx))
+    }
+
+    /// Step the back cursor one slot towards the front and yield that slot.
+    #[inline]
+    pub(crate) unsafe fn next_back_slot(&mut self) -> Option<(*mut u8, usize)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (mut leaf, mut idx) = self.back;
+        while idx == 0 {
+            leaf = self.prev_leaf(leaf);
+            idx = self.leaf_len(leaf);
+        }
+        idx -= 1;
+        self.back = (leaf, idx);
+        Some((leaf, idx))
+    }
+
+    /// Next slot to yield from the front.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn front(&self) -> (*mut u8, usize) {
+        self.front
+    }
+
+    /// One past the last slot to yield.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn back(&self) -> (*mut u8, usize) {
+        self.back
+    }
+
+    /// Drop `n` slots from the front, a whole leaf at a time where possible.
+    pub(crate) unsafe fn advance_front(&mut self, mut n: usize) {
+        while n > 0 && !self.is_empty() {
+            let (leaf, idx) = self.front;
+            let last = leaf == self.back.0;
+            let avail = if last {
+                self.back.1
+            } else {
+                self.leaf_len(leaf)
+            } - idx;
+            if n < avail {
+                self.front = self.normalize(leaf, idx + n);
+                return;
+            }
+            n -= avail;
+            self.front = if last {
+                self.back
+            } else {
+                self.normalize(leaf, idx + avail)
+            };
+        }
+    }
+
+    /// Drop `n` slots from the back, a whole leaf at a time where possible.
+    pub(crate) unsafe fn retreat_back(&mut self, mut n: usize) {
+        while n > 0 && !self.is_empty() {
+            let (mut leaf, mut idx) = self.back;
+            if idx == 0 {
+                leaf = self.prev_leaf(leaf);
+                idx = self.leaf_len(leaf);
+            }
+            let first = leaf == self.front.0;
+            let avail = idx - if first { self.front.1 } else { 0 };
+            if n < avail {
+                self.back = (leaf, idx - n);
+                return;
+            }
+            n -= avail;
+            self.back = if first { self.front } else { (leaf, 0) };
+        }
+    }
+
+    /// Move the front cursor to `slot`, which must not lie past the back cursor.
+    pub(crate) unsafe fn seek_front(&mut self, slot: (*mut u8, usize)) {
+        self.front = self.normalize(slot.0, slot.1);
+    }
+
+    /// Move the back cursor to `slot`, which must not lie before the front cursor.
+    pub(crate) unsafe fn seek_back(&mut self, slot: (*mut u8, usize)) {
+        self.back = self.normalize(slot.0, slot.1);
+    }
+
+    #[inline(always)]
+    pub(crate) unsafe fn key_at<K>(&self, leaf: *mut u8, idx: usize) -> *mut K {
+        (leaf.add(self.layout.keys_off) as *mut K).add(idx)
+    }
+
+    #[inline(always)]
+    pub(crate) unsafe fn val_at<V>(&self, leaf: *mut u8, idx: usize) -> *mut V {
+        (leaf.add(self.layout.vals_off) as *mut V).add(idx)
+    }
+}
+
+/// Ranks of the remaining front and back (exclusive) of an iteration over a
+/// counted map, so its length and far positions are known without walking.
+#[derive(Copy, Clone)]
+pub(crate) struct RankSpan<'a> {
+    root: *mut u8,
+    layout: &'a BranchLayout,
+    front: usize,
+    back: usize,
+}
+
+pub struct Items<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) range: LeafRange,
+    pub(crate) span: Option<RankSpan<'a>>,
+    /// Pairs left to yield, when known without walking: always for the whole
+    /// map, and for any range of a counted map.
+    pub(crate) len: Option<usize>,
+    pub(crate) _marker: PhantomData<&'a BPlusTreeMap<K, V, A>>,
+}
+
+// SAFETY: the iterator reads the map's leaves, and through id links the arena's
+// slab table, like the `&BPlusTreeMap` it was made from.
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Send for Items<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for Items<'_, K, V, A> {}
+
+impl<K, V, A: NodeAllocator> Clone for Items<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Items {
+            range: self.range,
+            span: self.span,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> Items<'a, K, V, A> {
+    #[inline]
+    unsafe fn pair(&self, (leaf, idx): (*mut u8, usize)) -> (&'a K, &'a V) {
+        (
+            &*self.range.key_at::<K>(leaf, idx),
+            &*self.range.val_at::<V>(leaf, idx),
+        )
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> Iterator for Items<'a, K, V, A> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+        unsafe {
+            let slot = self.range.next_slot()?;
+            if let Some(len) = &mut self.len {
+                *len -= 1;
+            }
+            if let Some(span) = &mut self.span {
+                span.front += 1;
+            }
+            Some(self.pair(slot))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.len {
+            Some(len) => (len, Some(len)),
+            None => (0, None),
+        }
+    }
+
+    /// O(log n) on a counted map; otherwise whole leaves are skipped at a time.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        unsafe {
+            if let Some(len) = self.len {
+                if n >= len {
+                    self.len = Some(0);
+                    if let Some(span) = &mut self.span {
+                        span.front = span.back;
+                    }
+                    self.range.seek_front(self.range.back);
+                    return None;
+                }
+                self.len = Some(len - n);
+            }
+            match &mut self.span {
+                Some(span) => {
+                    span.front += n;
+                    let slot = slot_for_rank(span.root, span.layout, span.front);
+                    self.range.seek_front(slot);
+                }
+                None => self.range.advance_front(n),
+            }
+            self.next()
+        }
     }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Items<'a, K, V> {
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for Items<'a, K, V, A> {
     fn next_back(&mut self) -> Option<<Self as Iterator>::Item> {
-        self.inner.next_back()
+        unsafe {
+            let slot = self.range.next_back_slot()?;
+            if let Some(len) = &mut self.len {
+                *len -= 1;
+            }
+            if let Some(span) = &mut self.span {
+                span.back -= 1;
+            }
+            Some(self.pair(slot))
+        }
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        unsafe {
+            if let Some(len) = self.len {
+                if n >= len {
+                    self.len = Some(0);
+                    if let Some(span) = &mut self.span {
+                        span.back = span.front;
+                    }
+                    self.range.seek_back(self.range.front);
+                    return None;
+                }
+                self.len = Some(len - n);
+            }
+            match &mut self.span {
+                Some(span) => {
+                    span.back -= n;
+                    let (leaf, idx) = slot_for_rank(span.root, span.layout, span.back - 1);
+                    self.range.seek_back((leaf, idx + 1));
+                }
+                None => self.range.retreat_back(n),
+            }
+            self.next_back()
+        }
     }
 }
 
-pub struct Keys<'a, K, V> {
-    pub(crate) inner: IntoIter<&'a K>,
-    pub(crate) _marker: PhantomData<V>,
+// `Items` is only handed out over the whole map, whose length is always known.
+impl<K, V, A: NodeAllocator> ExactSizeIterator for Items<'_, K, V, A> {}
+
+/// Iterator over a range of a map. Only a counted map knows the length of a
+/// range up front; elsewhere `size_hint` has no upper bound.
+pub struct Range<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: Items<'a, K, V, A>,
 }
 
-impl<'a, K, V> Iterator for Keys<'a, K, V> {
-    type Item = &'a K;
+impl<K, V, A: NodeAllocator> Clone for Range<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Range {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> Iterator for Range<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth(n)
+    }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for Range<'a, K, V, A> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.inner.next_back()
     }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth_back(n)
+    }
 }
 
-pub struct Values<'a, K, V> {
-    pub(crate) inner: IntoIter<&'a V>,
-    pub(crate) _marker: PhantomData<K>,
+pub struct Keys<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: Items<'a, K, V, A>,
 }
 
-impl<'a, K, V> Iterator for Values<'a, K, V> {
+impl<'a, K, V, A: NodeAllocator> Iterator for Keys<'a, K, V, A> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth(n).map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for Keys<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth_back(n).map(|(k, _)| k)
+    }
+}
+
+impl<K, V, A: NodeAllocator> ExactSizeIterator for Keys<'_, K, V, A> {}
+
+pub struct Values<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: Items<'a, K, V, A>,
+}
+
+impl<'a, K, V, A: NodeAllocator> Iterator for Values<'a, K, V, A> {
     type Item = &'a V;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth(n).map(|(_, v)| v)
     }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for Values<'a, K, V, A> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        self.inner.next_back()
+        self.inner.next_back().map(|(_, v)| v)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth_back(n).map(|(_, v)| v)
     }
 }
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
-    pub fn items(&self) -> Items<'_, K, V> {
-        Items {
-            inner: self
-                .collect_range_bounds(Bound::Unbounded, Bound::Unbounded)
-                .into_iter(),
+impl<K, V, A: NodeAllocator> ExactSizeIterator for Values<'_, K, V, A> {}
+
+pub struct ItemsMut<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) range: LeafRange,
+    pub(crate) _marker: PhantomData<&'a mut BPlusTreeMap<K, V, A>>,
+}
+
+// SAFETY: the iterator hands out the map's values like the `&mut BPlusTreeMap`
+// it was made from.
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for ItemsMut<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for ItemsMut<'_, K, V, A> {}
+
+impl<'a, K, V, A: NodeAllocator> Iterator for ItemsMut<'a, K, V, A> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            let (leaf, idx) = self.range.next_slot()?;
+            Some((
+                &*self.range.key_at::<K>(leaf, idx),
+                &mut *self.range.val_at::<V>(leaf, idx),
+            ))
         }
     }
+}
 
-    pub fn keys(&self) -> Keys<'_, K, V> {
-        Keys::<K, V> {
-            inner: self.items().map(|(k, _)| k).collect::<Vec<_>>().into_iter(),
-            _marker: PhantomData,
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for ItemsMut<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        unsafe {
+            let (leaf, idx) = self.range.next_back_slot()?;
+            Some((
+                &*self.range.key_at::<K>(leaf, idx),
+                &mut *self.range.val_at::<V>(leaf, idx),
+            ))
         }
     }
+}
 
-    pub fn values(&self) -> Values<'_, K, V> {
-        Values::<K, V> {
-            inner: self.items().map(|(_, v)| v).collect::<Vec<_>>().into_iter(),
+pub struct ValuesMut<'a, K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: ItemsMut<'a, K, V, A>,
+}
+
+impl<'a, K, V, A: NodeAllocator> Iterator for ValuesMut<'a, K, V, A> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V, A: NodeAllocator> DoubleEndedIterator for ValuesMut<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+/// Owning iterator over the pairs of a [`BPlusTreeMap`].
+///
+/// Branch nodes are released up front; leaves are read with `ptr::read` from both
+/// ends and freed as soon as a cursor moves off them. Slots before `front.1` and
+/// from `back.1` on are already moved out of their leaves.
+pub struct IntoIter<K, V, A: NodeAllocator = Global> {
+    front: (*mut u8, usize),
+    back: (*mut u8, usize),
+    leaf_layout: LeafLayout,
+    alloc: A,
+    _marker: PhantomData<(K, V)>,
+}
+
+// SAFETY: the iterator owns the remaining leaves and the pairs in them.
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for IntoIter<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for IntoIter<K, V, A> {}
+
+impl<K, V, A: NodeAllocator> IntoIter<K, V, A> {
+    #[inline(always)]
+    unsafe fn leaf_len(&self, leaf: *mut u8) -> usize {
+        (*(leaf as *const NodeHdr)).len as usize
+    }
+
+    #[inline(always)]
+    unsafe fn read_at(&self, leaf: *mut u8, idx: usize) -> (K, V) {
+        let parts = layout::carve_leaf::<K, V>(NonNull::new_unchecked(leaf), &self.leaf_layout);
+        (
+            ptr::read(parts.keys_ptr.add(idx) as *const K),
+            ptr::read(parts.vals_ptr.add(idx) as *const V),
+        )
+    }
+
+    #[inline(always)]
+    unsafe fn free_leaf(&self, leaf: *mut u8) {
+        let layout = layout_for(self.leaf_layout.bytes, self.leaf_layout.max_align);
+        self.alloc.deallocate(NonNull::new_unchecked(leaf), layout);
+    }
+}
+
+impl<K, V, A: NodeAllocator> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            loop {
+                if self.front == self.back {
+                    return None;
+                }
+                let (leaf, idx) = self.front;
+                if idx < self.leaf_len(leaf) {
+                    self.front.1 += 1;
+                    return Some(self.read_at(leaf, idx));
+                }
+                // Exhausted a leaf that the back cursor has not reached.
+                let next = self.leaf_layout.next_of(leaf);
+                self.free_leaf(leaf);
+                self.front = (next, 0);
+            }
+        }
+    }
+}
+
+impl<K, V, A: NodeAllocator> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        unsafe {
+            loop {
+                if self.front == self.back {
+                    return None;
+                }
+                let (leaf, idx) = self.back;
+                if idx > 0 {
+                    self.back.1 -= 1;
+                    return Some(self.read_at(leaf, idx - 1));
+                }
+                assert!(
+                    self.leaf_layout.prev_off.is_some(),
+                    "leaves are doubly linked"
+                );
+                let prev = self.leaf_layout.prev_of(leaf);
+                self.free_leaf(leaf);
+                self.back = (prev, self.leaf_len(prev));
+            }
+        }
+    }
+}
+
+impl<K, V, A: NodeAllocator> Drop for IntoIter<K, V, A> {
+    fn drop(&mut self) {
+        // Keep draining (and freeing leaves) even if dropping a K or V panics.
+        struct DropGuard<'a, K, V, A: NodeAllocator>(&'a mut IntoIter<K, V, A>);
+
+        impl<K, V, A: NodeAllocator> Drop for DropGuard<'_, K, V, A> {
+            fn drop(&mut self) {
+                for pair in self.0.by_ref() {
+                    drop(pair);
+                }
+                unsafe { self.0.release_last_leaf() };
+            }
+        }
+
+        while let Some(pair) = self.next() {
+            let guard = DropGuard(self);
+            drop(pair);
+            mem::forget(guard);
+        }
+        unsafe { self.release_last_leaf() };
+    }
+}
+
+impl<K, V, A: NodeAllocator> IntoIter<K, V, A> {
+    /// Free the single leaf both cursors rest on once the iterator is exhausted.
+    unsafe fn release_last_leaf(&mut self) {
+        let leaf = mem::replace(&mut self.front.0, ptr::null_mut());
+        self.back = (ptr::null_mut(), 0);
+        self.front.1 = 0;
+        if !leaf.is_null() {
+            self.free_leaf(leaf);
+        }
+    }
+}
+
+pub struct IntoKeys<K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: IntoIter<K, V, A>,
+}
+
+impl<K, V, A: NodeAllocator> Iterator for IntoKeys<K, V, A> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+}
+
+impl<K, V, A: NodeAllocator> DoubleEndedIterator for IntoKeys<K, V, A> {
+    fn next_back(&mut self) -> Option<K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+pub struct IntoValues<K, V, A: NodeAllocator = Global> {
+    pub(crate) inner: IntoIter<K, V, A>,
+}
+
+impl<K, V, A: NodeAllocator> Iterator for IntoValues<K, V, A> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, A: NodeAllocator> DoubleEndedIterator for IntoValues<K, V, A> {
+    fn next_back(&mut self) -> Option<V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> IntoIterator for BPlusTreeMap<K, V, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    fn into_iter(mut self) -> IntoIter<K, V, A> {
+        let front = self
+            .leftmost_leaf()
+            .map_or(ptr::null_mut(), NonNull::as_ptr);
+        let back = match self.rightmost_leaf() {
+            Some(leaf) => unsafe {
+                (
+                    leaf.as_ptr(),
+                    (*(leaf.as_ptr() as *const NodeHdr)).len as usize,
+                )
+            },
+            None => (ptr::null_mut(), 0),
+        };
+        if let Some(root) = self.root.take() {
+            unsafe { self.free_branches_keep_leaves(root) };
+        }
+        // The leaves now belong to the iterator, which takes over the allocator.
+        let mut this = ManuallyDrop::new(self);
+        let alloc = unsafe {
+            this.release_pool();
+            ptr::read(&this.alloc)
+        };
+        IntoIter {
+            front: (front, 0),
+            back,
+            leaf_layout: this.leaf_layout,
+            alloc,
             _marker: PhantomData,
         }
     }
+}
+
+impl<'a, K: Ord, V, A: NodeAllocator> IntoIterator for &'a BPlusTreeMap<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Items<'a, K, V, A>;
 
-    pub fn items_range(&self, start: Option<&K>, end: Option<&K>) -> Items<'_, K, V> {
+    fn into_iter(self) -> Items<'a, K, V, A> {
+        self.items()
+    }
+}
+
+impl<'a, K: Ord, V, A: NodeAllocator> IntoIterator for &'a mut BPlusTreeMap<K, V, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = ItemsMut<'a, K, V, A>;
+
+    fn into_iter(self) -> ItemsMut<'a, K, V, A> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    pub fn items(&self) -> Items<'_, K, V, A> {
+        self.items_between(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V, A> {
+        Keys {
+            inner: self.items(),
+        }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V, A> {
+        Values {
+            inner: self.items(),
+        }
+    }
+
+    pub fn items_range(&self, start: Option<&K>, end: Option<&K>) -> Range<'_, K, V, A> {
         let sb = start.map_or(Bound::Unbounded, Bound::Included);
         let eb = end.map_or(Bound::Unbounded, Bound::Excluded);
-        Items {
-            inner: self.collect_range_bounds(sb, eb).into_iter(),
+        Range {
+            inner: self.items_between(sb, eb),
         }
     }
 
-    pub fn range<R: RangeBounds<K>>(&self, r: R) -> Items<'_, K, V> {
-        Items {
-            inner: self
-                .collect_range_bounds(r.start_bound(), r.end_bound())
-                .into_iter(),
+    pub fn range<Q, R>(&self, r: R) -> Range<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            inner: self.items_between(r.start_bound(), r.end_bound()),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> ItemsMut<'_, K, V, A> {
+        ItemsMut {
+            range: self.leaf_range(Bound::Unbounded, Bound::Unbounded),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, A> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    pub fn range_mut<Q, R>(&mut self, r: R) -> ItemsMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+        R: RangeBounds<Q>,
+    {
+        ItemsMut {
+            range: self.leaf_range(r.start_bound(), r.end_bound()),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V, A> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V, A> {
+        IntoValues {
+            inner: self.into_iter(),
         }
     }
 
     pub fn first(&self) -> Option<(&K, &V)> {
-        self.items().next()
+        self.first_key_value()
     }
 
     pub fn last(&self) -> Option<(&K, &V)> {
-        self.items().last()
-    }
-
-    pub(crate) fn collect_range_bounds<'a>(
-        &'a self,
-        start: Bound<&K>,
-        end: Bound<&K>,
-    ) -> Vec<(&'a K, &'a V)> {
-        let mut out = Vec::new();
-        let leaf_ptr = match start {
-            Bound::Unbounded => self.leftmost_leaf(),
-            Bound::Included(k) | Bound::Excluded(k) => self.leaf_for_key(k),
-        };
-        if leaf_ptr.is_none() {
-            return out;
+        self.last_key_value()
+    }
+
+    /// Smallest key and its value, found by descending the leftmost spine.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let leaf = self.leftmost_leaf()?;
+        unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            if (*parts.hdr).len == 0 {
+                return None;
+            }
+            Some((
+                &*(parts.keys_ptr as *const K),
+                &*(parts.vals_ptr as *const V),
+            ))
         }
+    }
+
+    /// Largest key and its value, found by descending the rightmost spine.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let leaf = self.rightmost_leaf()?;
         unsafe {
-            let mut cur = leaf_ptr.unwrap().as_ptr();
-            let mut first_idx = 0usize;
-            if let Bound::Included(s) | Bound::Excluded(s) = start {
-                let parts =
-                    layout::carve_leaf::<K, V>(NonNull::new_unchecked(cur), &self.leaf_layout);
-                let len = (*parts.hdr).len as usize;
-                let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
-                match self.binary_search_keys(keys, s) {
-                    Ok(i) => {
-                        first_idx = if matches!(start, Bound::Excluded(_)) {
-                            i + 1
-                        } else {
-                            i
-                        };
-                    }
-                    Err(i) => {
-                        first_idx = i;
-                    }
-                }
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            if len == 0 {
+                return None;
             }
-            loop {
-                if cur.is_null() {
-                    break;
-                }
-                let hdr = &*(cur as *const NodeHdr);
-                if hdr.tag != NodeTag::Leaf {
-                    break;
-                }
-                let len = hdr.len as usize;
-                let keys_ptr = (cur.add(self.leaf_layout.keys_off)) as *const K;
-                let vals_ptr = (cur.add(self.leaf_layout.vals_off)) as *const V;
-                for i in first_idx..len {
-                    let kref = &*keys_ptr.add(i);
-                    let end_ok = match end {
-                        Bound::Unbounded => true,
-                        Bound::Included(e) => kref <= e,
-                        Bound::Excluded(e) => kref < e,
-                    };
-                    if !end_ok {
-                        return out;
-                    }
-                    let vref = &*vals_ptr.add(i);
-                    out.push((kref, vref));
-                }
-                first_idx = 0;
-                let next_ptr = (cur.add(self.leaf_layout.next_off)) as *const *mut u8;
-                cur = *next_ptr;
+            Some((
+                &*(parts.keys_ptr.add(len - 1) as *const K),
+                &*(parts.vals_ptr.add(len - 1) as *const V),
+            ))
+        }
+    }
+
+    /// Iterator over `start..end`; on a counted map it also tracks the ranks of
+    /// both ends, which give its length. Otherwise the length is only known for
+    /// the whole map.
+    fn items_between<Q>(&self, start: Bound<&Q>, end: Bound<&Q>) -> Items<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let range = self.leaf_range(start, end);
+        let span = match self.root {
+            Some(root) if self.is_counted() => {
+                let (front, back) = if range.is_empty() {
+                    (0, 0)
+                } else {
+                    (self.bound_rank(start, false), self.bound_rank(end, true))
+                };
+                Some(RankSpan {
+                    root: root.as_ptr(),
+                    layout: &self.branch_layout,
+                    front,
+                    back,
+                })
             }
+            _ => None,
+        };
+        let len = match (&span, start, end) {
+            (Some(span), _, _) => Some(span.back - span.front),
+            (None, Bound::Unbounded, Bound::Unbounded) => Some(self.len),
+            (None, _, _) => None,
+        };
+        Items {
+            range,
+            span,
+            len,
+            _marker: PhantomData,
         }
-        out
+    }
+
+    /// Locate the leaf positions delimiting `start..end` without touching any items.
+    pub(crate) fn leaf_range<Q>(&self, start: Bound<&Q>, end: Bound<&Q>) -> LeafRange
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        if bounds_are_empty(start, end) {
+            return LeafRange::empty(self.leaf_layout);
+        }
+
+        let front = match start {
+            Bound::Unbounded => self.leftmost_leaf().map(|leaf| (leaf.as_ptr(), 0)),
+            Bound::Included(s) => self.leaf_position(s, false),
+            Bound::Excluded(s) => self.leaf_position(s, true),
+        };
+        let back = match end {
+            Bound::Unbounded => self.rightmost_leaf().map(|leaf| unsafe {
+                (
+                    leaf.as_ptr(),
+                    (*(leaf.as_ptr() as *const NodeHdr)).len as usize,
+                )
+            }),
+            Bound::Included(e) => self.leaf_position(e, true),
+            Bound::Excluded(e) => self.leaf_position(e, false),
+        };
+
+        match (front, back) {
+            (Some(front), Some(back)) => unsafe { LeafRange::new(front, back, self.leaf_layout) },
+            _ => LeafRange::empty(self.leaf_layout),
+        }
+    }
+
+    /// Leaf and slot of the first key `>= key` (or `> key` when `after_equal`).
+    pub(crate) fn leaf_position<Q>(&self, key: &Q, after_equal: bool) -> Option<(*mut u8, usize)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let leaf = self.leaf_for_key(key)?;
+        unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            let len = (*parts.hdr).len as usize;
+            let keys = core::slice::from_raw_parts(parts.keys_ptr as *const K, len);
+            let idx = match self.binary_search_keys(keys, key) {
+                Ok(i) if after_equal => i + 1,
+                Ok(i) | Err(i) => i,
+            };
+            Some((leaf.as_ptr(), idx))
+        }
+    }
+}
+
+/// True when no key can satisfy both bounds (an inverted or degenerate range).
+pub(crate) fn bounds_are_empty<Q: ?Sized + Ord>(start: Bound<&Q>, end: Bound<&Q>) -> bool {
+    if let (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e)) =
+        (start, end)
+    {
+        let both_included = matches!((start, end), (Bound::Included(_), Bound::Included(_)));
+        return s > e || (s == e && !both_included);
+    }
+    false
+}
+
+/// True when `key` does not lie beyond the `end` bound.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn below_end<K: Borrow<Q>, Q: ?Sized + Ord>(end: Bound<&Q>, key: &K) -> bool {
+    match end {
+        Bound::Unbounded => true,
+        Bound::Included(e) => key.borrow() <= e,
+        Bound::Excluded(e) => key.borrow() < e,
     }
 }
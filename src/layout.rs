@@ -1,6 +1,10 @@
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 use core::mem::{align_of, size_of};
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
+
+use crate::arena::SlabDir;
+use crate::NULL_NODE;
 
 #[inline]
 pub const fn align_up(x: usize, a: usize) -> usize {
@@ -19,54 +23,147 @@ pub enum NodeTag {
 #[derive(Copy, Clone, Debug)]
 pub struct NodeHdr {
     pub tag: NodeTag, // 1 byte
+    pub flags: u8,    // `VACANT`, or zero for a node in use
     pub len: u16,     // number of initialized keys in this node
-    pub flags: u8,    // reserved
+    /// Arena id of the node when its map links nodes by id, zero otherwise.
+    pub id: u32,
 }
 
-#[derive(Copy, Clone, Debug)]
+impl NodeHdr {
+    /// `flags` of a block that holds no node: fresh from an arena, or pooled.
+    pub const VACANT: u8 = 1;
+}
+
+/// How nodes refer to their children and sibling leaves: by address, or by the
+/// 32-bit id a [`SlabArena`](crate::SlabArena) gave them.
+///
+/// Every map takes its links from its allocator; see
+/// [`NodeAllocator::node_links`](crate::NodeAllocator::node_links).
+///
+/// Id links read the arena's slab table, which the arena updates through `&self`,
+/// so links (and the layouts holding them) are neither `Send` nor `Sync`. Maps and
+/// their iterators take those bounds from the allocator instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NodeLinks {
+    /// Slab table that ids are looked up in, or `None` for pointer links.
+    slabs: Option<NonNull<SlabDir>>,
+}
+
+impl NodeLinks {
+    /// Links that hold node addresses.
+    pub const POINTERS: Self = Self { slabs: None };
+
+    pub(crate) fn ids(slabs: NonNull<SlabDir>) -> Self {
+        Self { slabs: Some(slabs) }
+    }
+
+    /// Whether links are 32-bit arena ids.
+    #[inline]
+    pub fn is_compact(&self) -> bool {
+        self.slabs.is_some()
+    }
+
+    /// Size and alignment of one link.
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        if self.is_compact() {
+            size_of::<u32>()
+        } else {
+            size_of::<*const ()>()
+        }
+    }
+
+    /// The node a link slot refers to, or null.
+    #[inline(always)]
+    unsafe fn load(self, slot: *const u8) -> *mut u8 {
+        match self.slabs {
+            None => *(slot as *const *mut u8),
+            Some(slabs) => slabs.as_ref().node_at(*(slot as *const u32)),
+        }
+    }
+
+    /// Point a link slot at `node`, which may be null.
+    #[inline(always)]
+    unsafe fn store(self, slot: *mut u8, node: *mut u8) {
+        match self.slabs {
+            None => *(slot as *mut *mut u8) = node,
+            Some(_) => {
+                *(slot as *mut u32) = if node.is_null() {
+                    NULL_NODE
+                } else {
+                    (*(node as *const NodeHdr)).id
+                }
+            }
+        }
+    }
+
+    /// The slab table ids are looked up in, for maps that link by id.
+    pub(crate) fn slabs(self) -> Option<NonNull<SlabDir>> {
+        self.slabs
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct LeafLayout {
     pub bytes: usize,
     pub cap: u16,
     pub max_align: usize,
     pub hdr_size: usize,
-    // sibling pointers
+    // sibling links
     pub next_off: usize,
     pub prev_off: Option<usize>,
     // arrays
     pub keys_off: usize,
     pub vals_off: usize,
+    /// How the sibling links refer to other leaves.
+    pub links: NodeLinks,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct BranchLayout {
     pub bytes: usize,
     pub cap: u16,
     pub max_align: usize,
     pub hdr_size: usize,
-    pub children_off: usize, // [*mut NodeHdr; cap+1]
-    pub keys_off: usize,     // [K; cap]
+    pub children_off: usize, // [link; cap+1]
+    pub seps_off: usize,     // [link; cap], first leaf of each right subtree
+    /// [usize; cap+1] pairs under each child, in counted layouts only.
+    pub counts_off: Option<usize>,
+    /// How the child and separator links refer to other nodes.
+    pub links: NodeLinks,
 }
 
 impl LeafLayout {
     /// Compute a leaf layout for key type K and value type V.
     /// If `doubly_linked` is true, include space for both next and prev pointers.
+    /// A zero-sized `V` (as in a set) takes no space: the value array aliases the
+    /// key array and capacity depends on the key size alone.
     pub fn compute<K, V>(bytes: usize, doubly_linked: bool) -> Self {
-        let a_ptr = align_of::<*const ()>();
+        Self::compute_linked::<K, V>(bytes, doubly_linked, NodeLinks::POINTERS)
+    }
+
+    /// Like [`Self::compute`], for leaves linked by `links`. Id links take half
+    /// the room of pointers, and can leave a key or two more.
+    pub fn compute_linked<K, V>(bytes: usize, doubly_linked: bool, links: NodeLinks) -> Self {
+        let a_link = links.bytes();
         let a_k = align_of::<K>();
         let a_v = align_of::<V>();
-        let s_ptr = size_of::<*const ()>();
+        let s_link = links.bytes();
         let s_k = size_of::<K>();
         let s_v = size_of::<V>();
 
-        let max_align = a_ptr.max(a_k).max(a_v).max(align_of::<NodeHdr>());
+        let max_align = a_link.max(a_k).max(a_v).max(align_of::<NodeHdr>());
         let hdr_size = align_up(size_of::<NodeHdr>(), max_align);
 
-        let sib_bytes = if doubly_linked { 2 * s_ptr } else { s_ptr };
-        let sib_off = align_up(hdr_size, a_ptr);
+        let sib_bytes = if doubly_linked { 2 * s_link } else { s_link };
+        let sib_off = align_up(hdr_size, a_link);
         let after_sib = sib_off + sib_bytes;
 
         // quick upper bound, ignoring alignment between arrays
-        let mut cap_guess = bytes.saturating_sub(after_sib) / (s_k + s_v);
+        let mut cap_guess = bytes
+            .saturating_sub(after_sib)
+            .checked_div(s_k + s_v)
+            .unwrap_or(0);
         if cap_guess > u16::MAX as usize {
             cap_guess = u16::MAX as usize;
         }
@@ -78,12 +175,13 @@ impl LeafLayout {
             hdr_size,
             next_off: sib_off,
             prev_off: if doubly_linked {
-                Some(sib_off + s_ptr)
+                Some(sib_off + s_link)
             } else {
                 None
             },
             keys_off: after_sib,
             vals_off: after_sib,
+            links,
         };
 
         while cap_guess > 0 {
@@ -107,7 +205,7 @@ impl LeafLayout {
                     (second_off, first_off)
                 };
                 best.keys_off = keys_off;
-                best.vals_off = vals_off;
+                best.vals_off = if s_v == 0 { keys_off } else { vals_off };
                 return best;
             }
             cap_guess -= 1;
@@ -118,18 +216,23 @@ impl LeafLayout {
 
     /// Compute a leaf layout targeting an exact capacity (number of key/value pairs).
     pub fn compute_for_cap<K, V>(cap: u16, doubly_linked: bool) -> Self {
-        let a_ptr = align_of::<*const ()>();
+        Self::compute_for_cap_linked::<K, V>(cap, doubly_linked, NodeLinks::POINTERS)
+    }
+
+    /// Like [`Self::compute_for_cap`], for leaves linked by `links`.
+    pub fn compute_for_cap_linked<K, V>(cap: u16, doubly_linked: bool, links: NodeLinks) -> Self {
+        let a_link = links.bytes();
         let a_k = align_of::<K>();
         let a_v = align_of::<V>();
-        let s_ptr = size_of::<*const ()>();
+        let s_link = links.bytes();
         let s_k = size_of::<K>();
         let s_v = size_of::<V>();
 
-        let max_align = a_ptr.max(a_k).max(a_v).max(align_of::<NodeHdr>());
+        let max_align = a_link.max(a_k).max(a_v).max(align_of::<NodeHdr>());
         let hdr_size = align_up(size_of::<NodeHdr>(), max_align);
 
-        let sib_bytes = if doubly_linked { 2 * s_ptr } else { s_ptr };
-        let sib_off = align_up(hdr_size, a_ptr);
+        let sib_bytes = if doubly_linked { 2 * s_link } else { s_link };
+        let sib_off = align_up(hdr_size, a_link);
         let after_sib = sib_off + sib_bytes;
 
         let cap_usize = cap as usize;
@@ -150,6 +253,7 @@ impl LeafLayout {
         } else {
             (second_off, first_off)
         };
+        let vals_off = if s_v == 0 { keys_off } else { vals_off };
 
         Self {
             bytes: end_aligned,
@@ -158,146 +262,231 @@ impl LeafLayout {
             hdr_size,
             next_off: sib_off,
             prev_off: if doubly_linked {
-                Some(sib_off + s_ptr)
+                Some(sib_off + s_link)
             } else {
                 None
             },
             keys_off,
             vals_off,
+            links,
+        }
+    }
+}
+
+impl LeafLayout {
+    /// The leaf after `leaf`, or null.
+    #[inline(always)]
+    pub(crate) unsafe fn next_of(&self, leaf: *mut u8) -> *mut u8 {
+        self.links.load(leaf.add(self.next_off))
+    }
+
+    /// The leaf before `leaf`, or null; always null for singly linked leaves.
+    #[inline(always)]
+    pub(crate) unsafe fn prev_of(&self, leaf: *mut u8) -> *mut u8 {
+        match self.prev_off {
+            Some(off) => self.links.load(leaf.add(off)),
+            None => ptr::null_mut(),
         }
     }
 }
 
 impl BranchLayout {
-    /// Compute a branch layout for key type K.
-    pub fn compute<K>(bytes: usize) -> Self {
-        let a_ptr = align_of::<*const ()>();
-        let a_k = align_of::<K>();
-        let s_ptr = size_of::<*const ()>();
-        let s_k = size_of::<K>();
+    /// Compute a branch layout fitting in `bytes`.
+    ///
+    /// Separators are links to the leaf holding the first key of the subtree to
+    /// their right, so the layout does not depend on the key type.
+    pub fn compute(bytes: usize) -> Self {
+        Self::compute_linked(bytes, false, NodeLinks::POINTERS)
+    }
+
+    /// Compute a branch layout that also stores the number of pairs under each
+    /// child, for order statistics. Fewer separators fit in the same budget.
+    pub fn compute_counted(bytes: usize) -> Self {
+        Self::compute_linked(bytes, true, NodeLinks::POINTERS)
+    }
+
+    /// Compute a branch layout targeting an exact capacity (number of separators).
+    pub fn compute_for_cap(cap: u16) -> Self {
+        Self::compute_for_cap_linked(cap, false, NodeLinks::POINTERS)
+    }
+
+    /// Compute a counted branch layout targeting an exact capacity (number of
+    /// separators).
+    pub fn compute_for_cap_counted(cap: u16) -> Self {
+        Self::compute_for_cap_linked(cap, true, NodeLinks::POINTERS)
+    }
 
-        let max_align = a_ptr.max(a_k).max(align_of::<NodeHdr>());
+    /// Whether branches store per-child subtree sizes.
+    #[inline]
+    pub fn is_counted(&self) -> bool {
+        self.counts_off.is_some()
+    }
+
+    /// Child `i` of `branch`.
+    #[inline(always)]
+    pub(crate) unsafe fn child_of(&self, branch: *mut u8, i: usize) -> *mut u8 {
+        self.links
+            .load(branch.add(self.children_off + i * self.links.bytes()))
+    }
+
+    /// Compute a branch layout, counted or not, whose children and separators are
+    /// linked by `links`. Id links leave room for more children in the same budget.
+    pub fn compute_linked(bytes: usize, counted: bool, links: NodeLinks) -> Self {
+        let max_align = Self::max_align(counted, links);
         let hdr_size = align_up(size_of::<NodeHdr>(), max_align);
+        // Each child takes a link, plus a count when counted.
+        let s_child = child_slot_size(counted, links);
 
-        // quick upper bound ignoring alignment: children (cap+1) pointers + cap keys
-        let mut cap_guess = if s_k + s_ptr == 0 {
-            0
-        } else {
-            bytes.saturating_sub(hdr_size) / (s_k + s_ptr)
-        };
-        if cap_guess > u16::MAX as usize {
-            cap_guess = u16::MAX as usize;
+        // quick upper bound ignoring alignment: children (cap+1) slots + cap separators
+        let mut cap_guess = (bytes.saturating_sub(hdr_size + s_child) / (s_child + links.bytes()))
+            .min(u16::MAX as usize);
+        while cap_guess > 0 {
+            let fit = Self::compute_for_cap_linked(cap_guess as u16, counted, links);
+            if fit.bytes <= bytes {
+                return Self { bytes, ..fit };
+            }
+            cap_guess -= 1;
         }
 
-        // Children usually have higher alignment; place higher-aligned first.
-        let children_first = a_ptr >= a_k;
-
         // Defaults if nothing fits
-        let mut best = Self {
+        Self {
             bytes,
             cap: 0,
             max_align,
             hdr_size,
             children_off: hdr_size,
-            keys_off: hdr_size,
-        };
-
-        while cap_guess > 0 {
-            let first_a = if children_first { a_ptr } else { a_k };
-            let first_s = if children_first { s_ptr } else { s_k };
-            let first_len = if children_first {
-                cap_guess + 1
-            } else {
-                cap_guess
-            };
-
-            let second_a = if children_first { a_k } else { a_ptr };
-            let second_s = if children_first { s_k } else { s_ptr };
-            let second_len = if children_first {
-                cap_guess
-            } else {
-                cap_guess + 1
-            };
-
-            let first_off = align_up(hdr_size, first_a);
-            let second_off = align_up(first_off + first_len * first_s, second_a);
-            let end = second_off + second_len * second_s;
-            let end_aligned = align_up(end, max_align);
-
-            if end_aligned <= bytes {
-                best.cap = cap_guess as u16;
-                if children_first {
-                    best.children_off = first_off;
-                    best.keys_off = second_off;
-                } else {
-                    best.children_off = second_off;
-                    best.keys_off = first_off;
-                }
-                return best;
-            }
-            cap_guess -= 1;
+            seps_off: hdr_size,
+            counts_off: None,
+            links,
         }
-
-        best
     }
 
-    /// Compute a branch layout targeting an exact capacity (number of keys).
-    pub fn compute_for_cap<K>(cap: u16) -> Self {
-        let a_ptr = align_of::<*const ()>();
-        let a_k = align_of::<K>();
-        let s_ptr = size_of::<*const ()>();
-        let s_k = size_of::<K>();
-        let max_align = a_ptr.max(a_k).max(align_of::<NodeHdr>());
+    /// Like [`Self::compute_linked`], targeting an exact capacity.
+    pub fn compute_for_cap_linked(cap: u16, counted: bool, links: NodeLinks) -> Self {
+        let max_align = Self::max_align(counted, links);
         let hdr_size = align_up(size_of::<NodeHdr>(), max_align);
-
-        let children_first = a_ptr >= a_k;
-        let first_a = if children_first { a_ptr } else { a_k };
-        let first_s = if children_first { s_ptr } else { s_k };
-        let first_len = if children_first {
-            cap as usize + 1
-        } else {
-            cap as usize
-        };
-
-        let second_a = if children_first { a_k } else { a_ptr };
-        let second_s = if children_first { s_k } else { s_ptr };
-        let second_len = if children_first {
-            cap as usize
-        } else {
-            cap as usize + 1
-        };
-
-        let first_off = align_up(hdr_size, first_a);
-        let second_off = align_up(first_off + first_len * first_s, second_a);
-        let end = second_off + second_len * second_s;
-        let end_aligned = align_up(end, max_align);
-
-        let (children_off, keys_off) = if children_first {
-            (first_off, second_off)
+        let s_link = links.bytes();
+
+        // Separators follow the children, and counts, when present, start on a
+        // usize boundary after the separators.
+        let children_off = align_up(hdr_size, s_link);
+        let seps_off = children_off + (cap as usize + 1) * s_link;
+        let seps_end = seps_off + cap as usize * s_link;
+        let (counts_off, end) = if counted {
+            let counts_off = align_up(seps_end, align_of::<usize>());
+            (
+                Some(counts_off),
+                counts_off + (cap as usize + 1) * size_of::<usize>(),
+            )
         } else {
-            (second_off, first_off)
+            (None, seps_end)
         };
 
         Self {
-            bytes: end_aligned,
+            bytes: align_up(end, max_align),
             cap,
             max_align,
             hdr_size,
             children_off,
-            keys_off,
+            seps_off,
+            counts_off,
+            links,
         }
     }
+
+    fn max_align(counted: bool, links: NodeLinks) -> usize {
+        let a_counts = if counted { align_of::<usize>() } else { 1 };
+        links.bytes().max(a_counts).max(align_of::<NodeHdr>())
+    }
+}
+
+#[inline]
+fn child_slot_size(counted: bool, links: NodeLinks) -> usize {
+    if counted {
+        links.bytes() + size_of::<usize>()
+    } else {
+        links.bytes()
+    }
 }
 
 // ============================
 // Raw carving helpers
 // ============================
 
+/// One link slot of a node, read and written through the node's [`NodeLinks`].
+#[derive(Copy, Clone)]
+pub struct Link {
+    slot: *mut u8,
+    links: NodeLinks,
+}
+
+impl Link {
+    /// The node linked to, or null.
+    #[inline(always)]
+    pub unsafe fn get(self) -> *mut u8 {
+        self.links.load(self.slot)
+    }
+
+    /// Link to `node`, or to nothing if it is null.
+    #[inline(always)]
+    pub unsafe fn set(self, node: *mut u8) {
+        self.links.store(self.slot, node)
+    }
+}
+
+/// A link array of a branch: its children, or its separators.
+#[derive(Copy, Clone)]
+pub struct Children {
+    base: *mut u8,
+    links: NodeLinks,
+}
+
+impl Children {
+    #[inline(always)]
+    fn slot(self, i: usize) -> *mut u8 {
+        self.base.wrapping_add(i * self.links.bytes())
+    }
+
+    /// Child `i`, or null for an empty slot.
+    #[inline(always)]
+    pub unsafe fn get(self, i: usize) -> *mut u8 {
+        self.links.load(self.slot(i))
+    }
+
+    /// Point slot `i` at `node`, which may be null.
+    #[inline(always)]
+    pub unsafe fn set(self, i: usize, node: *mut u8) {
+        self.links.store(self.slot(i), node)
+    }
+
+    /// Move `n` links from slot `from` to slot `to`; the ranges may overlap.
+    #[inline]
+    pub unsafe fn shift(self, from: usize, to: usize, n: usize) {
+        ptr::copy(self.slot(from), self.slot(to), n * self.links.bytes());
+    }
+
+    /// Copy `n` links from slot `from` into slot `to` of another branch linked
+    /// the same way.
+    #[inline]
+    pub unsafe fn copy_to(self, from: usize, dst: Children, to: usize, n: usize) {
+        debug_assert_eq!(self.links, dst.links);
+        ptr::copy_nonoverlapping(self.slot(from), dst.slot(to), n * self.links.bytes());
+    }
+
+    /// Empty `n` slots from slot `i`.
+    #[inline]
+    pub unsafe fn clear(self, i: usize, n: usize) {
+        // A null pointer is all zeros and the null id all ones.
+        let fill = if self.links.is_compact() { 0xFF } else { 0 };
+        ptr::write_bytes(self.slot(i), fill, n * self.links.bytes());
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct LeafParts<K, V> {
     pub hdr: *mut NodeHdr,
-    pub next_ptr: *mut *mut u8,
-    pub prev_ptr: Option<*mut *mut u8>,
+    pub next: Link,
+    pub prev: Option<Link>,
     pub keys_ptr: *mut MaybeUninit<K>,
     pub vals_ptr: *mut MaybeUninit<V>,
 }
@@ -307,40 +496,56 @@ impl<K, V> LeafParts<K, V> {}
 #[derive(Copy, Clone)]
 pub struct BranchParts<K> {
     pub hdr: *mut NodeHdr,
-    pub children_ptr: *mut MaybeUninit<*mut u8>,
-    pub keys_ptr: *mut MaybeUninit<K>,
+    pub children: Children,
+    /// Separator `i` links to the leaf whose first key bounds child `i + 1` from below.
+    pub seps: Children,
+    _key: PhantomData<K>,
 }
 
 impl<K> BranchParts<K> {}
 
-/// Carve a leaf node's header, sibling pointers, and arrays from a raw base pointer.
+/// Carve a leaf node's header, sibling links, and arrays from a raw base pointer.
 #[inline(always)]
 pub unsafe fn carve_leaf<K, V>(base: NonNull<u8>, layout: &LeafLayout) -> LeafParts<K, V> {
     let p = base.as_ptr();
     let hdr = p as *mut NodeHdr;
-    let next_ptr = p.add(layout.next_off) as *mut *mut u8;
-    let prev_ptr = layout.prev_off.map(|off| p.add(off) as *mut *mut u8);
+    let links = layout.links;
+    let next = Link {
+        slot: p.add(layout.next_off),
+        links,
+    };
+    let prev = layout.prev_off.map(|off| Link {
+        slot: p.add(off),
+        links,
+    });
     let keys_ptr = p.add(layout.keys_off) as *mut MaybeUninit<K>;
     let vals_ptr = p.add(layout.vals_off) as *mut MaybeUninit<V>;
     LeafParts {
         hdr,
-        next_ptr,
-        prev_ptr,
+        next,
+        prev,
         keys_ptr,
         vals_ptr,
     }
 }
 
-/// Carve a branch node's header, children pointers, and keys array from a raw base pointer.
+/// Carve a branch node's header, child links, and separator links from a raw base pointer.
 #[inline(always)]
 pub unsafe fn carve_branch<K>(base: NonNull<u8>, layout: &BranchLayout) -> BranchParts<K> {
     let p = base.as_ptr();
     let hdr = p as *mut NodeHdr;
-    let children_ptr = p.add(layout.children_off) as *mut MaybeUninit<*mut u8>;
-    let keys_ptr = p.add(layout.keys_off) as *mut MaybeUninit<K>;
+    let children = Children {
+        base: p.add(layout.children_off),
+        links: layout.links,
+    };
+    let seps = Children {
+        base: p.add(layout.seps_off),
+        links: layout.links,
+    };
     BranchParts {
         hdr,
-        children_ptr,
-        keys_ptr,
+        children,
+        seps,
+        _key: PhantomData,
     }
 }
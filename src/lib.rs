@@ -1,65 +1,213 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 use core::marker::PhantomData;
 use core::ptr::{self, NonNull};
 
+use node_alloc::NodePool;
+
+/// Like `format!`, into an [`ErrorText`].
+macro_rules! text {
+    ($($arg:tt)*) => {
+        $crate::error_text(format_args!($($arg)*))
+    };
+}
+
+mod arena;
+#[cfg(feature = "alloc")]
+mod batch;
+#[cfg(feature = "alloc")]
+mod build;
+#[cfg(feature = "alloc")]
+mod clone;
 mod common;
+mod cursor;
 mod delete;
+#[cfg(feature = "alloc")]
+mod drain;
+mod entry;
+#[cfg(feature = "alloc")]
+mod extract;
 mod get;
 mod insert;
 mod iterate;
 mod layout;
 mod node_alloc;
-
-pub use iterate::{Items, Keys, Values};
-pub use layout::{align_up, BranchLayout, LeafLayout, NodeHdr, NodeTag};
-pub use node_alloc::{
-    alloc_branch_block, alloc_leaf_block, alloc_raw, dealloc_raw, init_branch_block,
-    init_leaf_block,
+mod order;
+mod region;
+mod reserve;
+#[cfg(feature = "alloc")]
+mod set;
+#[cfg(feature = "alloc")]
+mod split;
+mod traits;
+
+pub use arena::SlabArena;
+pub use cursor::{Cursor, CursorMut};
+#[cfg(feature = "alloc")]
+pub use drain::Drain;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+#[cfg(feature = "alloc")]
+pub use extract::ExtractIf;
+pub use iterate::{
+    IntoIter, IntoKeys, IntoValues, Items, ItemsMut, Keys, Range, Values, ValuesMut,
+};
+pub use layout::{align_up, BranchLayout, LeafLayout, NodeHdr, NodeLinks, NodeTag};
+#[cfg(feature = "alloc")]
+pub use node_alloc::{alloc_branch_block, alloc_leaf_block, alloc_raw, dealloc_raw};
+pub use node_alloc::{init_branch_block, init_leaf_block, Global, NodeAllocator};
+pub use region::Region;
+#[cfg(feature = "alloc")]
+pub use set::{
+    BPlusTreeSet, Difference, Intersection, SetIntoIter, SetIter, SetRange, SymmetricDifference,
+    Union,
 };
 
 /// Raw-memory B+ tree map with fixed-size leaf and branch nodes.
 ///
 /// This type only defines the top-level container and precomputed layouts.
 /// Nodes are single raw allocations carved according to these layouts.
-pub struct BPlusTreeMap<K, V> {
+///
+/// Separators in branch nodes link to the leaf holding the first key of their
+/// right subtree instead of copying that key, so keys need not implement `Clone`
+/// and are only ever stored once.
+///
+/// # Thread safety
+///
+/// The map is `Send` when `K`, `V` and the allocator are, and `Sync` when they
+/// are `Sync`, like `BTreeMap`. Borrowing iterators and cursors follow the references they stand
+/// for, and the owning iterator follows the map.
+///
+/// ```
+/// use bplustree::BPlusTreeMap;
+///
+/// let map: BPlusTreeMap<u32, String> = (0..100).map(|i| (i, i.to_string())).collect();
+/// let total: usize = std::thread::scope(|s| {
+///     let halves = [s.spawn(|| map.range(..50).count()), s.spawn(|| map.range(50..).count())];
+///     halves.into_iter().map(|h| h.join().unwrap()).sum()
+/// });
+/// assert_eq!(total, 100);
+/// std::thread::spawn(move || assert_eq!(map.len(), 100)).join().unwrap();
+/// ```
+///
+/// A key or value that is not `Send` keeps the map from crossing threads:
+///
+/// ```compile_fail
+/// use bplustree::BPlusTreeMap;
+/// use std::rc::Rc;
+///
+/// let mut map = BPlusTreeMap::default();
+/// map.insert(Rc::new(1), 1);
+/// std::thread::spawn(move || drop(map));
+/// ```
+///
+/// ```compile_fail
+/// use bplustree::BPlusTreeMap;
+/// use std::rc::Rc;
+///
+/// let mut map = BPlusTreeMap::default();
+/// map.insert(1, Rc::new(1));
+/// let iter = map.into_iter();
+/// std::thread::spawn(move || drop(iter));
+/// ```
+///
+/// Nor can a map of non-`Sync` values be shared, directly or through an iterator:
+///
+/// ```compile_fail
+/// use bplustree::BPlusTreeMap;
+/// use std::cell::Cell;
+///
+/// let mut map = BPlusTreeMap::default();
+/// map.insert(1, Cell::new(1));
+/// std::thread::scope(|s| {
+///     s.spawn(|| map.get(&1).map(Cell::get));
+/// });
+/// ```
+///
+/// ```compile_fail
+/// use bplustree::BPlusTreeMap;
+/// use std::cell::Cell;
+///
+/// let mut map = BPlusTreeMap::default();
+/// map.insert(Cell::new(1), 1);
+/// let keys = map.keys();
+/// std::thread::scope(|s| {
+///     s.spawn(move || keys.count());
+/// });
+/// ```
+///
+/// Borrowing iterators also carry the allocator's bounds. The leaves of a map in
+/// a shared [`SlabArena`] are linked through the arena, so they stay on its thread:
+///
+/// ```compile_fail
+/// use bplustree::{BPlusTreeMap, SlabArena};
+///
+/// let arena = SlabArena::new();
+/// let mut map = BPlusTreeMap::new_in(4, &arena).unwrap();
+/// map.insert(1, 1);
+/// let items = map.items();
+/// std::thread::scope(|s| {
+///     s.spawn(move || items.count());
+/// });
+/// ```
+pub struct BPlusTreeMap<K, V, A: NodeAllocator = Global> {
     /// Root node (points to a node header at offset 0), or None if empty.
     root: Option<NonNull<u8>>,
 
+    /// Number of pairs stored, kept in step by the leaf insert/remove primitives
+    /// and the bulk operations.
+    len: usize,
+
     /// Fixed per-kind layouts computed from byte budgets and K/V sizes.
     leaf_layout: LeafLayout,
     branch_layout: BranchLayout,
 
+    /// Free node blocks kept for reuse, including those allocated up front by the
+    /// fallible operations.
+    pool: NodePool,
+
+    /// Where every node of the map comes from and goes back to.
+    alloc: A,
+
     _marker: PhantomData<(K, V)>,
 }
 
-impl<K, V> Drop for BPlusTreeMap<K, V> {
+// SAFETY: the map owns its nodes and the pairs in them; node pointers are never
+// shared outside the map or the borrows it hands out.
+unsafe impl<K: Send, V: Send, A: NodeAllocator + Send> Send for BPlusTreeMap<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: NodeAllocator + Sync> Sync for BPlusTreeMap<K, V, A> {}
+
+impl<K, V, A: NodeAllocator> Drop for BPlusTreeMap<K, V, A> {
     fn drop(&mut self) {
         if let Some(root) = self.root.take() {
             unsafe {
                 self.free_tree_no_drop(root);
             }
         }
+        unsafe { self.release_pool() };
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<K, V> BPlusTreeMap<K, V> {
     /// Common cache line size assumption (bytes).
     pub const CACHE_LINE_BYTES: usize = 64;
 
+    /// Leaf budget used by `Default` and the collection conversions (8 cache lines).
+    pub const DEFAULT_LEAF_BYTES: usize = 8 * Self::CACHE_LINE_BYTES;
+
+    /// Branch budget used by `Default` and the collection conversions (8 cache lines).
+    pub const DEFAULT_BRANCH_BYTES: usize = 8 * Self::CACHE_LINE_BYTES;
+
+    /// Smallest node capacity chosen by [`Self::with_default_budgets`].
+    pub const MIN_DEFAULT_CAPACITY: u16 = 4;
+
     /// Construct with explicit byte budgets for leaves and branches.
     /// Doubly-linked leaves are used to support reverse iteration efficiently.
     pub fn with_budgets(leaf_bytes: usize, branch_bytes: usize) -> Self {
-        let leaf_layout = LeafLayout::compute::<K, V>(leaf_bytes, true);
-        let branch_layout = BranchLayout::compute::<K>(branch_bytes);
-        Self {
-            root: None,
-            leaf_layout,
-            branch_layout,
-            _marker: PhantomData,
-        }
+        Self::with_budgets_in(leaf_bytes, branch_bytes, Global)
     }
 
     /// Construct using cache-line counts for leaf and branch nodes.
@@ -70,6 +218,65 @@ impl<K, V> BPlusTreeMap<K, V> {
         Self::with_budgets(lb, bb)
     }
 
+    /// Construct with the default budgets of [`Self::DEFAULT_LEAF_BYTES`] and
+    /// [`Self::DEFAULT_BRANCH_BYTES`]. Types too large for those budgets get nodes
+    /// sized for [`Self::MIN_DEFAULT_CAPACITY`] entries instead.
+    pub fn with_default_budgets() -> Self {
+        let mut map = Self::with_budgets(Self::DEFAULT_LEAF_BYTES, Self::DEFAULT_BRANCH_BYTES);
+        if map.leaf_layout.cap < Self::MIN_DEFAULT_CAPACITY {
+            map.leaf_layout = LeafLayout::compute_for_cap::<K, V>(Self::MIN_DEFAULT_CAPACITY, true);
+        }
+        if map.branch_layout.cap < Self::MIN_DEFAULT_CAPACITY {
+            map.branch_layout = BranchLayout::compute_for_cap(Self::MIN_DEFAULT_CAPACITY);
+        }
+        map
+    }
+}
+
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Like [`BPlusTreeMap::with_budgets`], with every node allocated from `alloc`.
+    pub fn with_budgets_in(leaf_bytes: usize, branch_bytes: usize, alloc: A) -> Self {
+        let links = alloc.node_links();
+        let leaf_layout = LeafLayout::compute_linked::<K, V>(leaf_bytes, true, links);
+        let branch_layout = BranchLayout::compute_linked(branch_bytes, false, links);
+        Self::with_layouts_in(leaf_layout, branch_layout, alloc)
+    }
+
+    /// An empty map with the given node layouts.
+    pub(crate) fn with_layouts_in(
+        leaf_layout: LeafLayout,
+        branch_layout: BranchLayout,
+        alloc: A,
+    ) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            leaf_layout,
+            branch_layout,
+            pool: NodePool::new(),
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// A map with no root, the same node layouts and pool limit as `self`, and a
+    /// clone of its allocator.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn empty_like(&self) -> Self
+    where
+        A: Clone,
+    {
+        let mut map =
+            Self::with_layouts_in(self.leaf_layout, self.branch_layout, self.alloc.clone());
+        map.pool.set_limit(self.pool.limit());
+        map
+    }
+
+    /// Returns the allocator the map's nodes come from.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
     /// Returns the configured layout for leaf nodes.
     pub fn leaf_layout(&self) -> &LeafLayout {
         &self.leaf_layout
@@ -80,6 +287,74 @@ impl<K, V> BPlusTreeMap<K, V> {
         &self.branch_layout
     }
 
+    /// Number of free node blocks the map keeps for reuse.
+    pub fn pooled_nodes(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Most free node blocks the map keeps for reuse; 16 unless changed.
+    pub fn max_pooled_nodes(&self) -> usize {
+        self.pool.limit()
+    }
+
+    /// Keep at most `nodes` free node blocks for reuse, freeing any beyond that
+    /// now. Zero turns pooling off.
+    pub fn set_max_pooled_nodes(&mut self, nodes: usize) {
+        self.pool.set_limit(nodes);
+        unsafe { self.trim_pool() };
+    }
+
+    /// Free every node block kept for reuse, including those set aside by
+    /// [`Self::reserve`].
+    pub fn shrink_to_fit(&mut self) {
+        unsafe { self.release_pool() };
+    }
+
+    /// A new leaf block, taken from the pool when it has one.
+    pub(crate) unsafe fn new_leaf(&mut self) -> NonNull<u8> {
+        self.pool.leaf(&self.alloc, &self.leaf_layout)
+    }
+
+    /// A new branch block, taken from the pool when it has one.
+    pub(crate) unsafe fn new_branch(&mut self) -> NonNull<u8> {
+        self.pool
+            .branch(&self.alloc, &self.leaf_layout, &self.branch_layout)
+    }
+
+    /// Pool `leaves` leaf and `branches` branch blocks for the operation about to
+    /// run, or fail without keeping more than the pool limit.
+    pub(crate) fn reserve_nodes(&mut self, leaves: usize, branches: usize) -> BTreeResult<()> {
+        unsafe {
+            let (leaf, branch) = (&self.leaf_layout, &self.branch_layout);
+            self.pool.fill(&self.alloc, leaf, branch, leaves, branches)
+        }
+    }
+
+    /// Free the pooled blocks beyond the pool limit, such as those an operation
+    /// reserved but did not use.
+    pub(crate) unsafe fn trim_pool(&mut self) {
+        self.pool
+            .trim(&self.alloc, &self.leaf_layout, &self.branch_layout);
+    }
+
+    /// Free every pooled block.
+    pub(crate) unsafe fn release_pool(&mut self) {
+        self.pool
+            .release(&self.alloc, &self.leaf_layout, &self.branch_layout);
+    }
+
+    /// Return a leaf block to the pool, or to the allocator if the pool is full.
+    pub(crate) unsafe fn free_leaf(&mut self, node: NonNull<u8>) {
+        let (leaf, branch) = (&self.leaf_layout, &self.branch_layout);
+        self.pool.recycle(&self.alloc, leaf, branch, node, true);
+    }
+
+    /// Return a branch block to the pool, or to the allocator if the pool is full.
+    pub(crate) unsafe fn free_branch(&mut self, node: NonNull<u8>) {
+        let (leaf, branch) = (&self.leaf_layout, &self.branch_layout);
+        self.pool.recycle(&self.alloc, leaf, branch, node, false);
+    }
+
     /// Recursively free all nodes without dropping K,V (for Drop impl).
     unsafe fn free_tree_no_drop(&mut self, node: NonNull<u8>) {
         let hdr = &*(node.as_ptr() as *const NodeHdr);
@@ -94,7 +369,7 @@ impl<K, V> BPlusTreeMap<K, V> {
                     ptr::drop_in_place((parts.vals_ptr as *mut V).add(i));
                 }
 
-                dealloc_raw(node, self.leaf_layout.bytes, self.leaf_layout.max_align);
+                self.free_leaf(node);
             }
             NodeTag::Branch => {
                 let parts = layout::carve_branch::<K>(node, &self.branch_layout);
@@ -102,20 +377,33 @@ impl<K, V> BPlusTreeMap<K, V> {
 
                 // Recursively free all children first
                 for i in 0..=len {
-                    let child_ptr = *((parts.children_ptr as *const *mut u8).add(i));
-                    if let Some(child) = NonNull::new(child_ptr) {
+                    if let Some(child) = NonNull::new(parts.children.get(i)) {
                         self.free_tree_no_drop(child);
                     }
                 }
 
-                // Drop all separator keys
-                for i in 0..len {
-                    ptr::drop_in_place((parts.keys_ptr as *mut K).add(i));
-                }
+                self.free_branch(node);
+            }
+        }
+    }
+}
 
-                dealloc_raw(node, self.branch_layout.bytes, self.branch_layout.max_align);
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Free every branch node below (and including) `node`, but leave the leaves
+    /// allocated and linked. Used when handing the leaf chain to an owning iterator.
+    pub(crate) unsafe fn free_branches_keep_leaves(&mut self, node: NonNull<u8>) {
+        let hdr = &*(node.as_ptr() as *const NodeHdr);
+        if hdr.tag == NodeTag::Leaf {
+            return;
+        }
+        let parts = layout::carve_branch::<K>(node, &self.branch_layout);
+        let len = (*parts.hdr).len as usize;
+        for i in 0..=len {
+            if let Some(child) = NonNull::new(parts.children.get(i)) {
+                self.free_branches_keep_leaves(child);
             }
         }
+        self.free_branch(node);
     }
 }
 
@@ -127,7 +415,7 @@ impl<K, V> BPlusTreeMap<K, V> {
 // temporary shims or stubs (e.g., arena stats) and will be gated or removed as
 // the raw-memory implementation matures.
 
-use alloc::format;
+#[cfg(feature = "alloc")]
 use alloc::string::String;
 use core::fmt;
 
@@ -135,14 +423,98 @@ pub const NULL_NODE: u32 = u32::MAX;
 
 #[derive(Debug)]
 pub enum BPlusTreeError {
-    InvalidCapacity(String),
+    InvalidCapacity(ErrorText),
     KeyNotFound,
-    DataIntegrityError(String),
-    ArenaError(String),
-    NodeError(String),
-    CorruptedTree(String),
-    InvalidState(String),
-    AllocationError(String),
+    DataIntegrityError(ErrorText),
+    ArenaError(ErrorText),
+    NodeError(ErrorText),
+    CorruptedTree(ErrorText),
+    InvalidState(ErrorText),
+    AllocationError(ErrorText),
+}
+
+/// Text of a [`BPlusTreeError`].
+#[cfg(feature = "alloc")]
+pub type ErrorText = String;
+
+/// Text of a [`BPlusTreeError`]. Without the `alloc` feature it is kept inline
+/// and cut short after [`ErrorText::CAPACITY`] bytes.
+#[cfg(not(feature = "alloc"))]
+#[derive(Clone, Copy)]
+pub struct ErrorText {
+    buf: [u8; ErrorText::CAPACITY],
+    len: u8,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl ErrorText {
+    /// Most bytes of text kept.
+    pub const CAPACITY: usize = 80;
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever written.
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len as usize]) }
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl fmt::Write for ErrorText {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = self.len as usize;
+        let mut end = s.len().min(Self::CAPACITY - start);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf[start..start + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end as u8;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl core::ops::Deref for ErrorText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl From<&str> for ErrorText {
+    fn from(s: &str) -> Self {
+        text!("{}", s)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl fmt::Debug for ErrorText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl fmt::Display for ErrorText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Formats an [`ErrorText`]; see `text!`.
+#[cfg(feature = "alloc")]
+fn error_text(args: fmt::Arguments<'_>) -> ErrorText {
+    alloc::fmt::format(args)
+}
+
+#[cfg(not(feature = "alloc"))]
+fn error_text(args: fmt::Arguments<'_>) -> ErrorText {
+    let mut text = ErrorText {
+        buf: [0; ErrorText::CAPACITY],
+        len: 0,
+    };
+    let _ = fmt::Write::write_fmt(&mut text, args);
+    text
 }
 
 impl fmt::Display for BPlusTreeError {
@@ -179,35 +551,40 @@ impl<K, V> NodeRef<K, V> {
     }
 }
 
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> BPlusTreeMap<K, V> {
     // ===== Compatibility constructors =====
     pub fn new(capacity: usize) -> Result<Self, BPlusTreeError> {
+        Self::new_in(capacity, Global)
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Like [`BPlusTreeMap::new`], with every node allocated from `alloc`.
+    pub fn new_in(capacity: usize, alloc: A) -> Result<Self, BPlusTreeError> {
         if capacity < 4 {
             return Err(BPlusTreeError::InvalidCapacity("capacity too small".into()));
         }
         let cap_u16 = core::cmp::min(capacity as u16, u16::MAX);
-        let leaf_layout = LeafLayout::compute_for_cap::<K, V>(cap_u16, true);
-        let branch_layout = BranchLayout::compute_for_cap::<K>(cap_u16);
-        let mut tree = Self {
-            root: None,
-            leaf_layout,
-            branch_layout,
-            _marker: PhantomData,
-        };
-        unsafe {
-            let leaf = alloc_leaf_block(&tree.leaf_layout)
-                .ok_or_else(|| BPlusTreeError::AllocationError("leaf root".into()))?;
-            tree.root = Some(leaf);
-        }
+        let links = alloc.node_links();
+        let leaf_layout = LeafLayout::compute_for_cap_linked::<K, V>(cap_u16, true, links);
+        let branch_layout = BranchLayout::compute_for_cap_linked(cap_u16, false, links);
+        let mut tree = Self::with_layouts_in(leaf_layout, branch_layout, alloc);
+        tree.reserve_nodes(1, 0)?;
+        unsafe { tree.root = Some(tree.new_leaf()) };
         Ok(tree)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len() == 0
+        self.len == 0
     }
 
     pub fn len(&self) -> usize {
-        // Compute dynamically by walking the leaf linked list from the leftmost leaf
+        self.len
+    }
+
+    /// Count the pairs by walking the leaf chain, for checking `len`.
+    pub(crate) fn leaf_walk_len(&self) -> usize {
         let mut total = 0usize;
         let mut cur = match self.leftmost_leaf() {
             Some(p) => p.as_ptr(),
@@ -222,13 +599,14 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
                 let parts =
                     layout::carve_leaf::<K, V>(NonNull::new_unchecked(cur), &self.leaf_layout);
                 total += (*parts.hdr).len as usize;
-                cur = *parts.next_ptr;
+                cur = parts.next.get();
             }
         }
         total
     }
 
     pub fn clear(&mut self) {
+        self.len = 0;
         if let Some(root) = self.root.take() {
             unsafe {
                 self.free_tree_no_drop(root);
@@ -316,28 +694,29 @@ impl<T> BTreeResultExt<T> for Result<T, BPlusTreeError> {
 
 impl BPlusTreeError {
     pub fn invalid_capacity(got: usize, min: usize) -> Self {
-        BPlusTreeError::InvalidCapacity(format!(
+        BPlusTreeError::InvalidCapacity(text!(
             "Capacity {} is invalid (minimum required: {})",
-            got, min
+            got,
+            min
         ))
     }
     pub fn data_integrity(op: &str, why: &str) -> Self {
-        BPlusTreeError::DataIntegrityError(format!("{}: {}", op, why))
+        BPlusTreeError::DataIntegrityError(text!("{}: {}", op, why))
     }
     pub fn arena_error(what: &str, why: &str) -> Self {
-        BPlusTreeError::ArenaError(format!("{} failed: {}", what, why))
+        BPlusTreeError::ArenaError(text!("{} failed: {}", what, why))
     }
     pub fn node_error(kind: &str, id: u32, why: &str) -> Self {
-        BPlusTreeError::NodeError(format!("{} node {}: {}", kind, id, why))
+        BPlusTreeError::NodeError(text!("{} node {}: {}", kind, id, why))
     }
     pub fn corrupted_tree(where_: &str, why: &str) -> Self {
-        BPlusTreeError::CorruptedTree(format!("{} corruption: {}", where_, why))
+        BPlusTreeError::CorruptedTree(text!("{} corruption: {}", where_, why))
     }
     pub fn invalid_state(op: &str, why: &str) -> Self {
-        BPlusTreeError::InvalidState(format!("Cannot {}: {}", op, why))
+        BPlusTreeError::InvalidState(text!("Cannot {}: {}", op, why))
     }
     pub fn allocation_error(what: &str, why: &str) -> Self {
-        BPlusTreeError::AllocationError(format!("Failed to allocate {}: {}", what, why))
+        BPlusTreeError::AllocationError(text!("Failed to allocate {}: {}", what, why))
     }
 }
 
@@ -350,7 +729,7 @@ impl Eq for BPlusTreeError {}
 
 // Extra convenience/debug API stubs used in tests
 #[cfg(feature = "compat_test_api")]
-impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
     pub fn validate(&self) -> BTreeResult<()> {
         Ok(())
     }
@@ -360,9 +739,6 @@ impl<K: Ord + Clone, V> BPlusTreeMap<K, V> {
     pub fn try_get(&self, key: &K) -> KeyResult<&V> {
         self.get_item(key)
     }
-    pub fn try_insert(&mut self, key: K, value: V) -> BTreeResult<Option<V>> {
-        Ok(self.insert(key, value))
-    }
     pub fn try_remove(&mut self, key: &K) -> ModifyResult<V> {
         self.remove_item(key)
     }
@@ -1,16 +1,116 @@
-extern crate alloc;
-
-use alloc::alloc::{alloc, dealloc, Layout};
+#[cfg(feature = "alloc")]
+use alloc::alloc::{alloc, dealloc};
+use core::alloc::Layout;
+use core::mem;
 use core::ptr::{self, NonNull};
 
-use crate::layout::{carve_leaf, BranchLayout, LeafLayout, NodeHdr, NodeTag};
+use crate::layout::{carve_leaf, BranchLayout, LeafLayout, NodeHdr, NodeLinks, NodeTag};
+use crate::{BPlusTreeError, BTreeResult};
 
 #[inline]
-fn layout_for(bytes: usize, align: usize) -> Layout {
+pub(crate) fn layout_for(bytes: usize, align: usize) -> Layout {
     // SAFETY: align is computed from type/layout alignments => power of two, non-zero.
     Layout::from_size_align(bytes, align).expect("invalid layout")
 }
 
+/// Where a [`BPlusTreeMap`](crate::BPlusTreeMap) gets its node memory from.
+///
+/// Nodes are fixed-size blocks, one size for leaves and one for branches, so an
+/// implementation can be as simple as a bump arena or a free list. Allocation
+/// failure is reported as `None`; the map turns it into a panic, or into
+/// `AllocationError` for the `try_` operations.
+///
+/// # Safety
+/// A block returned by `allocate` must be valid for reads and writes of
+/// `layout.size()` bytes, aligned to `layout.align()`, and stay so until it is
+/// passed to `deallocate` with the same layout. Moving the allocator must not
+/// invalidate its blocks, and a clone of it must be able to free them, since
+/// `split_off` hands nodes to a map holding a clone.
+pub unsafe trait NodeAllocator {
+    /// Allocate a block for `layout`, whose size is never zero.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Return a block to the allocator.
+    ///
+    /// # Safety
+    /// `ptr` must come from `allocate` on this allocator with the same `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Whether `self` may free blocks allocated by `other`. `append` only moves
+    /// nodes between maps when this holds and copies the pairs otherwise. The
+    /// default holds for stateless, zero-sized allocators.
+    fn can_free_from(&self, other: &Self) -> bool {
+        let _ = other;
+        core::mem::size_of_val(self) == 0
+    }
+
+    /// How nodes from this allocator link to each other. Pointers, unless the
+    /// blocks come from a [`SlabArena`](crate::SlabArena), which numbers them;
+    /// an allocator that hands out an arena's blocks must return its links too.
+    fn node_links(&self) -> NodeLinks {
+        NodeLinks::POINTERS
+    }
+}
+
+/// The global allocator; the default for every map.
+///
+/// Without the `alloc` feature there is no global allocator: every allocation
+/// fails, and maps need an allocator of their own, such as a memory region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Global;
+
+#[cfg(feature = "alloc")]
+unsafe impl NodeAllocator for Global {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        NonNull::new(unsafe { alloc(layout) })
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        dealloc(ptr.as_ptr(), layout);
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+unsafe impl NodeAllocator for Global {
+    #[inline]
+    fn allocate(&self, _layout: Layout) -> Option<NonNull<u8>> {
+        None
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        unreachable!("Global hands out no blocks without the alloc feature")
+    }
+}
+
+unsafe impl<A: NodeAllocator + ?Sized> NodeAllocator for &A {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+
+    #[inline]
+    fn can_free_from(&self, other: &Self) -> bool {
+        ptr::eq(*self, *other) || (**self).can_free_from(other)
+    }
+
+    #[inline]
+    fn node_links(&self) -> NodeLinks {
+        (**self).node_links()
+    }
+}
+
+/// Allocate `bytes` with the given alignment from the global allocator.
+///
+/// # Safety
+/// `bytes` must be non-zero and `align` a power of two.
+#[cfg(feature = "alloc")]
 #[inline]
 pub unsafe fn alloc_raw(bytes: usize, align: usize) -> Option<NonNull<u8>> {
     let layout = layout_for(bytes, align);
@@ -18,6 +118,11 @@ pub unsafe fn alloc_raw(bytes: usize, align: usize) -> Option<NonNull<u8>> {
     NonNull::new(p)
 }
 
+/// Return a block obtained from [`alloc_raw`] to the global allocator.
+///
+/// # Safety
+/// `ptr` must come from `alloc_raw(bytes, align)` with the same arguments.
+#[cfg(feature = "alloc")]
 #[inline]
 pub unsafe fn dealloc_raw(ptr: NonNull<u8>, bytes: usize, align: usize) {
     let layout = layout_for(bytes, align);
@@ -25,6 +130,14 @@ pub unsafe fn dealloc_raw(ptr: NonNull<u8>, bytes: usize, align: usize) {
 }
 
 /// Allocate a leaf node block and initialize its header and sibling pointers.
+///
+/// This is the raw global-allocator path, for blocks managed outside of any
+/// map; it bypasses the node pools, which belong to maps and sit in front of
+/// their [`NodeAllocator`].
+///
+/// # Safety
+/// `layout` must describe a non-zero block size.
+#[cfg(feature = "alloc")]
 #[inline]
 pub unsafe fn alloc_leaf_block(layout: &LeafLayout) -> Option<NonNull<u8>> {
     let p = alloc_raw(layout.bytes, layout.max_align)?;
@@ -33,45 +146,336 @@ pub unsafe fn alloc_leaf_block(layout: &LeafLayout) -> Option<NonNull<u8>> {
 }
 
 /// Initialize an existing leaf block's header and siblings to defaults.
+///
+/// # Safety
+/// `base` must point to a writable block of at least `layout.bytes` bytes, with
+/// its arena id already in the header if the layout links by id.
 #[inline]
 pub unsafe fn init_leaf_block(base: NonNull<u8>, layout: &LeafLayout) {
-    // Header
+    init_header(base, NodeTag::Leaf, layout.links);
+
+    // Sibling links set to null by default
+    let parts = carve_leaf::<(), ()>(base, layout);
+    parts.next.set(ptr::null_mut());
+    if let Some(prev) = parts.prev {
+        prev.set(ptr::null_mut());
+    }
+}
+
+/// Write an empty header, keeping the id an arena gave the block.
+#[inline]
+unsafe fn init_header(base: NonNull<u8>, tag: NodeTag, links: NodeLinks) {
     let hdr = base.as_ptr() as *mut NodeHdr;
+    let id = if links.is_compact() {
+        ptr::addr_of!((*hdr).id).read()
+    } else {
+        0
+    };
     ptr::write(
         hdr,
         NodeHdr {
-            tag: NodeTag::Leaf,
-            len: 0,
+            tag,
             flags: 0,
+            len: 0,
+            id,
         },
     );
-
-    // Sibling pointers set to null by default
-    let parts = carve_leaf::<(), ()>(base, layout);
-    ptr::write(parts.next_ptr, core::ptr::null_mut());
-    if let Some(prev) = parts.prev_ptr {
-        ptr::write(prev, core::ptr::null_mut());
-    }
 }
 
-/// Allocate a branch node block and initialize its header.
+/// Allocate a branch node block and initialize its header. Like
+/// [`alloc_leaf_block`], it bypasses the node pools.
+///
+/// # Safety
+/// `layout` must describe a non-zero block size.
+#[cfg(feature = "alloc")]
 #[inline]
 pub unsafe fn alloc_branch_block(layout: &BranchLayout) -> Option<NonNull<u8>> {
     let p = alloc_raw(layout.bytes, layout.max_align)?;
-    init_branch_block(p);
+    init_branch_block(p, layout);
     Some(p)
 }
 
 /// Initialize an existing branch block's header to defaults.
+///
+/// # Safety
+/// `base` must point to a writable block large enough for a `NodeHdr`, with its
+/// arena id already in the header if the layout links by id.
 #[inline]
-pub unsafe fn init_branch_block(base: NonNull<u8>) {
-    let hdr = base.as_ptr() as *mut NodeHdr;
-    ptr::write(
-        hdr,
-        NodeHdr {
-            tag: NodeTag::Branch,
-            len: 0,
-            flags: 0,
-        },
-    );
+pub unsafe fn init_branch_block(base: NonNull<u8>, layout: &BranchLayout) {
+    init_header(base, NodeTag::Branch, layout.links);
+}
+
+/// Blocks a pool keeps between operations unless told otherwise.
+pub(crate) const DEFAULT_POOL_LIMIT: usize = 16;
+
+/// Pooled blocks chained through the word right after their header, which
+/// leaves an arena id in the header alone.
+struct FreeList {
+    head: Option<NonNull<u8>>,
+    len: usize,
+}
+
+impl FreeList {
+    /// Where a pooled block keeps the next one.
+    const LINK: usize = mem::size_of::<NodeHdr>();
+
+    const fn new() -> Self {
+        Self { head: None, len: 0 }
+    }
+
+    /// Whether blocks of `layout` have room for the link.
+    fn fits(layout: Layout) -> bool {
+        layout.size() >= Self::LINK + mem::size_of::<*mut u8>()
+    }
+
+    /// # Safety
+    /// `block` must be unused and fit the link.
+    unsafe fn push(&mut self, block: NonNull<u8>) {
+        let hdr = block.as_ptr() as *mut NodeHdr;
+        ptr::addr_of_mut!((*hdr).flags).write(NodeHdr::VACANT);
+        let next = self.head.map_or(ptr::null_mut(), NonNull::as_ptr);
+        (block.as_ptr().add(Self::LINK) as *mut *mut u8).write_unaligned(next);
+        self.head = Some(block);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<NonNull<u8>> {
+        let block = self.head?;
+        self.head = NonNull::new(unsafe {
+            (block.as_ptr().add(Self::LINK) as *const *mut u8).read_unaligned()
+        });
+        self.len -= 1;
+        Some(block)
+    }
+}
+
+/// Free node blocks a map keeps for reuse: blocks released by merges and
+/// `clear`, and blocks allocated ahead of a fallible operation so that it cannot
+/// run out of memory halfway through a split chain.
+///
+/// The blocks are uninitialized and kept in one free list per block size; when
+/// leaf and branch blocks have the same size and alignment, both kinds share the
+/// leaf list. [`NodePool::leaf`] and [`NodePool::branch`] initialize blocks as
+/// they are handed out and allocate fresh ones once the pool is empty. Every
+/// block comes from, and goes back to, the allocator the methods are given, which
+/// must be the same one each time, and was made for the layouts they are given.
+///
+/// Blocks can also be held for later inserts: trimming never goes below the held
+/// counts, which drop as blocks are handed out.
+pub(crate) struct NodePool {
+    leaves: FreeList,
+    branches: FreeList,
+    /// Most blocks kept once an operation is over, besides held ones.
+    limit: usize,
+    held_leaves: usize,
+    held_branches: usize,
+}
+
+impl NodePool {
+    pub(crate) const fn new() -> Self {
+        Self {
+            leaves: FreeList::new(),
+            branches: FreeList::new(),
+            limit: DEFAULT_POOL_LIMIT,
+            held_leaves: 0,
+            held_branches: 0,
+        }
+    }
+
+    /// Number of pooled blocks.
+    pub(crate) fn len(&self) -> usize {
+        self.leaves.len + self.branches.len
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Leaf and branch blocks held for later inserts.
+    pub(crate) fn held(&self) -> (usize, usize) {
+        (self.held_leaves, self.held_branches)
+    }
+
+    /// Keep `leaves` leaf and `branches` branch blocks through trimming. The pool
+    /// must already have them.
+    pub(crate) fn hold(&mut self, leaves: usize, branches: usize) {
+        self.held_leaves = leaves;
+        self.held_branches = branches;
+    }
+
+    /// Block layouts for leaves and branches, and whether they share a free list.
+    fn classes(leaf_layout: &LeafLayout, branch_layout: &BranchLayout) -> (Layout, Layout, bool) {
+        let leaf = layout_for(leaf_layout.bytes, leaf_layout.max_align);
+        let branch = layout_for(branch_layout.bytes, branch_layout.max_align);
+        (leaf, branch, leaf == branch)
+    }
+
+    /// Top the pool up to `leaves` leaf and `branches` branch blocks. If any
+    /// allocation fails, the pool is trimmed back to its limit.
+    ///
+    /// # Safety
+    /// See the type docs.
+    pub(crate) unsafe fn fill<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        leaf_layout: &LeafLayout,
+        branch_layout: &BranchLayout,
+        leaves: usize,
+        branches: usize,
+    ) -> BTreeResult<()> {
+        let (leaf, branch, shared) = Self::classes(leaf_layout, branch_layout);
+        let filled = if shared {
+            Self::fill_one(alloc, &mut self.leaves, leaves + branches, leaf)
+        } else {
+            Self::fill_one(alloc, &mut self.leaves, leaves, leaf)
+                .and_then(|()| Self::fill_one(alloc, &mut self.branches, branches, branch))
+        };
+        if filled.is_none() {
+            // Give the memory back before building the error message.
+            self.trim(alloc, leaf_layout, branch_layout);
+            return Err(BPlusTreeError::allocation_error(
+                "tree nodes",
+                "out of memory",
+            ));
+        }
+        Ok(())
+    }
+
+    unsafe fn fill_one<A: NodeAllocator>(
+        alloc: &A,
+        blocks: &mut FreeList,
+        want: usize,
+        layout: Layout,
+    ) -> Option<()> {
+        let missing = want.saturating_sub(blocks.len);
+        if missing > 0 && !FreeList::fits(layout) {
+            return None;
+        }
+        for _ in 0..missing {
+            blocks.push(alloc.allocate(layout)?);
+        }
+        Some(())
+    }
+
+    /// A leaf block, from the pool if it has one.
+    ///
+    /// # Safety
+    /// See the type docs.
+    pub(crate) unsafe fn leaf<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        layout: &LeafLayout,
+    ) -> NonNull<u8> {
+        let block = match self.leaves.pop() {
+            Some(block) => block,
+            None => alloc
+                .allocate(layout_for(layout.bytes, layout.max_align))
+                .expect("alloc leaf"),
+        };
+        self.held_leaves = self.held_leaves.saturating_sub(1);
+        init_leaf_block(block, layout);
+        block
+    }
+
+    /// A branch block, from the pool if it has one.
+    ///
+    /// # Safety
+    /// See the type docs.
+    pub(crate) unsafe fn branch<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        leaf_layout: &LeafLayout,
+        branch_layout: &BranchLayout,
+    ) -> NonNull<u8> {
+        let (_, branch, shared) = Self::classes(leaf_layout, branch_layout);
+        let pooled = match self.branches.pop() {
+            Some(block) => Some(block),
+            None if shared => self.leaves.pop(),
+            None => None,
+        };
+        let block = pooled.unwrap_or_else(|| alloc.allocate(branch).expect("alloc branch"));
+        self.held_branches = self.held_branches.saturating_sub(1);
+        init_branch_block(block, branch_layout);
+        block
+    }
+
+    /// Take back a leaf (or, with `is_leaf` false, a branch) block that is no
+    /// longer in use, freeing it if the pool is full.
+    ///
+    /// # Safety
+    /// See the type docs; `block` must not be used again by the caller.
+    pub(crate) unsafe fn recycle<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        leaf_layout: &LeafLayout,
+        branch_layout: &BranchLayout,
+        block: NonNull<u8>,
+        is_leaf: bool,
+    ) {
+        let (leaf, branch, shared) = Self::classes(leaf_layout, branch_layout);
+        let layout = if is_leaf { leaf } else { branch };
+        let keep = self.len() < self.limit && FreeList::fits(layout);
+        let list = if is_leaf || shared {
+            &mut self.leaves
+        } else {
+            &mut self.branches
+        };
+        if keep {
+            list.push(block);
+        } else {
+            alloc.deallocate(block, layout);
+        }
+    }
+
+    /// Free pooled blocks until at most the limit is left, branches first, but
+    /// keep the held ones.
+    ///
+    /// # Safety
+    /// See the type docs.
+    pub(crate) unsafe fn trim<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        leaf_layout: &LeafLayout,
+        branch_layout: &BranchLayout,
+    ) {
+        let (leaf, branch, shared) = Self::classes(leaf_layout, branch_layout);
+        let (keep_leaves, keep_branches) = if shared {
+            (self.held_leaves + self.held_branches, 0)
+        } else {
+            (self.held_leaves, self.held_branches)
+        };
+        while self.len() > self.limit {
+            if self.branches.len > keep_branches {
+                alloc.deallocate(self.branches.pop().expect("pool has branches"), branch);
+            } else if self.leaves.len > keep_leaves {
+                alloc.deallocate(self.leaves.pop().expect("pool has leaves"), leaf);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Free every pooled block, held or not.
+    ///
+    /// # Safety
+    /// See the type docs.
+    pub(crate) unsafe fn release<A: NodeAllocator>(
+        &mut self,
+        alloc: &A,
+        leaf_layout: &LeafLayout,
+        branch_layout: &BranchLayout,
+    ) {
+        let (leaf, branch, _) = Self::classes(leaf_layout, branch_layout);
+        self.hold(0, 0);
+        while let Some(block) = self.leaves.pop() {
+            alloc.deallocate(block, leaf);
+        }
+        while let Some(block) = self.branches.pop() {
+            alloc.deallocate(block, branch);
+        }
+    }
 }
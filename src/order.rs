@@ -0,0 +1,290 @@
+use core::borrow::Borrow;
+use core::mem::ManuallyDrop;
+use core::ops::{Bound, RangeBounds};
+use core::ptr::{self, NonNull};
+
+use crate::iterate::bounds_are_empty;
+use crate::layout::{self, BranchLayout, LeafLayout};
+#[cfg(feature = "alloc")]
+use crate::{BPlusTreeError, Global};
+use crate::{BPlusTreeMap, NodeAllocator, NodeHdr, NodeTag};
+
+/// Leaf slot holding the pair of rank `rank` (0-based) under a counted `node`.
+/// `rank` must be below the number of pairs in the subtree.
+pub(crate) unsafe fn slot_for_rank(
+    mut node: *mut u8,
+    branch_layout: &BranchLayout,
+    mut rank: usize,
+) -> (*mut u8, usize) {
+    let counts_off = branch_layout
+        .counts_off
+        .expect("rank lookup needs a counted layout");
+    while (*(node as *const NodeHdr)).tag == NodeTag::Branch {
+        let len = (*(node as *const NodeHdr)).len as usize;
+        let counts = node.add(counts_off) as *const usize;
+        let mut i = 0;
+        while i < len && rank >= *counts.add(i) {
+            rank -= *counts.add(i);
+            i += 1;
+        }
+        node = branch_layout.child_of(node, i);
+    }
+    (node, rank)
+}
+
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Whether branches track how many pairs sit under each child, making the
+    /// order statistics O(log n).
+    pub fn is_counted(&self) -> bool {
+        self.branch_layout.is_counted()
+    }
+
+    /// The per-child counts of `branch`, if the layout has them.
+    #[inline]
+    pub(crate) unsafe fn counts_of(&self, branch: NonNull<u8>) -> Option<*mut usize> {
+        self.branch_layout
+            .counts_off
+            .map(|off| branch.as_ptr().add(off) as *mut usize)
+    }
+
+    /// Pairs under `node`: a leaf's length, or the sum of a counted branch's counts.
+    pub(crate) unsafe fn subtree_len(&self, node: NonNull<u8>) -> usize {
+        let hdr = &*(node.as_ptr() as *const NodeHdr);
+        match (hdr.tag, self.counts_of(node)) {
+            (NodeTag::Leaf, _) => hdr.len as usize,
+            (NodeTag::Branch, Some(counts)) => (0..=hdr.len as usize).map(|i| *counts.add(i)).sum(),
+            (NodeTag::Branch, None) => unreachable!("subtree length of an uncounted branch"),
+        }
+    }
+
+    /// Recompute the count of child `idx` of `branch` from the child itself.
+    pub(crate) unsafe fn recount_child(&self, branch: NonNull<u8>, idx: usize) {
+        if let Some(counts) = self.counts_of(branch) {
+            let b = layout::carve_branch::<K>(branch, &self.branch_layout);
+            let child = NonNull::new_unchecked(b.children.get(idx));
+            *counts.add(idx) = self.subtree_len(child);
+        }
+    }
+
+    /// Recompute every count of `branch`.
+    pub(crate) unsafe fn recount_children(&self, branch: NonNull<u8>) {
+        for idx in 0..=(*(branch.as_ptr() as *const NodeHdr)).len as usize {
+            self.recount_child(branch, idx);
+        }
+    }
+
+    /// Shift counts `from..=len` of `branch` one slot right, mirroring the
+    /// children when one is inserted before `from`.
+    pub(crate) unsafe fn shift_counts_right(&self, branch: NonNull<u8>, from: usize, len: usize) {
+        if let Some(counts) = self.counts_of(branch) {
+            ptr::copy(counts.add(from), counts.add(from + 1), len + 1 - from);
+        }
+    }
+
+    /// Shift counts `from + 1..=len` of `branch` one slot left, mirroring the
+    /// children when child `from` is removed.
+    pub(crate) unsafe fn shift_counts_left(&self, branch: NonNull<u8>, from: usize, len: usize) {
+        if let Some(counts) = self.counts_of(branch) {
+            ptr::copy(counts.add(from + 1), counts.add(from), len - from);
+        }
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Add one to (or take one from) the counts on the path down to `key`, after a
+    /// pair was inserted into or removed from its leaf without any restructuring.
+    pub(crate) unsafe fn adjust_counts_towards<Q>(&self, key: &Q, added: bool)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        if !self.is_counted() {
+            return;
+        }
+        let Some(mut cur) = self.root else {
+            return;
+        };
+        while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+            let (child, idx) = self.child_for_key(cur, key).expect("child must exist");
+            let count = self.counts_of(cur).unwrap().add(idx);
+            if added {
+                *count += 1;
+            } else {
+                *count -= 1;
+            }
+            cur = child;
+        }
+    }
+
+    /// Number of keys below `key` (or not above it when `after_equal`).
+    fn rank_of<Q>(&self, key: &Q, after_equal: bool) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let Some(mut cur) = self.root else {
+            return 0;
+        };
+        if !self.is_counted() {
+            // Sum the leaves before the one holding the position.
+            let Some((leaf, idx)) = self.leaf_position(key, after_equal) else {
+                return 0;
+            };
+            let mut rank = idx;
+            let mut l = self
+                .leftmost_leaf()
+                .map_or(ptr::null_mut(), NonNull::as_ptr);
+            unsafe {
+                while l != leaf {
+                    rank += (*(l as *const NodeHdr)).len as usize;
+                    l = self.leaf_layout.next_of(l);
+                }
+            }
+            return rank;
+        }
+        let mut rank = 0;
+        unsafe {
+            while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+                let (child, idx) = self.child_for_key(cur, key).expect("child must exist");
+                let counts = self.counts_of(cur).unwrap();
+                rank += (0..idx).map(|i| *counts.add(i)).sum::<usize>();
+                cur = child;
+            }
+            let parts = layout::carve_leaf::<K, V>(cur, &self.leaf_layout);
+            let keys =
+                core::slice::from_raw_parts(parts.keys_ptr as *const K, (*parts.hdr).len as usize);
+            rank + match self.binary_search_keys(keys, key) {
+                Ok(i) if after_equal => i + 1,
+                Ok(i) | Err(i) => i,
+            }
+        }
+    }
+
+    /// Rank of the first pair at or after a start bound (`is_end == false`), or of
+    /// the first pair past an end bound.
+    pub(crate) fn bound_rank<Q>(&self, bound: Bound<&Q>, is_end: bool) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        match bound {
+            Bound::Unbounded if is_end => self.len,
+            Bound::Unbounded => 0,
+            Bound::Included(key) => self.rank_of(key, is_end),
+            Bound::Excluded(key) => self.rank_of(key, !is_end),
+        }
+    }
+
+    /// Leaf slot of the pair at `index` in key order.
+    fn slot_at(&self, index: usize) -> Option<(NonNull<u8>, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        let root = self.root?;
+        unsafe {
+            if self.is_counted() {
+                let (leaf, idx) = slot_for_rank(root.as_ptr(), &self.branch_layout, index);
+                return Some((NonNull::new_unchecked(leaf), idx));
+            }
+            let mut rest = index;
+            let mut leaf = self.leftmost_leaf()?;
+            loop {
+                let len = (*(leaf.as_ptr() as *const NodeHdr)).len as usize;
+                if rest < len {
+                    return Some((leaf, rest));
+                }
+                rest -= len;
+                let next = self.leaf_layout.next_of(leaf.as_ptr());
+                leaf = NonNull::new(next)?;
+            }
+        }
+    }
+
+    /// Number of keys strictly below `key`, which is also the position `key` has
+    /// or would have in key order.
+    ///
+    /// O(log n) on a counted map; otherwise the leaves before `key` are walked.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.rank_of(key, false)
+    }
+
+    /// The pair at position `index` in key order.
+    ///
+    /// O(log n) on a counted map; otherwise the leaves before it are walked.
+    pub fn select(&self, index: usize) -> Option<(&K, &V)> {
+        let (leaf, idx) = self.slot_at(index)?;
+        unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            Some((
+                &*(parts.keys_ptr.add(idx) as *const K),
+                &*(parts.vals_ptr.add(idx) as *const V),
+            ))
+        }
+    }
+
+    /// Remove and return the pair at position `index` in key order.
+    pub fn remove_nth(&mut self, index: usize) -> Option<(K, V)> {
+        let (leaf, idx) = self.slot_at(index)?;
+        unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            // A bitwise copy of the key steers the removal; the owned key comes back
+            // out of the leaf, so the copy must never be dropped.
+            let key = ManuallyDrop::new(ptr::read(parts.keys_ptr.add(idx) as *const K));
+            self.remove_entry(&*key)
+        }
+    }
+
+    /// Number of keys in `range`.
+    ///
+    /// O(log n) on a counted map; otherwise the leaves up to the range end are walked.
+    pub fn range_count<Q, R>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = (range.start_bound(), range.end_bound());
+        if bounds_are_empty(start, end) {
+            return 0;
+        }
+        self.bound_rank(end, true)
+            .saturating_sub(self.bound_rank(start, false))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> BPlusTreeMap<K, V> {
+    /// Construct a counted map whose nodes hold `capacity` entries; see
+    /// [`Self::is_counted`].
+    pub fn new_counted(capacity: usize) -> Result<Self, BPlusTreeError> {
+        let mut map = Self::new(capacity)?;
+        map.branch_layout = BranchLayout::compute_for_cap_counted(map.branch_layout.cap);
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K, V> BPlusTreeMap<K, V> {
+    /// Construct a counted map with explicit byte budgets for leaves and branches;
+    /// see [`Self::is_counted`].
+    pub fn with_budgets_counted(leaf_bytes: usize, branch_bytes: usize) -> Self {
+        Self::with_budgets_counted_in(leaf_bytes, branch_bytes, Global)
+    }
+}
+
+impl<K, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Like [`BPlusTreeMap::with_budgets_counted`], with every node allocated from
+    /// `alloc`.
+    pub fn with_budgets_counted_in(leaf_bytes: usize, branch_bytes: usize, alloc: A) -> Self {
+        let links = alloc.node_links();
+        Self::with_layouts_in(
+            LeafLayout::compute_linked::<K, V>(leaf_bytes, true, links),
+            BranchLayout::compute_linked(branch_bytes, true, links),
+            alloc,
+        )
+    }
+}
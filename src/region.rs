@@ -0,0 +1,163 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+use crate::{BPlusTreeMap, NodeAllocator};
+
+/// Distinct block layouts a region serves, such as the leaf and branch layouts
+/// of two kinds of map.
+const CLASSES: usize = 4;
+
+/// Freed blocks of one layout, chained through their first word.
+struct FreeClass {
+    layout: Cell<Option<Layout>>,
+    head: Cell<*mut u8>,
+    live: Cell<usize>,
+}
+
+/// A [`NodeAllocator`] that carves nodes out of a caller's memory region, for
+/// maps that must not touch the heap.
+///
+/// Blocks are cut from the front of the region, and freed ones are kept on a
+/// free list per layout and handed out again first. The region serves up to four
+/// distinct block layouts (a map uses one or two). Once it is used up, the
+/// fallible operations return `AllocationError` and the others panic.
+///
+/// ```
+/// use bplustree::BPlusTreeMap;
+/// use core::mem::MaybeUninit;
+///
+/// let mut buf = [MaybeUninit::uninit(); 4096];
+/// let mut map = BPlusTreeMap::in_region(&mut buf, 256, 256);
+/// let mut inserted = 0;
+/// while map.try_insert(inserted, inserted).is_ok() {
+///     inserted += 1;
+/// }
+/// assert!(inserted > 100);
+/// assert_eq!(map.len(), inserted as usize);
+/// ```
+pub struct Region<'a> {
+    base: NonNull<u8>,
+    len: usize,
+    /// Bytes carved from the front so far.
+    used: Cell<usize>,
+    classes: [FreeClass; CLASSES],
+    _region: PhantomData<&'a mut [MaybeUninit<u8>]>,
+}
+
+// SAFETY: the region stands for the exclusive borrow it was made from. It is not
+// `Sync`: its free lists are updated through `&self`.
+unsafe impl Send for Region<'_> {}
+
+impl<'a> Region<'a> {
+    /// A region handing out the memory of `region`.
+    pub fn new(region: &'a mut [MaybeUninit<u8>]) -> Self {
+        Self {
+            base: NonNull::new(region.as_mut_ptr() as *mut u8).expect("slice is non-null"),
+            len: region.len(),
+            used: Cell::new(0),
+            classes: core::array::from_fn(|_| FreeClass {
+                layout: Cell::new(None),
+                head: Cell::new(ptr::null_mut()),
+                live: Cell::new(0),
+            }),
+            _region: PhantomData,
+        }
+    }
+
+    /// Nodes handed out and not yet freed.
+    pub fn live_nodes(&self) -> usize {
+        self.classes.iter().map(|c| c.live.get()).sum()
+    }
+
+    /// Bytes at the end of the region that no block has been carved from yet.
+    pub fn unused_bytes(&self) -> usize {
+        self.len - self.used.get()
+    }
+
+    /// The class serving `layout`, claiming a free one if none does yet.
+    fn class_for(&self, layout: Layout) -> Option<&FreeClass> {
+        let mut unclaimed = None;
+        for class in &self.classes {
+            match class.layout.get() {
+                Some(claimed) if claimed == layout => return Some(class),
+                Some(_) => {}
+                None => {
+                    unclaimed.get_or_insert(class);
+                }
+            }
+        }
+        let class = unclaimed?;
+        class.layout.set(Some(layout));
+        Some(class)
+    }
+
+    /// Carve a block for `layout` from the front of the region. Blocks have room
+    /// for the free list's link.
+    fn carve(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.base.as_ptr() as usize;
+        let start = (base + self.used.get())
+            .checked_next_multiple_of(layout.align())?
+            .checked_sub(base)?;
+        let end = start.checked_add(layout.size().max(size_of::<*mut u8>()))?;
+        if end > self.len {
+            return None;
+        }
+        self.used.set(end);
+        NonNull::new(unsafe { self.base.as_ptr().add(start) })
+    }
+}
+
+unsafe impl NodeAllocator for Region<'_> {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let class = self.class_for(layout)?;
+        let block = match NonNull::new(class.head.get()) {
+            Some(block) => {
+                class
+                    .head
+                    .set(unsafe { (block.as_ptr() as *const *mut u8).read_unaligned() });
+                block
+            }
+            None => self.carve(layout)?,
+        };
+        class.live.set(class.live.get() + 1);
+        Some(block)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let class = self
+            .classes
+            .iter()
+            .find(|c| c.layout.get() == Some(layout))
+            .expect("block from this region");
+        (ptr.as_ptr() as *mut *mut u8).write_unaligned(class.head.get());
+        class.head.set(ptr.as_ptr());
+        class.live.set(class.live.get() - 1);
+    }
+}
+
+impl fmt::Debug for Region<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Region")
+            .field("len", &self.len)
+            .field("live_nodes", &self.live_nodes())
+            .field("unused_bytes", &self.unused_bytes())
+            .finish()
+    }
+}
+
+impl<'a, K, V> BPlusTreeMap<K, V, Region<'a>> {
+    /// An empty map whose nodes are carved out of `region`, with byte budgets of
+    /// `leaf_bytes` and `branch_bytes` per node; see [`Region`]. The map never
+    /// uses the heap, and is available without the `alloc` feature.
+    pub fn in_region(
+        region: &'a mut [MaybeUninit<u8>],
+        leaf_bytes: usize,
+        branch_bytes: usize,
+    ) -> Self {
+        Self::with_budgets_in(leaf_bytes, branch_bytes, Region::new(region))
+    }
+}
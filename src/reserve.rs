@@ -0,0 +1,97 @@
+use core::ptr::NonNull;
+
+use crate::layout;
+use crate::{BPlusTreeMap, BTreeResult, NodeAllocator, NodeHdr, NodeTag};
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> BPlusTreeMap<K, V> {
+    /// An empty map with the default budgets and every node for its first
+    /// `capacity` inserts already allocated; see [`Self::reserve`].
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut map = Self::with_default_budgets();
+        map.reserve(capacity);
+        map
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> BPlusTreeMap<K, V, A> {
+    /// Allocate every node that `additional` more inserts could need, so that
+    /// none of them calls the allocator.
+    ///
+    /// The nodes wait in the node pool, beyond its limit, until inserts use them.
+    /// Removals in between, and [`Self::shrink_to_fit`], void the guarantee.
+    /// Panics if the allocator runs out of memory; see [`Self::try_reserve`].
+    pub fn reserve(&mut self, additional: usize) {
+        self.try_reserve(additional).expect("alloc reserve");
+    }
+
+    /// Like [`Self::reserve`], but returns `AllocationError` if the allocator
+    /// runs out of memory, and reserves nothing more.
+    pub fn try_reserve(&mut self, additional: usize) -> BTreeResult<()> {
+        let (leaves, branches) = self.reserve_needs(additional);
+        let (held_leaves, held_branches) = self.pool.held();
+        let (leaves, branches) = (leaves.max(held_leaves), branches.max(held_branches));
+        self.reserve_nodes(leaves, branches)?;
+        self.pool.hold(leaves, branches);
+        Ok(())
+    }
+
+    /// Most new leaves and branches `additional` inserts can need.
+    ///
+    /// Every non-root node is at least half full and only fills up as keys
+    /// arrive, which caps the node count of each level once the inserts are done;
+    /// its current count is at least what full nodes would take. A level also
+    /// gains no more nodes than the level below it does, one split each.
+    fn reserve_needs(&self, additional: usize) -> (usize, usize) {
+        let leaf_cap = self.leaf_layout.cap as usize;
+        let branch_cap = self.branch_layout.cap as usize;
+        let height = self.height();
+        // Fewest nodes the level below has now, and most it can have afterwards.
+        let mut fewest = if self.root.is_some() {
+            self.len.div_ceil(leaf_cap).max(1)
+        } else {
+            0
+        };
+        let mut most = ((self.len + additional) / self.min_leaf_len().max(1)).max(1);
+        let leaves = additional.min(most - fewest);
+        let (mut gained, mut branches) = (leaves, 0);
+        let mut level = 1;
+        while gained > 0 {
+            let fewest_here = if level < height {
+                fewest.div_ceil(branch_cap + 1).max(1)
+            } else {
+                0
+            };
+            // A level with a single node has nothing above it.
+            let most_here = if most < 2 {
+                0
+            } else {
+                (most / (self.min_branch_len() + 1)).max(1)
+            };
+            gained = gained.min(most_here.saturating_sub(fewest_here));
+            branches += gained;
+            (fewest, most) = (fewest_here, most_here);
+            level += 1;
+        }
+        (leaves, branches)
+    }
+
+    /// Number of node levels, zero without a root.
+    fn height(&self) -> usize {
+        let mut height = 0;
+        let mut cur = self.root;
+        while let Some(node) = cur {
+            height += 1;
+            unsafe {
+                cur = match (*(node.as_ptr() as *const NodeHdr)).tag {
+                    NodeTag::Leaf => None,
+                    NodeTag::Branch => {
+                        let b = layout::carve_branch::<K>(node, &self.branch_layout);
+                        NonNull::new(b.children.get(0))
+                    }
+                };
+            }
+        }
+        height
+    }
+}
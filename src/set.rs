@@ -0,0 +1,694 @@
+use core::borrow::Borrow;
+use core::cmp::{max, min, Ordering};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{FusedIterator, Peekable};
+use core::ops::{BitAnd, BitOr, BitXor, Bound, RangeBounds, Sub};
+
+use crate::{BPlusTreeError, BPlusTreeMap, BranchLayout, IntoKeys, Items, LeafLayout, Range};
+
+/// Raw-memory B+ tree set, a [`BPlusTreeMap`] with `()` values.
+///
+/// The value array of a `()` leaf takes no space, so leaf capacity depends on the
+/// size of `T` alone.
+pub struct BPlusTreeSet<T> {
+    map: BPlusTreeMap<T, ()>,
+}
+
+impl<T> BPlusTreeSet<T> {
+    /// Construct with explicit byte budgets for leaves and branches.
+    pub fn with_budgets(leaf_bytes: usize, branch_bytes: usize) -> Self {
+        Self {
+            map: BPlusTreeMap::with_budgets(leaf_bytes, branch_bytes),
+        }
+    }
+
+    /// Construct using cache-line counts for leaf and branch nodes.
+    pub fn with_cache_lines(leaf_lines: usize, branch_lines: usize) -> Self {
+        Self {
+            map: BPlusTreeMap::with_cache_lines(leaf_lines, branch_lines),
+        }
+    }
+
+    /// Construct with the map's default budgets.
+    pub fn with_default_budgets() -> Self {
+        Self {
+            map: BPlusTreeMap::with_default_budgets(),
+        }
+    }
+
+    /// Returns the configured layout for leaf nodes.
+    pub fn leaf_layout(&self) -> &LeafLayout {
+        self.map.leaf_layout()
+    }
+
+    /// Returns the configured layout for branch nodes.
+    pub fn branch_layout(&self) -> &BranchLayout {
+        self.map.branch_layout()
+    }
+}
+
+impl<T: Ord> BPlusTreeSet<T> {
+    /// Construct with nodes holding `capacity` elements, as [`BPlusTreeMap::new`].
+    pub fn new(capacity: usize) -> Result<Self, BPlusTreeError> {
+        Ok(Self {
+            map: BPlusTreeMap::new(capacity)?,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Add `value`, returning false (and keeping the stored element) if an equal
+    /// element is already present.
+    pub fn insert(&mut self, value: T) -> bool {
+        match self.map.entry(value) {
+            crate::Entry::Vacant(e) => {
+                e.insert(());
+                true
+            }
+            crate::Entry::Occupied(_) => false,
+        }
+    }
+
+    /// Remove the element equal to `value`, returning whether it was present.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Remove and return the element equal to `value`.
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.remove_entry(value).map(|(k, ())| k)
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// The stored element equal to `value`.
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map
+            .lower_bound(Bound::Included(value))
+            .peek_next()
+            .map(|(k, _)| k)
+            .filter(|k| (*k).borrow() == value)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|(k, _)| k)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|(k, _)| k)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.map.pop_first().map(|(k, ())| k)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.map.pop_last().map(|(k, ())| k)
+    }
+
+    pub fn iter(&self) -> SetIter<'_, T> {
+        SetIter {
+            inner: self.map.items(),
+        }
+    }
+
+    pub fn range<Q, R>(&self, r: R) -> SetRange<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+        R: RangeBounds<Q>,
+    {
+        SetRange {
+            inner: self.map.range(r),
+        }
+    }
+
+    /// Keep only the elements for which `f` returns true.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.map.retain(|k, _| f(k));
+    }
+
+    /// Move every element `>= value` into a new set and return it.
+    pub fn split_off<Q>(&mut self, value: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Self {
+            map: self.map.split_off(value),
+        }
+    }
+
+    /// Move every element of `other` into `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.map.append(&mut other.map);
+    }
+
+    /// Elements in `self` or `other`, ascending and without repeats.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T> {
+        Union {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+        }
+    }
+
+    /// Elements in both `self` and `other`, ascending.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T> {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let inner = if small.len().saturating_mul(SEARCH_RATIO) < large.len() {
+            IntersectionInner::Search {
+                small: small.iter(),
+                large,
+            }
+        } else {
+            IntersectionInner::Stitch {
+                a: self.iter().peekable(),
+                b: other.iter().peekable(),
+            }
+        };
+        Intersection { inner }
+    }
+
+    /// Elements in `self` but not in `other`, ascending.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T> {
+        let inner = if self.len().saturating_mul(SEARCH_RATIO) < other.len() {
+            DifferenceInner::Search {
+                a: self.iter(),
+                other,
+            }
+        } else {
+            DifferenceInner::Stitch {
+                a: self.iter(),
+                b: other.iter().peekable(),
+            }
+        };
+        Difference { inner }
+    }
+
+    /// Elements in exactly one of `self` and `other`, ascending.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T> {
+        SymmetricDifference {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+        }
+    }
+
+    /// True if every element of `self` is in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    /// True if every element of `other` is in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// True if `self` and `other` have no element in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    pub fn check_invariants(&self) -> bool {
+        self.map.check_invariants()
+    }
+
+    pub fn check_invariants_detailed(&self) -> Result<(), alloc::string::String> {
+        self.map.check_invariants_detailed()
+    }
+}
+
+/// When one set is this many times larger than the other, intersection and
+/// difference look elements up in it instead of walking both sets in step.
+const SEARCH_RATIO: usize = 16;
+
+pub struct SetIter<'a, T> {
+    inner: Items<'a, T, ()>,
+}
+
+impl<T> Clone for SetIter<'_, T> {
+    fn clone(&self) -> Self {
+        SetIter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.inner.nth(n).map(|(k, _)| k)
+    }
+}
+
+impl<T> DoubleEndedIterator for SetIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth_back(n).map(|(k, _)| k)
+    }
+}
+
+impl<T> ExactSizeIterator for SetIter<'_, T> {}
+
+impl<T> FusedIterator for SetIter<'_, T> {}
+
+pub struct SetRange<'a, T> {
+    inner: Range<'a, T, ()>,
+}
+
+impl<T> Clone for SetRange<'_, T> {
+    fn clone(&self) -> Self {
+        SetRange {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SetRange<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.inner.nth(n).map(|(k, _)| k)
+    }
+}
+
+impl<T> DoubleEndedIterator for SetRange<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth_back(n).map(|(k, _)| k)
+    }
+}
+
+impl<T> FusedIterator for SetRange<'_, T> {}
+
+pub struct SetIntoIter<T> {
+    inner: IntoKeys<T, ()>,
+}
+
+impl<T> Iterator for SetIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next()
+    }
+}
+
+impl<T> DoubleEndedIterator for SetIntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.inner.next_back()
+    }
+}
+
+impl<T> FusedIterator for SetIntoIter<T> {}
+
+/// Compare the heads of two ascending iterators; an exhausted side sorts last.
+fn cmp_heads<T: Ord>(a: Option<&&T>, b: Option<&&T>) -> Option<Ordering> {
+    match (a, b) {
+        (None, None) => None,
+        (Some(_), None) => Some(Ordering::Less),
+        (None, Some(_)) => Some(Ordering::Greater),
+        (Some(x), Some(y)) => Some(x.cmp(y)),
+    }
+}
+
+pub struct Union<'a, T> {
+    a: Peekable<SetIter<'a, T>>,
+    b: Peekable<SetIter<'a, T>>,
+}
+
+impl<T> Clone for Union<'_, T> {
+    fn clone(&self) -> Self {
+        Union {
+            a: self.a.clone(),
+            b: self.b.clone(),
+        }
+    }
+}
+
+impl<'a, T: Ord> Iterator for Union<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match cmp_heads(self.a.peek(), self.b.peek())? {
+            Ordering::Less => self.a.next(),
+            Ordering::Greater => self.b.next(),
+            Ordering::Equal => {
+                self.b.next();
+                self.a.next()
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, _) = self.a.size_hint();
+        let (b, _) = self.b.size_hint();
+        (max(a, b), None)
+    }
+}
+
+impl<T: Ord> FusedIterator for Union<'_, T> {}
+
+pub struct SymmetricDifference<'a, T> {
+    a: Peekable<SetIter<'a, T>>,
+    b: Peekable<SetIter<'a, T>>,
+}
+
+impl<T> Clone for SymmetricDifference<'_, T> {
+    fn clone(&self) -> Self {
+        SymmetricDifference {
+            a: self.a.clone(),
+            b: self.b.clone(),
+        }
+    }
+}
+
+impl<'a, T: Ord> Iterator for SymmetricDifference<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match cmp_heads(self.a.peek(), self.b.peek())? {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => return self.b.next(),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+            }
+        }
+    }
+}
+
+impl<T: Ord> FusedIterator for SymmetricDifference<'_, T> {}
+
+enum IntersectionInner<'a, T> {
+    /// Walk both sets in step.
+    Stitch {
+        a: Peekable<SetIter<'a, T>>,
+        b: Peekable<SetIter<'a, T>>,
+    },
+    /// Look each element of the smaller set up in the larger one.
+    Search {
+        small: SetIter<'a, T>,
+        large: &'a BPlusTreeSet<T>,
+    },
+}
+
+pub struct Intersection<'a, T> {
+    inner: IntersectionInner<'a, T>,
+}
+
+impl<T> Clone for Intersection<'_, T> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            IntersectionInner::Stitch { a, b } => IntersectionInner::Stitch {
+                a: a.clone(),
+                b: b.clone(),
+            },
+            IntersectionInner::Search { small, large } => IntersectionInner::Search {
+                small: small.clone(),
+                large,
+            },
+        };
+        Intersection { inner }
+    }
+}
+
+impl<'a, T: Ord> Iterator for Intersection<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match &mut self.inner {
+            IntersectionInner::Stitch { a, b } => loop {
+                match a.peek()?.cmp(b.peek()?) {
+                    Ordering::Less => {
+                        a.next();
+                    }
+                    Ordering::Greater => {
+                        b.next();
+                    }
+                    Ordering::Equal => {
+                        b.next();
+                        return a.next();
+                    }
+                }
+            },
+            IntersectionInner::Search { small, large } => small.find(|x| large.contains(*x)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IntersectionInner::Stitch { a, b } => (0, Some(min(a.size_hint().0, b.size_hint().0))),
+            IntersectionInner::Search { small, .. } => (0, small.size_hint().1),
+        }
+    }
+}
+
+impl<T: Ord> FusedIterator for Intersection<'_, T> {}
+
+enum DifferenceInner<'a, T> {
+    /// Walk both sets in step.
+    Stitch {
+        a: SetIter<'a, T>,
+        b: Peekable<SetIter<'a, T>>,
+    },
+    /// Look each element of `self` up in the much larger `other`.
+    Search {
+        a: SetIter<'a, T>,
+        other: &'a BPlusTreeSet<T>,
+    },
+}
+
+pub struct Difference<'a, T> {
+    inner: DifferenceInner<'a, T>,
+}
+
+impl<T> Clone for Difference<'_, T> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            DifferenceInner::Stitch { a, b } => DifferenceInner::Stitch {
+                a: a.clone(),
+                b: b.clone(),
+            },
+            DifferenceInner::Search { a, other } => DifferenceInner::Search {
+                a: a.clone(),
+                other,
+            },
+        };
+        Difference { inner }
+    }
+}
+
+impl<'a, T: Ord> Iterator for Difference<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match &mut self.inner {
+            DifferenceInner::Stitch { a, b } => 'outer: loop {
+                let x = a.next()?;
+                while let Some(y) = b.peek() {
+                    match x.cmp(y) {
+                        Ordering::Less => break,
+                        Ordering::Equal => {
+                            b.next();
+                            continue 'outer;
+                        }
+                        Ordering::Greater => {
+                            b.next();
+                        }
+                    }
+                }
+                return Some(x);
+            },
+            DifferenceInner::Search { a, other } => a.find(|x| !other.contains(*x)),
+        }
+    }
+}
+
+impl<T: Ord> FusedIterator for Difference<'_, T> {}
+
+impl<T: Ord> Default for BPlusTreeSet<T> {
+    fn default() -> Self {
+        Self::with_default_budgets()
+    }
+}
+
+impl<T: Clone> Clone for BPlusTreeSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for BPlusTreeSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> PartialEq for BPlusTreeSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Ord> Eq for BPlusTreeSet<T> {}
+
+impl<T: Ord> PartialOrd for BPlusTreeSet<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for BPlusTreeSet<T> {
+    /// Lexicographic over the elements in order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Ord + Hash> Hash for BPlusTreeSet<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for value in self {
+            value.hash(state);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BPlusTreeSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().map(|value| (value, ())).collect(),
+        }
+    }
+}
+
+impl<T: Ord, const N: usize> From<[T; N]> for BPlusTreeSet<T> {
+    fn from(values: [T; N]) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T: Ord> Extend<T> for BPlusTreeSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T: Ord + Copy> Extend<&'a T> for BPlusTreeSet<T> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T: Ord> IntoIterator for BPlusTreeSet<T> {
+    type Item = T;
+    type IntoIter = SetIntoIter<T>;
+
+    fn into_iter(self) -> SetIntoIter<T> {
+        SetIntoIter {
+            inner: self.map.into_keys(),
+        }
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a BPlusTreeSet<T> {
+    type Item = &'a T;
+    type IntoIter = SetIter<'a, T>;
+
+    fn into_iter(self) -> SetIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: Ord + Clone> BitOr<&BPlusTreeSet<T>> for &BPlusTreeSet<T> {
+    type Output = BPlusTreeSet<T>;
+
+    fn bitor(self, rhs: &BPlusTreeSet<T>) -> BPlusTreeSet<T> {
+        self.union(rhs).cloned().collect()
+    }
+}
+
+impl<T: Ord + Clone> BitAnd<&BPlusTreeSet<T>> for &BPlusTreeSet<T> {
+    type Output = BPlusTreeSet<T>;
+
+    fn bitand(self, rhs: &BPlusTreeSet<T>) -> BPlusTreeSet<T> {
+        self.intersection(rhs).cloned().collect()
+    }
+}
+
+impl<T: Ord + Clone> Sub<&BPlusTreeSet<T>> for &BPlusTreeSet<T> {
+    type Output = BPlusTreeSet<T>;
+
+    fn sub(self, rhs: &BPlusTreeSet<T>) -> BPlusTreeSet<T> {
+        self.difference(rhs).cloned().collect()
+    }
+}
+
+impl<T: Ord + Clone> BitXor<&BPlusTreeSet<T>> for &BPlusTreeSet<T> {
+    type Output = BPlusTreeSet<T>;
+
+    fn bitxor(self, rhs: &BPlusTreeSet<T>) -> BPlusTreeSet<T> {
+        self.symmetric_difference(rhs).cloned().collect()
+    }
+}
@@ -0,0 +1,277 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::mem;
+use core::ops::Bound;
+use core::ptr::{self, NonNull};
+
+use crate::insert::InsertResult;
+use crate::layout;
+use crate::{BPlusTreeMap, NodeAllocator, NodeHdr, NodeTag};
+
+impl<K: Ord, V, A: NodeAllocator + Clone> BPlusTreeMap<K, V, A> {
+    /// Move every pair with a key `>= key` into a new map and return it.
+    ///
+    /// Each node on the root-to-leaf path towards `key` is cut in two, with the
+    /// right halves forming the new tree; the leaf chain is severed at the cut.
+    /// Only the nodes along the two cut borders are rebalanced afterwards.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let mut right = self.empty_like();
+        let Some(root) = self.root else {
+            return right;
+        };
+        unsafe {
+            let mut path: Vec<(NonNull<u8>, usize)> = Vec::new();
+            let mut cur = root;
+            while (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Branch {
+                let (child, idx) = self.child_for_key(cur, key).expect("child must exist");
+                path.push((cur, idx));
+                cur = child;
+            }
+
+            // Cut the leaf: items from the first key >= `key` onwards move right.
+            let leaf = layout::carve_leaf::<K, V>(cur, &self.leaf_layout);
+            let len = (*leaf.hdr).len as usize;
+            let keys = core::slice::from_raw_parts(leaf.keys_ptr as *const K, len);
+            let at = match self.binary_search_keys(keys, key) {
+                Ok(i) | Err(i) => i,
+            };
+            let new_leaf = self.new_leaf();
+            let r = layout::carve_leaf::<K, V>(new_leaf, &self.leaf_layout);
+            ptr::copy_nonoverlapping(leaf.keys_ptr.add(at), r.keys_ptr, len - at);
+            ptr::copy_nonoverlapping(leaf.vals_ptr.add(at), r.vals_ptr, len - at);
+            (*r.hdr).len = (len - at) as u16;
+            (*leaf.hdr).len = at as u16;
+            let next = leaf.next.get();
+            leaf.next.set(ptr::null_mut());
+            if let Some(next) = NonNull::new(next) {
+                self.link_leaves(new_leaf, next);
+            }
+
+            // Cut each branch on the path, bottom-up: separators from the cut index and
+            // the children after it move right, below the right half of the cut child.
+            // Separators only link to leaves on their own side of the cut.
+            let mut right_child = new_leaf;
+            for &(node, idx) in path.iter().rev() {
+                let b = layout::carve_branch::<K>(node, &self.branch_layout);
+                let len = (*b.hdr).len as usize;
+                let branch = self.new_branch();
+                let rb = layout::carve_branch::<K>(branch, &self.branch_layout);
+                let children = b.children;
+                let rchildren = rb.children;
+                b.seps.copy_to(idx, rb.seps, 0, len - idx);
+                rchildren.set(0, right_child.as_ptr());
+                children.copy_to(idx + 1, rchildren, 1, len - idx);
+                children.clear(idx + 1, len - idx);
+                // Counts of the two cut children are redone by the border repair.
+                if let (Some(counts), Some(rcounts)) =
+                    (self.counts_of(node), self.counts_of(branch))
+                {
+                    ptr::copy_nonoverlapping(counts.add(idx + 1), rcounts.add(1), len - idx);
+                }
+                (*rb.hdr).len = (len - idx) as u16;
+                (*b.hdr).len = idx as u16;
+                right_child = branch;
+            }
+            right.root = Some(right_child);
+
+            self.repair_border(true);
+            right.repair_border(false);
+        }
+        let total = self.len;
+        self.len = match self.root {
+            Some(root) if self.is_counted() => unsafe { self.subtree_len(root) },
+            _ => Self::count_left(self, &right, total),
+        };
+        right.len = total - self.len;
+        right
+    }
+
+    /// Number of pairs in `left`, out of `total` in `left` and `right` together.
+    /// Both leaf chains are walked in lockstep, so this costs O(min) leaf visits.
+    fn count_left(left: &Self, right: &Self, total: usize) -> usize {
+        let mut l = left
+            .leftmost_leaf()
+            .map_or(ptr::null_mut(), NonNull::as_ptr);
+        let mut r = right
+            .leftmost_leaf()
+            .map_or(ptr::null_mut(), NonNull::as_ptr);
+        let (mut left_len, mut right_len) = (0, 0);
+        unsafe {
+            while !l.is_null() && !r.is_null() {
+                left_len += (*(l as *const NodeHdr)).len as usize;
+                right_len += (*(r as *const NodeHdr)).len as usize;
+                l = left.leaf_layout.next_of(l);
+                r = right.leaf_layout.next_of(r);
+            }
+        }
+        if l.is_null() {
+            left_len
+        } else {
+            total - right_len
+        }
+    }
+
+    /// Move every pair of `other` into `self`, leaving `other` empty. On equal keys
+    /// the value from `other` wins.
+    ///
+    /// When the key ranges do not overlap and both maps share node layouts and
+    /// allocator, the shorter tree is hung off the matching spine of the taller one in O(log n).
+    /// Otherwise both maps are merged linearly and the result is rebuilt bottom-up.
+    pub fn append(&mut self, other: &mut Self) {
+        if other.is_empty() {
+            return;
+        }
+        // Nodes can only change maps if the layouts agree and `self` can free them.
+        let movable = self.leaf_layout == other.leaf_layout
+            && self.branch_layout == other.branch_layout
+            && self.alloc.can_free_from(&other.alloc)
+            && other.alloc.can_free_from(&self.alloc);
+        if movable && self.is_empty() {
+            mem::swap(self, other);
+            return;
+        }
+        if movable {
+            let order = if self.max_key() < other.min_key() {
+                Some(true)
+            } else if other.max_key() < self.min_key() {
+                Some(false)
+            } else {
+                None
+            };
+            if let Some(self_is_left) = order {
+                let mine = self.root.take().expect("non-empty map has a root");
+                let theirs = other.root.take().expect("non-empty map has a root");
+                self.len += mem::take(&mut other.len);
+                unsafe {
+                    if self_is_left {
+                        self.join(mine, theirs);
+                    } else {
+                        self.join(theirs, mine);
+                    }
+                }
+                return;
+            }
+        }
+
+        let empty = self.empty_like();
+        let mine = mem::replace(self, empty);
+        let empty = other.empty_like();
+        let theirs = mem::replace(other, empty);
+        let mut a = mine.into_iter().peekable();
+        let mut b = theirs.into_iter().peekable();
+        let merged = core::iter::from_fn(|| match (a.peek(), b.peek()) {
+            (Some((ka, _)), Some((kb, _))) => match ka.cmp(kb) {
+                Ordering::Less => a.next(),
+                Ordering::Greater => b.next(),
+                Ordering::Equal => {
+                    a.next();
+                    b.next()
+                }
+            },
+            (Some(_), None) => a.next(),
+            (None, _) => b.next(),
+        });
+        let fill = self.leaf_layout.cap as usize;
+        unsafe { self.build_from_sorted(merged, fill) };
+    }
+
+    fn min_key(&self) -> &K {
+        let leaf = self.leftmost_leaf().expect("non-empty map has a leaf");
+        unsafe { &*(layout::carve_leaf::<K, V>(leaf, &self.leaf_layout).keys_ptr as *const K) }
+    }
+
+    fn max_key(&self) -> &K {
+        let leaf = self.rightmost_leaf().expect("non-empty map has a leaf");
+        unsafe {
+            let parts = layout::carve_leaf::<K, V>(leaf, &self.leaf_layout);
+            &*(parts.keys_ptr.add((*parts.hdr).len as usize - 1) as *const K)
+        }
+    }
+
+    /// Join two non-empty trees whose keys are all ordered `left < right` into `self`.
+    unsafe fn join(&mut self, left: NonNull<u8>, right: NonNull<u8>) {
+        let left_spine = self.spine(left, true);
+        let right_spine = self.spine(right, false);
+        let left_last = *left_spine.last().unwrap();
+        let right_first = *right_spine.last().unwrap();
+        self.link_leaves(left_last, right_first);
+        let sep = right_first;
+
+        let (lh, rh) = (left_spine.len(), right_spine.len());
+        match lh.cmp(&rh) {
+            Ordering::Equal => {
+                self.root = Some(left);
+                self.grow_root(sep, right);
+            }
+            Ordering::Greater => {
+                // `right` becomes the last child of the left spine node one level above it.
+                self.root = Some(left);
+                self.insert_along_spine(&left_spine[..lh - rh], true, sep, right);
+            }
+            Ordering::Less => {
+                // `left` takes the first child slot of the right spine node one level above
+                // it; the old first child is reinserted just after it.
+                self.root = Some(right);
+                let b = layout::carve_branch::<K>(right_spine[rh - lh - 1], &self.branch_layout);
+                let children = b.children;
+                let first = NonNull::new_unchecked(children.get(0));
+                children.set(0, left.as_ptr());
+                self.insert_along_spine(&right_spine[..rh - lh], false, sep, first);
+            }
+        }
+        self.repair_border(false);
+        self.repair_border(true);
+    }
+
+    /// Insert `child` after the last (or first) child of the bottom node of `spine`,
+    /// carrying splits up the spine and growing the root if it splits too.
+    unsafe fn insert_along_spine(
+        &mut self,
+        spine: &[NonNull<u8>],
+        at_end: bool,
+        sep: NonNull<u8>,
+        child: NonNull<u8>,
+    ) {
+        let mut pending = (sep, child);
+        for &node in spine.iter().rev() {
+            let idx = if at_end {
+                (*(node.as_ptr() as *const NodeHdr)).len as usize
+            } else {
+                0
+            };
+            match self.insert_into_branch(node, idx, pending.0, pending.1, None) {
+                InsertResult::Split {
+                    sep_leaf, right, ..
+                } => pending = (sep_leaf, right),
+                InsertResult::NoSplit(_) => return,
+            }
+        }
+        self.grow_root(pending.0, pending.1);
+    }
+
+    /// Nodes from `node` down to the leftmost (or rightmost) leaf, inclusive.
+    unsafe fn spine(&self, node: NonNull<u8>, rightmost: bool) -> Vec<NonNull<u8>> {
+        let mut spine = Vec::new();
+        let mut cur = node;
+        loop {
+            spine.push(cur);
+            if (*(cur.as_ptr() as *const NodeHdr)).tag == NodeTag::Leaf {
+                return spine;
+            }
+            let b = layout::carve_branch::<K>(cur, &self.branch_layout);
+            let idx = if rightmost { (*b.hdr).len as usize } else { 0 };
+            cur = NonNull::new_unchecked(b.children.get(idx));
+        }
+    }
+
+    /// Rebalance the nodes along the leftmost (or rightmost) root-to-leaf path.
+    unsafe fn repair_border(&mut self, rightmost: bool) {
+        let path = self.boundary_path(Bound::Unbounded, rightmost);
+        self.rebalance_between(&path, &path);
+    }
+}
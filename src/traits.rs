@@ -0,0 +1,131 @@
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Index;
+
+use crate::{BPlusTreeMap, NodeAllocator};
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> Default for BPlusTreeMap<K, V> {
+    /// An empty map with the default node budgets.
+    fn default() -> Self {
+        Self::with_default_budgets()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug, A: NodeAllocator> fmt::Debug for BPlusTreeMap<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.items()).finish()
+    }
+}
+
+impl<K: Ord, V: PartialEq, A: NodeAllocator> PartialEq for BPlusTreeMap<K, V, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.items().eq(other.items())
+    }
+}
+
+impl<K: Ord, V: Eq, A: NodeAllocator> Eq for BPlusTreeMap<K, V, A> {}
+
+impl<K: Ord, V: PartialOrd, A: NodeAllocator> PartialOrd for BPlusTreeMap<K, V, A> {
+    /// Lexicographic over the pairs in key order.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.items().partial_cmp(other.items())
+    }
+}
+
+impl<K: Ord, V: Ord, A: NodeAllocator> Ord for BPlusTreeMap<K, V, A> {
+    /// Lexicographic over the pairs in key order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.items().cmp(other.items())
+    }
+}
+
+impl<K: Ord + Hash, V: Hash, A: NodeAllocator> Hash for BPlusTreeMap<K, V, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for pair in self.items() {
+            pair.hash(state);
+        }
+    }
+}
+
+impl<K, Q, V, A: NodeAllocator> Index<&Q> for BPlusTreeMap<K, V, A>
+where
+    K: Ord + Borrow<Q>,
+    Q: ?Sized + Ord,
+{
+    type Output = V;
+
+    /// Panics if `key` is not in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> FromIterator<(K, V)> for BPlusTreeMap<K, V> {
+    /// Sort the pairs and build the tree bottom-up with full leaves. For repeated
+    /// keys the last pair wins, as with repeated `insert`.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut pairs: Vec<(K, V)> = iter.into_iter().collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut map = Self::with_default_budgets();
+        let mut pairs = pairs.into_iter().peekable();
+        let deduped = core::iter::from_fn(|| loop {
+            let pair = pairs.next()?;
+            match pairs.peek() {
+                Some(next) if next.0 == pair.0 => continue,
+                _ => return Some(pair),
+            }
+        });
+        let fill = map.leaf_layout.cap as usize;
+        unsafe { map.build_from_sorted(deduped, fill) };
+        map
+    }
+}
+
+impl<K: Ord, V, A: NodeAllocator> Extend<(K, V)> for BPlusTreeMap<K, V, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy, A: NodeAllocator> Extend<(&'a K, &'a V)>
+    for BPlusTreeMap<K, V, A>
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy, A: NodeAllocator> Extend<&'a (K, V)> for BPlusTreeMap<K, V, A> {
+    fn extend<I: IntoIterator<Item = &'a (K, V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BPlusTreeMap<K, V> {
+    fn from(pairs: [(K, V); N]) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> From<BTreeMap<K, V>> for BPlusTreeMap<K, V> {
+    /// The pairs are already sorted and unique, so the tree is built directly.
+    fn from(map: BTreeMap<K, V>) -> Self {
+        let mut out = Self::with_default_budgets();
+        let fill = out.leaf_layout.cap as usize;
+        unsafe { out.build_from_sorted(map.into_iter(), fill) };
+        out
+    }
+}
@@ -77,7 +77,7 @@ fn test_branch_borrow_from_underfull_sibling_attack() {
     }
 
     // Delete strategically to make siblings exactly at minimum
-    for key in vec![18, 28, 38, 48] {
+    for key in [18, 28, 38, 48] {
         tree.remove(&key);
     }
 
@@ -254,7 +254,7 @@ fn test_force_branch_rebalance_panic() {
     insert_with_multiplier_int(&mut tree, 16, 10);
 
     // Delete in specific order to create minimum branches
-    for i in vec![10, 30, 50, 70, 90, 110, 130] {
+    for i in [10, 30, 50, 70, 90, 110, 130] {
         tree.remove(&i);
     }
 
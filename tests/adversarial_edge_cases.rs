@@ -75,7 +75,7 @@ fn test_minimum_capacity_edge_cases_attack() {
 fn test_odd_capacity_arithmetic_attack() {
     // Attack: Use odd capacities to expose integer division bugs
 
-    for capacity in vec![5, 7, 9, 11] {
+    for capacity in [5, 7, 9, 11] {
         let mut tree = create_attack_tree(capacity);
 
         // Fill to exactly trigger splits at boundaries
@@ -0,0 +1,117 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use test_utils::*;
+
+/// Deterministic pseudo-random keys in `0..modulus`.
+fn keys(n: usize, seed: u64, modulus: i32) -> Vec<i32> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % modulus as u64) as i32
+        })
+        .collect()
+}
+
+/// Insert `batch` one pair at a time, collecting what each insert returned.
+fn one_by_one(map: &mut BTreeMap<i32, i32>, batch: &[(i32, i32)]) -> Vec<Option<i32>> {
+    batch.iter().map(|&(k, v)| map.insert(k, v)).collect()
+}
+
+#[test]
+fn test_batch_insert_matches_one_by_one_inserts() {
+    for &cap in &[4_usize, 5, 8, 16] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for (round, size) in [1, 3, 40, 700, 2500, 10].into_iter().enumerate() {
+            let batch: Vec<(i32, i32)> = keys(size, (cap * 31 + round) as u64, 3000)
+                .into_iter()
+                .enumerate()
+                .map(|(i, k)| (k, (round * 10_000 + i) as i32))
+                .collect();
+            let expected = one_by_one(&mut map, &batch);
+            assert_eq!(tree.batch_insert(batch).unwrap(), expected);
+            assert_matches_btreemap(&tree, &map, &format!("cap={} round={}", cap, round));
+        }
+    }
+}
+
+#[test]
+fn test_sorted_batches_into_an_existing_map() {
+    for &cap in &[4_usize, 7] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for k in (0..5000).step_by(5) {
+            tree.insert(k, 0);
+            map.insert(k, 0);
+        }
+        // Dense runs inside a few leaves, a suffix past the end, and a prefix.
+        let dense: Vec<(i32, i32)> = (1000..3000).map(|k| (k, 1)).collect();
+        let suffix: Vec<(i32, i32)> = (5000..9000).map(|k| (k, 2)).collect();
+        let prefix: Vec<(i32, i32)> = (-800..3).map(|k| (k, 3)).collect();
+        for batch in [dense, suffix, prefix] {
+            let expected = one_by_one(&mut map, &batch);
+            assert_eq!(tree.batch_insert(batch).unwrap(), expected);
+            assert_matches_btreemap(&tree, &map, &format!("cap={}", cap));
+        }
+    }
+}
+
+#[test]
+fn test_repeated_keys_within_a_batch() {
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    let mut map = BTreeMap::new();
+    tree.insert(5, 50);
+    map.insert(5, 50);
+    let batch = vec![(5, 1), (9, 2), (5, 3), (9, 4), (1, 5), (5, 6), (1, 7)];
+    let expected = one_by_one(&mut map, &batch);
+    assert_eq!(
+        expected,
+        [Some(50), None, Some(1), Some(2), None, Some(3), Some(5)]
+    );
+    assert_eq!(tree.batch_insert(batch).unwrap(), expected);
+    assert_matches_btreemap(&tree, &map, "repeats");
+    assert_eq!(tree.batch_insert(Vec::new()).unwrap(), Vec::new());
+}
+
+#[test]
+fn test_batch_insert_into_empty_and_counted_maps() {
+    for counted in [false, true] {
+        let mut tree = if counted {
+            BPlusTreeMap::new_counted(4).unwrap()
+        } else {
+            BPlusTreeMap::new(4).unwrap()
+        };
+        let mut map = BTreeMap::new();
+        let batch: Vec<(i32, i32)> = keys(3000, 99, 100_000)
+            .into_iter()
+            .map(|k| (k, k))
+            .collect();
+        let expected = one_by_one(&mut map, &batch);
+        assert_eq!(tree.batch_insert(batch).unwrap(), expected);
+        assert_matches_btreemap(&tree, &map, &format!("counted={}", counted));
+        if counted {
+            for (i, (k, v)) in map.iter().enumerate().step_by(97) {
+                assert_eq!(tree.select(i), Some((k, v)));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_batch_insert_drops_replaced_values_once() {
+    let marker = Rc::new(());
+    let mut tree: BPlusTreeMap<i32, Rc<()>> = BPlusTreeMap::new(4).unwrap();
+    let batch: Vec<_> = (0..300).map(|i| (i % 100, Rc::clone(&marker))).collect();
+    let olds = tree.batch_insert(batch).unwrap();
+    assert_eq!(olds.iter().filter(|o| o.is_some()).count(), 200);
+    assert_eq!(Rc::strong_count(&marker), 301);
+    drop(olds);
+    assert_eq!(Rc::strong_count(&marker), 101);
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
@@ -0,0 +1,71 @@
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+fn words(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("w{:04}", (i * 53) % n)).collect()
+}
+
+#[test]
+fn test_string_keys_are_found_by_str() {
+    let mut tree: BPlusTreeMap<String, usize> = BPlusTreeMap::new(4).unwrap();
+    for (i, w) in words(300).into_iter().enumerate() {
+        tree.insert(w, i);
+    }
+    assert!(tree.contains_key("w0042"));
+    assert!(!tree.contains_key("w9999"));
+    assert_eq!(tree.get("w0000"), Some(&0));
+    *tree.get_mut("w0000").unwrap() = 1000;
+    assert_eq!(tree.get("w0000"), Some(&1000));
+    assert_eq!(tree.remove("w0000"), Some(1000));
+    assert_eq!(tree.remove("w0000"), None);
+    assert_eq!(
+        tree.remove_entry("w0001").map(|(k, _)| k),
+        Some("w0001".to_string())
+    );
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_range_with_borrowed_bounds_matches_btreemap() {
+    let mut tree: BPlusTreeMap<String, usize> = BPlusTreeMap::new(5).unwrap();
+    let mut map = BTreeMap::new();
+    for (i, w) in words(200).into_iter().enumerate() {
+        tree.insert(w.clone(), i);
+        map.insert(w, i);
+    }
+    let bounds: [(Bound<&str>, Bound<&str>); 4] = [
+        (Bound::Included("w0010"), Bound::Excluded("w0020")),
+        (Bound::Excluded("w0150"), Bound::Unbounded),
+        (Bound::Unbounded, Bound::Included("w0003")),
+        (Bound::Included("w00"), Bound::Included("w001")),
+    ];
+    for range in bounds {
+        assert!(
+            tree.range::<str, _>(range).eq(map.range::<str, _>(range)),
+            "{:?}",
+            range
+        );
+    }
+    for (_, v) in tree.range_mut::<str, _>((Bound::Included("w0100"), Bound::Excluded("w0110"))) {
+        *v = 0;
+    }
+    assert!(tree
+        .range::<str, _>((Bound::Included("w0100"), Bound::Excluded("w0110")))
+        .all(|(_, v)| *v == 0));
+}
+
+#[test]
+fn test_cursor_and_split_off_accept_borrowed_keys() {
+    let mut tree: BPlusTreeMap<Vec<u8>, u8> = BPlusTreeMap::new(4).unwrap();
+    for b in 0..100u8 {
+        tree.insert(vec![b, b], b);
+    }
+    let cursor = tree.lower_bound(Bound::Included(&[50u8][..]));
+    assert_eq!(cursor.peek_next().map(|(_, v)| *v), Some(50));
+    assert_eq!(cursor.peek_prev().map(|(_, v)| *v), Some(49));
+    let right = tree.split_off(&[60u8][..]);
+    assert_eq!(tree.len(), 60);
+    assert_eq!(right.len(), 40);
+    assert!(tree.check_invariants() && right.check_invariants());
+}
@@ -126,7 +126,7 @@ fn test_mixed_operations_stress() {
     }
 
     // Tree should still be functional
-    assert!(tree.len() > 0);
+    assert!(!tree.is_empty());
 
     // Drop should not cause double-free
     drop(tree);
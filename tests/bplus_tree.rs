@@ -2021,13 +2021,13 @@ fn test_batch_insert() {
     let mut tree = BPlusTreeMap::new(4).unwrap();
 
     // Successful batch insert
-    let items = vec![(1, "one"), (2, "two"), (3, "three")];
+    let items = [(1, "one"), (2, "two"), (3, "three")];
     let result = tree.batch_insert(items.iter().map(|(k, v)| (*k, v.to_string())).collect());
     assert!(result.is_ok());
     assert_eq!(tree.len(), 3);
 
     // Batch insert with duplicates
-    let items2 = vec![(4, "four"), (2, "TWO"), (5, "five")];
+    let items2 = [(4, "four"), (2, "TWO"), (5, "five")];
     let result2 = tree.batch_insert(items2.iter().map(|(k, v)| (*k, v.to_string())).collect());
     assert!(result2.is_ok());
     assert_eq!(tree.len(), 5);
@@ -0,0 +1,81 @@
+mod test_utils;
+use bplustree::{BPlusTreeError, BPlusTreeMap};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use test_utils::*;
+
+#[test]
+fn test_from_sorted_iter_matches_btreemap_at_every_fill() {
+    for n in [0, 1, 2, 31, 500, 5000] {
+        for fill in [0.01, 0.5, 0.7, 1.0] {
+            let map: BTreeMap<i32, i32> = (0..n).map(|k| (k * 3, -k)).collect();
+            let tree = BPlusTreeMap::from_sorted_iter(map.clone(), fill).unwrap();
+            let ctx = format!("n={} fill={}", n, fill);
+            assert_matches_btreemap(&tree, &map, &ctx);
+        }
+    }
+}
+
+#[test]
+fn test_fill_factor_sets_leaf_occupancy() {
+    let n = 10_000;
+    let full = BPlusTreeMap::from_sorted_iter((0..n).map(|k| (k, k)), 1.0).unwrap();
+    let half = BPlusTreeMap::from_sorted_iter((0..n).map(|k| (k, k)), 0.5).unwrap();
+    let cap = full.leaf_layout().cap as usize;
+    assert_eq!(full.leaf_count(), (n as usize).div_ceil(cap));
+    let half_fill = cap.div_ceil(2);
+    assert!(half.leaf_count().abs_diff((n as usize).div_ceil(half_fill)) <= 1);
+
+    let mut inserted = BPlusTreeMap::with_default_budgets();
+    for k in 0..n {
+        inserted.insert(k, k);
+    }
+    assert!(full.leaf_count() < inserted.leaf_count());
+}
+
+#[test]
+fn test_bulk_load_into_custom_and_counted_layouts() {
+    let map: BTreeMap<i32, i32> = (0..700).map(|k| (k, k * k)).collect();
+    for mut tree in [
+        BPlusTreeMap::new(4).unwrap(),
+        BPlusTreeMap::new(5).unwrap(),
+        BPlusTreeMap::new_counted(4).unwrap(),
+    ] {
+        tree.bulk_load(map.clone(), 0.8).unwrap();
+        assert_matches_btreemap(&tree, &map, "bulk_load");
+        assert_eq!(tree.select(350), map.iter().nth(350));
+        assert!(tree.bulk_load([(1000, 0)], 1.0).is_err());
+    }
+}
+
+#[test]
+fn test_out_of_order_input_is_rejected_without_leaks() {
+    let marker = Rc::new(());
+    for bad_at in [1, 2, 50, 999] {
+        let pairs = (0..1000).map(|k| {
+            let key = if k == bad_at { k - 1 } else { k };
+            (key, Rc::clone(&marker))
+        });
+        let err = BPlusTreeMap::from_sorted_iter(pairs, 1.0).unwrap_err();
+        assert_eq!(
+            err,
+            BPlusTreeError::InvalidState(String::new()),
+            "bad_at={}",
+            bad_at
+        );
+        assert_eq!(Rc::strong_count(&marker), 1);
+    }
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    assert!(tree.bulk_load([(3, 0), (3, 1)], 1.0).is_err());
+    assert!(tree.is_empty());
+    assert!(tree.check_invariants());
+    tree.bulk_load([(3, 0), (4, 1)], 1.0).unwrap();
+    assert_eq!(tree.len(), 2);
+}
+
+#[test]
+fn test_invalid_fill_factors_are_rejected() {
+    for fill in [0.0, -0.5, 1.5, f64::NAN] {
+        assert!(BPlusTreeMap::from_sorted_iter([(1, 1)], fill).is_err());
+    }
+}
@@ -111,7 +111,7 @@ fn test_tree_with_drop_tracking() {
 
         // Remove some items
         for i in 0..3 {
-            tree.remove(&(i as i32));
+            tree.remove(&{ i });
         }
 
         println!(
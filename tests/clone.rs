@@ -0,0 +1,121 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use test_utils::*;
+
+#[test]
+fn test_clone_copies_contents_and_shape() {
+    for &cap in &[4_usize, 5, 16] {
+        for n in [0, 1, 4, 5, 100, 1000] {
+            let (tree, map) =
+                build_with_reference(cap, scattered_keys(n).map(|k| (k, k.to_string())));
+            let copy = tree.clone();
+            assert_matches_btreemap(&copy, &map, &format!("cap={} n={}", cap, n));
+            assert_eq!(copy.leaf_count(), tree.leaf_count());
+            assert_eq!(copy.leaf_layout(), tree.leaf_layout());
+            assert_eq!(copy.branch_layout(), tree.branch_layout());
+        }
+    }
+    let empty: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert!(empty.clone().is_empty());
+}
+
+#[test]
+fn test_clone_is_independent() {
+    let (mut tree, mut map) =
+        build_with_reference(4, scattered_keys(500).map(|k| (k, k.to_string())));
+    let mut copy = tree.clone();
+    let mut copied = map.clone();
+    for k in (0..500).step_by(2) {
+        copy.remove(&k);
+        copied.remove(&k);
+    }
+    copy.insert(1000, "new".into());
+    copied.insert(1000, "new".into());
+    *tree.get_mut(&1).unwrap() = "changed".into();
+    *map.get_mut(&1).unwrap() = "changed".into();
+    assert_matches_btreemap(&tree, &map, "original");
+    drop(tree);
+    assert_matches_btreemap(&copy, &copied, "copy");
+}
+
+/// Tracks live instances and panics once the shared clone budget runs out.
+struct Tracked {
+    id: i32,
+    live: Rc<Cell<usize>>,
+    budget: Rc<Cell<usize>>,
+}
+
+impl Tracked {
+    fn new(id: i32, live: &Rc<Cell<usize>>, budget: &Rc<Cell<usize>>) -> Self {
+        live.set(live.get() + 1);
+        Tracked {
+            id,
+            live: Rc::clone(live),
+            budget: Rc::clone(budget),
+        }
+    }
+}
+
+impl Clone for Tracked {
+    fn clone(&self) -> Self {
+        if self.budget.get() == 0 {
+            panic!("clone budget exhausted");
+        }
+        self.budget.set(self.budget.get() - 1);
+        Tracked::new(self.id, &self.live, &self.budget)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.live.set(self.live.get() - 1);
+    }
+}
+
+impl PartialEq for Tracked {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for Tracked {}
+impl PartialOrd for Tracked {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Tracked {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+#[test]
+fn test_panicking_clone_frees_the_partial_copy() {
+    let live = Rc::new(Cell::new(0));
+    let budget = Rc::new(Cell::new(usize::MAX));
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    for i in 0..300 {
+        tree.insert(
+            Tracked::new(i, &live, &budget),
+            Tracked::new(-i, &live, &budget),
+        );
+    }
+    assert_eq!(live.get(), 600);
+    // Budgets that run out on a key clone and on a value clone, in various leaves.
+    for allowed in [0, 1, 2, 7, 8, 99, 300, 599] {
+        budget.set(allowed);
+        let result = catch_unwind(AssertUnwindSafe(|| tree.clone()));
+        assert!(result.is_err(), "allowed={}", allowed);
+        assert_eq!(live.get(), 600, "allowed={}", allowed);
+    }
+    budget.set(usize::MAX);
+    let copy = tree.clone();
+    assert_eq!(live.get(), 1200);
+    assert!(copy.check_invariants());
+    drop(copy);
+    drop(tree);
+    assert_eq!(live.get(), 0);
+}
@@ -0,0 +1,125 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::ops::Bound;
+use test_utils::*;
+
+#[test]
+fn test_bounds_position_cursor_like_std() {
+    let (tree, map) = build_with_reference(4, scattered_keys(200).map(|k| (k * 2, k * 2)));
+    for probe in -3..405 {
+        for bound in [
+            Bound::Included(&probe),
+            Bound::Excluded(&probe),
+            Bound::Unbounded,
+        ] {
+            let lower = tree.lower_bound(bound);
+            assert_eq!(
+                lower.peek_next(),
+                map.range((bound, Bound::Unbounded)).next()
+            );
+            let before = match bound {
+                Bound::Included(p) => map.range(..p).next_back(),
+                Bound::Excluded(p) => map.range(..=p).next_back(),
+                Bound::Unbounded => None,
+            };
+            assert_eq!(lower.peek_prev(), before);
+            let upper = tree.upper_bound(bound);
+            assert_eq!(
+                upper.peek_prev(),
+                map.range((Bound::Unbounded, bound)).next_back()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_cursor_walks_the_whole_map_both_ways() {
+    for &cap in &[4_usize, 5, 16] {
+        let (tree, map) = build_with_reference(cap, scattered_keys(300).map(|k| (k * 2, k * 2)));
+        let mut cursor = tree.lower_bound(Bound::Unbounded);
+        assert_eq!(cursor.peek_prev(), None);
+        let mut forward = Vec::new();
+        while let Some((k, _)) = cursor.move_next() {
+            forward.push(*k);
+        }
+        assert!(forward.iter().eq(map.keys()));
+        let mut backward = Vec::new();
+        while let Some((k, _)) = cursor.move_prev() {
+            backward.push(*k);
+        }
+        assert!(backward.iter().eq(map.keys().rev()));
+        assert_eq!(cursor.peek_next(), map.iter().next());
+
+        let mut c = tree.upper_bound(Bound::Unbounded);
+        assert_eq!(c.peek_next(), None);
+        let snapshot = c.clone();
+        c.move_prev();
+        assert_eq!(snapshot.peek_prev(), map.iter().next_back());
+    }
+}
+
+#[test]
+fn test_cursor_mut_edits_while_walking() {
+    for &cap in &[4_usize, 5, 8] {
+        let (mut tree, mut map) =
+            build_with_reference(cap, scattered_keys(400).map(|k| (k * 2, k * 2)));
+        let mut cursor = tree.lower_bound_mut(Bound::Unbounded);
+        while let Some((&k, _)) = cursor.peek_next() {
+            if k % 3 == 0 {
+                assert_eq!(cursor.remove_next(), Some((k, k)));
+                map.remove(&k);
+            } else {
+                cursor.insert_before(k - 1, -k).unwrap();
+                map.insert(k - 1, -k);
+                let (_, v) = cursor.move_next().unwrap();
+                *v += 1;
+                *map.get_mut(&k).unwrap() += 1;
+            }
+        }
+        assert_matches_btreemap(&tree, &map, &format!("cap={}", cap));
+    }
+}
+
+#[test]
+fn test_cursor_mut_removes_backwards_and_inserts_after() {
+    let (mut tree, mut map) = build_with_reference(4, scattered_keys(250).map(|k| (k * 2, k * 2)));
+    let mut cursor = tree.upper_bound_mut(Bound::Included(&300));
+    for _ in 0..120 {
+        let (k, v) = cursor.remove_prev().unwrap();
+        map.remove(&k);
+        if k % 4 == 0 {
+            cursor.insert_after(k + 1, v).unwrap();
+            map.insert(k + 1, v);
+            assert_eq!(cursor.peek_next().map(|(k, _)| *k), Some(k + 1));
+        }
+    }
+    assert_matches_btreemap(&tree, &map, "remove_prev/insert_after");
+}
+
+#[test]
+fn test_insert_rejects_out_of_order_keys() {
+    let (mut tree, _) = build_with_reference(4, scattered_keys(50).map(|k| (k * 2, k * 2)));
+    let mut cursor = tree.lower_bound_mut(Bound::Included(&20));
+    assert!(cursor.insert_after(20, 0).is_err());
+    assert!(cursor.insert_before(18, 0).is_err());
+    assert!(cursor.insert_after(100, 0).is_err());
+    assert!(cursor.insert_after(19, 0).is_ok());
+    assert_eq!(cursor.as_cursor().peek_next(), Some((&19, &0)));
+    assert_eq!(tree.len(), 51);
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_cursor_on_empty_maps() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert_eq!(tree.lower_bound(Bound::Unbounded).peek_next(), None);
+    let mut cursor = tree.upper_bound_mut(Bound::Unbounded);
+    assert_eq!(cursor.remove_next(), None);
+    assert_eq!(cursor.move_prev(), None);
+    for k in 0..100 {
+        cursor.insert_before(k, k).unwrap();
+    }
+    assert_eq!(cursor.peek_prev().map(|(k, _)| *k), Some(99));
+    assert!(tree.check_invariants());
+    assert!(tree.keys().copied().eq(0..100));
+}
@@ -246,11 +246,7 @@ fn test_drop_after_removes() {
             let before_remove = counter.load(Ordering::SeqCst);
             tree.remove(&key);
             let after_remove = counter.load(Ordering::SeqCst);
-            let diff = if before_remove > after_remove {
-                before_remove - after_remove
-            } else {
-                0
-            };
+            let diff = before_remove.saturating_sub(after_remove);
             println!(
                 "  After remove {}: {} objects (dropped {})",
                 i, after_remove, diff
@@ -497,20 +493,12 @@ fn test_clear_with_20_items() {
         leaf_count, is_leaf_root
     );
 
-    // With 6 leaves, we expect 5 separator keys in the branch (clones of leaf keys)
-    // So total objects = 20 keys + 20 values + 5 separator keys = 45
-    let expected_with_separators = 40 + (leaf_count - 1);
-    println!(
-        "Expected objects (including separators): {}",
-        expected_with_separators
-    );
-
+    // Separators link to the leaves holding their keys instead of copying them,
+    // so only the 20 keys and 20 values are live.
+    assert!(leaf_count > 1);
     assert_eq!(
-        after_insert,
-        expected_with_separators,
-        "Should have {} objects (40 leaf items + {} separators)",
-        expected_with_separators,
-        leaf_count - 1
+        after_insert, 40,
+        "Should have 40 objects (20 keys + 20 values)"
     );
 
     tree.clear();
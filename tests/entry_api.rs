@@ -0,0 +1,87 @@
+use bplustree::{BPlusTreeMap, Entry};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_or_insert_counts_words_like_btreemap() {
+    let text = "the quick brown fox jumps over the lazy dog the end fox";
+    let mut tree: BPlusTreeMap<String, usize> = BPlusTreeMap::new(4).unwrap();
+    let mut map: BTreeMap<String, usize> = BTreeMap::new();
+    for word in text.split_whitespace() {
+        *tree.entry(word.to_string()).or_insert(0) += 1;
+        *map.entry(word.to_string()).or_insert(0) += 1;
+    }
+    assert!(tree.check_invariants());
+    assert!(tree.items().eq(map.iter()));
+}
+
+#[test]
+fn test_vacant_insert_splits_leaves_and_returns_slot() {
+    for &cap in &[4_usize, 5, 7] {
+        let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(cap).unwrap();
+        for i in 0..300 {
+            let k = (i * 37) % 300;
+            let v = tree.entry(k).or_insert_with(|| k * 10);
+            assert_eq!(*v, k * 10, "cap={} key={}", cap, k);
+            *v += 1;
+            assert!(tree.check_invariants(), "cap={} key={}", cap, k);
+        }
+        assert_eq!(tree.len(), 300);
+        for k in 0..300 {
+            assert_eq!(tree.get(&k), Some(&(k * 10 + 1)));
+        }
+    }
+}
+
+#[test]
+fn test_entry_on_tree_without_root() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert_eq!(*tree.entry(5).or_default(), 0);
+    tree.entry(5).and_modify(|v| *v += 3).or_insert(100);
+    assert_eq!(tree.get(&5), Some(&3));
+}
+
+#[test]
+fn test_occupied_entry_accessors() {
+    let mut tree: BPlusTreeMap<i32, String> = BPlusTreeMap::new(4).unwrap();
+    for i in 0..20 {
+        tree.insert(i, format!("v{}", i));
+    }
+    match tree.entry(7) {
+        Entry::Occupied(mut e) => {
+            assert_eq!(e.key(), &7);
+            assert_eq!(e.get(), "v7");
+            e.get_mut().push('!');
+            assert_eq!(e.insert("seven".to_string()), "v7!");
+        }
+        Entry::Vacant(_) => panic!("key 7 should be occupied"),
+    }
+    assert_eq!(tree.get(&7).map(String::as_str), Some("seven"));
+
+    match tree.entry(42) {
+        Entry::Vacant(e) => {
+            assert_eq!(e.key(), &42);
+            assert_eq!(e.into_key(), 42);
+        }
+        Entry::Occupied(_) => panic!("key 42 should be vacant"),
+    }
+    assert!(!tree.contains_key(&42));
+}
+
+#[test]
+fn test_occupied_remove_rebalances_underfull_leaves() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    let mut map = BTreeMap::new();
+    for i in 0..200 {
+        tree.insert(i, i * 2);
+        map.insert(i, i * 2);
+    }
+    for i in (0..200).rev().step_by(3).chain((0..200).step_by(2)) {
+        let expected = map.remove(&i);
+        match tree.entry(i) {
+            Entry::Occupied(e) => assert_eq!(Some(e.remove_entry()), expected.map(|v| (i, v))),
+            Entry::Vacant(_) => assert_eq!(expected, None),
+        }
+        assert!(tree.check_invariants(), "after removing {}", i);
+    }
+    assert!(tree.items().eq(map.iter()));
+}
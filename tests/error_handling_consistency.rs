@@ -217,7 +217,7 @@ fn test_operation_safety() {
 
         // Verify partial removal
         for i in 0..50 {
-            let should_exist = i < 10 || i >= 40;
+            let should_exist = !(10..40).contains(&i);
             let actually_exists = tree.contains_key(&(base + i));
             assert_eq!(
                 should_exist,
@@ -0,0 +1,205 @@
+mod test_utils;
+use bplustree::{BPlusTreeError, BPlusTreeMap};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use test_utils::*;
+
+/// The system allocator, except that it can be told to fail one allocation.
+struct FailingAlloc;
+
+thread_local! {
+    /// Allocations this thread may still make before the next one fails.
+    static FAIL_IN: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for FailingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let fail = FAIL_IN
+            .try_with(|left| match left.get() {
+                Some(0) => {
+                    left.set(None);
+                    true
+                }
+                Some(n) => {
+                    left.set(Some(n - 1));
+                    false
+                }
+                None => false,
+            })
+            .unwrap_or(false);
+        if fail {
+            std::ptr::null_mut()
+        } else {
+            System.alloc(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: FailingAlloc = FailingAlloc;
+
+/// Run `f` with its `n`th allocation (counting from zero) failing.
+fn failing_at<T>(n: usize, f: impl FnOnce() -> T) -> T {
+    FAIL_IN.with(|left| left.set(Some(n)));
+    let out = f();
+    FAIL_IN.with(|left| left.set(None));
+    out
+}
+
+fn allocation_error() -> BPlusTreeError {
+    BPlusTreeError::AllocationError(String::new())
+}
+
+/// A map whose leaves and branches are all full, so most inserts split all the
+/// way up to the root.
+fn full_tree(capacity: usize, counted: bool, n: i32) -> BPlusTreeMap<i32, i32> {
+    let mut tree = if counted {
+        BPlusTreeMap::new_counted(capacity).unwrap()
+    } else {
+        BPlusTreeMap::new(capacity).unwrap()
+    };
+    tree.bulk_load((0..n).map(|k| (k * 3, k)), 1.0).unwrap();
+    tree
+}
+
+#[test]
+fn test_try_insert_leaves_the_tree_untouched_when_any_allocation_fails() {
+    for counted in [false, true] {
+        let mut tree = full_tree(4, counted, 200);
+        let mut map: BTreeMap<i32, i32> = tree.items().map(|(k, v)| (*k, *v)).collect();
+        // The first four land in distinct full leaves; the last is already present.
+        for (i, key) in [-1, 301, 598, 151, 700, 301].into_iter().enumerate() {
+            let mut failures = 0;
+            for n in 0.. {
+                match failing_at(n, || tree.try_insert(key, -key)) {
+                    Err(e) => {
+                        assert_eq!(e, allocation_error());
+                        assert_matches_btreemap(&tree, &map, &format!("key={} n={}", key, n));
+                        failures += 1;
+                    }
+                    Ok(old) => {
+                        assert_eq!(old, map.insert(key, -key));
+                        break;
+                    }
+                }
+            }
+            assert_eq!(failures > 0, i < 4, "key={}", key);
+            assert_matches_btreemap(&tree, &map, &format!("key={}", key));
+        }
+        if counted {
+            assert_eq!(tree.select(100), map.iter().nth(100));
+        }
+    }
+}
+
+#[test]
+fn test_try_insert_allocates_nothing_without_a_split() {
+    let mut tree = full_tree(4, false, 50);
+    // An existing key only replaces the value.
+    assert_eq!(failing_at(0, || tree.try_insert(30, 0)), Ok(Some(10)));
+    tree.remove(&33);
+    assert_eq!(failing_at(0, || tree.try_insert(34, 0)), Ok(None));
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_try_batch_insert_leaves_the_tree_untouched_when_any_allocation_fails() {
+    let scattered: Vec<(i32, i32)> = (0..150).map(|i| ((i * 7919) % 1000, i)).collect();
+    let dense: Vec<(i32, i32)> = (400..1000).map(|k| (k, -k)).collect();
+    for (capacity, counted) in [(4, true), (5, false), (16, true)] {
+        for batch in [&scattered, &dense] {
+            let mut tree = full_tree(capacity, counted, 300);
+            let mut map: BTreeMap<i32, i32> = tree.items().map(|(k, v)| (*k, *v)).collect();
+            let mut failures = 0;
+            for n in 0.. {
+                let pairs = batch.clone();
+                match failing_at(n, || tree.try_batch_insert(pairs)) {
+                    Err(e) => {
+                        assert_eq!(e, allocation_error());
+                        assert_matches_btreemap(&tree, &map, &format!("cap={} n={}", capacity, n));
+                        failures += 1;
+                    }
+                    Ok(olds) => {
+                        let expected: Vec<_> =
+                            batch.iter().map(|&(k, v)| map.insert(k, v)).collect();
+                        assert_eq!(olds, expected);
+                        break;
+                    }
+                }
+            }
+            assert!(failures > 2);
+            assert_matches_btreemap(
+                &tree,
+                &map,
+                &format!("cap={} counted={}", capacity, counted),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_try_batch_insert_into_an_empty_map() {
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    tree.clear();
+    let batch: Vec<(i32, i32)> = (0..500).rev().map(|k| (k, k)).collect();
+    let mut n = 0;
+    let olds = loop {
+        let pairs = batch.clone();
+        match failing_at(n, || tree.try_batch_insert(pairs)) {
+            Err(_) => assert!(tree.is_empty() && tree.check_invariants()),
+            Ok(olds) => break olds,
+        }
+        n += 1;
+    };
+    assert!(n > 0);
+    assert!(olds.iter().all(Option::is_none));
+    let map: BTreeMap<i32, i32> = batch.into_iter().collect();
+    assert_matches_btreemap(&tree, &map, "empty");
+}
+
+#[test]
+fn test_try_clone_fails_without_leaking() {
+    let marker = Rc::new(());
+    let mut tree = BPlusTreeMap::new_counted(4).unwrap();
+    for k in 0..300 {
+        tree.insert(k, Rc::clone(&marker));
+    }
+    let mut failures = 0;
+    let copy = loop {
+        match failing_at(failures, || tree.try_clone()) {
+            Err(e) => {
+                assert_eq!(e, allocation_error());
+                assert_eq!(Rc::strong_count(&marker), 301);
+            }
+            Ok(copy) => break copy,
+        }
+        failures += 1;
+    };
+    assert!(failures > 2);
+    assert!(copy.check_invariants());
+    assert!(copy.keys().eq(tree.keys()));
+    assert_eq!(copy.select(150).map(|(k, _)| *k), Some(150));
+    assert_eq!(Rc::strong_count(&marker), 601);
+    drop(copy);
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+#[test]
+fn test_failed_insert_drops_the_pair() {
+    let marker = Rc::new(());
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    for k in 0..4 {
+        tree.insert(k, Rc::clone(&marker));
+    }
+    let err = failing_at(0, || tree.try_insert(10, Rc::clone(&marker)));
+    assert_eq!(err, Err(allocation_error()));
+    assert_eq!(Rc::strong_count(&marker), 5);
+    assert_eq!(tree.len(), 4);
+}
@@ -0,0 +1,74 @@
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_first_and_last_key_value() {
+    let mut tree: BPlusTreeMap<i32, String> = BPlusTreeMap::new(4).unwrap();
+    assert_eq!(tree.first_key_value(), None);
+    assert_eq!(tree.last_key_value(), None);
+    for i in (0..200).rev() {
+        tree.insert(i * 3, format!("v{}", i));
+        assert_eq!(tree.first_key_value().map(|(k, _)| *k), Some(i * 3));
+        assert_eq!(tree.last_key_value(), Some((&597, &"v199".to_string())));
+    }
+    assert_eq!(tree.first(), tree.first_key_value());
+    assert_eq!(tree.last(), tree.last_key_value());
+}
+
+#[test]
+fn test_pop_first_and_pop_last_drain_like_btreemap() {
+    for &cap in &[4_usize, 5, 16] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for i in 0..300 {
+            let k = (i * 71) % 300;
+            tree.insert(k, k);
+            map.insert(k, k);
+        }
+        let mut step = 0;
+        while !map.is_empty() {
+            let (got, expected) = if step % 3 == 0 {
+                (tree.pop_last(), map.pop_last())
+            } else {
+                (tree.pop_first(), map.pop_first())
+            };
+            assert_eq!(got, expected, "cap={} step={}", cap, step);
+            assert!(tree.check_invariants(), "cap={} step={}", cap, step);
+            step += 1;
+        }
+        assert_eq!(tree.pop_first(), None);
+        assert_eq!(tree.pop_last(), None);
+        assert!(tree.is_empty());
+    }
+}
+
+#[test]
+fn test_first_and_last_entry() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    assert!(tree.first_entry().is_none());
+    assert!(tree.last_entry().is_none());
+    for i in 0..50 {
+        tree.insert(i, i);
+    }
+    *tree.first_entry().unwrap().get_mut() += 100;
+    *tree.last_entry().unwrap().into_mut() += 1000;
+    assert_eq!(tree.get(&0), Some(&100));
+    assert_eq!(tree.get(&49), Some(&1049));
+    assert_eq!(tree.last_entry().unwrap().key(), &49);
+    assert_eq!(tree.first_entry().unwrap().remove(), 100);
+    assert_eq!(tree.first_key_value(), Some((&1, &1)));
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_work_queue_usage() {
+    let mut queue: BPlusTreeMap<(u32, u32), &str> = BPlusTreeMap::new(5).unwrap();
+    for round in 0..100u32 {
+        queue.insert((round % 7, round), "job");
+        if round % 3 == 2 {
+            let ((prio, _), _) = queue.pop_first().unwrap();
+            assert!(queue.first_key_value().is_none_or(|(k, _)| k.0 >= prio));
+        }
+    }
+    assert!(queue.check_invariants());
+}
@@ -0,0 +1,96 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::rc::Rc;
+use test_utils::*;
+
+/// Pairs `k -> "value_k"` for the keys below `n`, inserted out of order.
+fn pairs(n: i32) -> impl Iterator<Item = (i32, String)> {
+    scattered_keys(n).map(|k| (k, format!("value_{}", k)))
+}
+
+#[test]
+fn test_into_iter_yields_owned_pairs_in_order() {
+    for &cap in &[4_usize, 5, 16] {
+        let (tree, expected) = build_with_reference(cap, pairs(150));
+        assert_matches_btreemap(&tree, &expected, &format!("cap={}", cap));
+        let got: Vec<(i32, String)> = tree.into_iter().collect();
+        assert_eq!(got, expected.into_iter().collect::<Vec<_>>(), "cap={}", cap);
+    }
+}
+
+#[test]
+fn test_into_iter_from_both_ends() {
+    let (tree, _) = build_with_reference(4, pairs(61));
+    let mut it = tree.into_iter();
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+    while let Some((k, _)) = it.next() {
+        front.push(k);
+        match it.next_back() {
+            Some((k, _)) => back.push(k),
+            None => break,
+        }
+        if let Some((k, _)) = it.next_back() {
+            back.push(k);
+        }
+    }
+    back.reverse();
+    front.extend(back);
+    assert_eq!(front, (0..61).collect::<Vec<_>>());
+    assert!(it.next().is_none());
+    assert!(it.next_back().is_none());
+}
+
+#[test]
+fn test_into_keys_and_into_values() {
+    let (tree, _) = build_with_reference(5, pairs(40));
+    let keys: Vec<i32> = tree.into_keys().rev().collect();
+    assert_eq!(keys, (0..40).rev().collect::<Vec<_>>());
+    let (tree, _) = build_with_reference(5, pairs(3));
+    let values: Vec<String> = tree.into_values().collect();
+    assert_eq!(values, vec!["value_0", "value_1", "value_2"]);
+}
+
+#[test]
+fn test_dropping_partially_consumed_into_iter_drops_the_rest() {
+    let marker = Rc::new(());
+    for consumed in [0_usize, 1, 17, 99, 100] {
+        let mut tree: BPlusTreeMap<i32, Rc<()>> = BPlusTreeMap::new(4).unwrap();
+        for i in 0..100 {
+            tree.insert(i, Rc::clone(&marker));
+        }
+        assert_eq!(Rc::strong_count(&marker), 101);
+        let mut it = tree.into_iter();
+        let mut taken = Vec::new();
+        for i in 0..consumed {
+            taken.push(if i % 2 == 0 {
+                it.next()
+            } else {
+                it.next_back()
+            });
+        }
+        drop(it);
+        assert_eq!(Rc::strong_count(&marker), 1 + consumed);
+        drop(taken);
+        assert_eq!(Rc::strong_count(&marker), 1);
+    }
+}
+
+#[test]
+fn test_into_iter_on_empty_trees() {
+    let tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    assert_eq!(tree.into_iter().next(), None);
+    let tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert_eq!(tree.into_iter().next_back(), None);
+}
+
+#[test]
+fn test_borrowing_into_iterator_impls() {
+    let (mut tree, _) = build_with_reference(4, pairs(10));
+    for (_, v) in &mut tree {
+        v.push('!');
+    }
+    let collected: Vec<_> = (&tree).into_iter().map(|(_, v)| v.clone()).collect();
+    assert_eq!(collected[0], "value_0!");
+    assert_eq!(collected.len(), 10);
+}
@@ -0,0 +1,106 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::ops::Bound;
+use test_utils::*;
+
+#[test]
+fn test_items_interleaved_front_and_back_meet() {
+    for &cap in &[4_usize, 5, 8, 16] {
+        let (tree, map) = build_with_reference(cap, scattered_keys(200).map(|k| (k * 2, k)));
+        let mut it = tree.items();
+        let mut exp = map.iter();
+        let mut step = 0;
+        loop {
+            let (got, want) = if step % 3 == 0 {
+                (it.next_back(), exp.next_back())
+            } else {
+                (it.next(), exp.next())
+            };
+            assert_eq!(got, want, "cap={} step={}", cap, step);
+            if got.is_none() {
+                break;
+            }
+            step += 1;
+        }
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next_back(), None);
+    }
+}
+
+#[test]
+fn test_range_bounds_match_btreemap_both_directions() {
+    let (tree, map) = build_with_reference(4, scattered_keys(120).map(|k| (k * 2, k)));
+    let bounds = [
+        Bound::Unbounded,
+        Bound::Included(-1),
+        Bound::Included(0),
+        Bound::Excluded(0),
+        Bound::Included(37),
+        Bound::Excluded(38),
+        Bound::Included(120),
+        Bound::Excluded(239),
+        Bound::Included(238),
+        Bound::Excluded(500),
+    ];
+    for &start in &bounds {
+        for &end in &bounds {
+            let valid = match (start, end) {
+                (
+                    Bound::Included(s) | Bound::Excluded(s),
+                    Bound::Included(e) | Bound::Excluded(e),
+                ) => {
+                    s < e
+                        || (s == e
+                            && matches!((start, end), (Bound::Included(_), Bound::Included(_))))
+                }
+                _ => true,
+            };
+            let got: Vec<_> = tree.range((start, end)).collect();
+            let got_rev: Vec<_> = tree.range((start, end)).rev().collect();
+            if !valid {
+                assert!(got.is_empty(), "{:?}..{:?}", start, end);
+                continue;
+            }
+            let exp: Vec<_> = map.range((start, end)).collect();
+            let exp_rev: Vec<_> = map.range((start, end)).rev().collect();
+            assert_eq!(got, exp, "{:?}..{:?}", start, end);
+            assert_eq!(got_rev, exp_rev, "{:?}..{:?}", start, end);
+        }
+    }
+}
+
+#[test]
+fn test_keys_and_values_are_lazy_views_of_items() {
+    let (tree, map) = build_with_reference(5, scattered_keys(64).map(|k| (k * 2, k)));
+    assert!(tree.keys().eq(map.keys()));
+    assert!(tree.values().eq(map.values()));
+    assert!(tree.keys().rev().eq(map.keys().rev()));
+    assert!(tree.values().rev().eq(map.values().rev()));
+
+    let mut keys = tree.keys();
+    assert_eq!(keys.next(), Some(&0));
+    assert_eq!(keys.next_back(), Some(&126));
+}
+
+#[test]
+fn test_iterators_on_empty_trees() {
+    let tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    assert_eq!(tree.items().next(), None);
+    assert_eq!(tree.items().next_back(), None);
+    assert_eq!(tree.range(1..5).next(), None);
+
+    let unrooted: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert_eq!(unrooted.items().next(), None);
+    assert_eq!(unrooted.keys().next_back(), None);
+}
+
+#[test]
+fn test_iteration_after_removals_skips_drained_leaves() {
+    let (mut tree, mut map) = build_with_reference(4, scattered_keys(100).map(|k| (k * 2, k)));
+    for k in (0..200).step_by(6) {
+        tree.remove(&k);
+        map.remove(&k);
+    }
+    assert!(tree.items().eq(map.iter()));
+    assert!(tree.range(50..150).rev().eq(map.range(50..150).rev()));
+}
@@ -0,0 +1,89 @@
+mod test_utils;
+use bplustree::{BPlusTreeMap, Entry};
+use std::ops::Bound;
+use test_utils::*;
+
+#[test]
+fn test_len_follows_every_mutation() {
+    for &cap in &[4_usize, 5, 16] {
+        let (mut tree, mut map) = build_with_reference(cap, scattered_keys(300).map(|k| (k, k)));
+        assert_eq!(tree.len(), 300);
+        assert_eq!(tree.insert(7, 0), map.insert(7, 0));
+        assert_eq!(tree.len(), map.len());
+
+        for k in (0..300).step_by(3) {
+            tree.remove(&k);
+            map.remove(&k);
+        }
+        assert_eq!(tree.len(), map.len());
+
+        if let Entry::Vacant(e) = tree.entry(3) {
+            e.insert(3);
+        }
+        map.insert(3, 3);
+        if let Entry::Occupied(e) = tree.entry(4) {
+            e.remove();
+        }
+        map.remove(&4);
+        assert_eq!(tree.len(), map.len());
+
+        let mut cursor = tree.lower_bound_mut(Bound::Included(&100));
+        assert_eq!(cursor.remove_next(), Some((100, 100)));
+        cursor.insert_before(100, 1).unwrap();
+        map.insert(100, 1);
+        tree.pop_first();
+        map.pop_first();
+        tree.pop_last();
+        map.pop_last();
+        assert_eq!(tree.len(), map.len());
+
+        tree.retain(|k, _| k % 2 == 0);
+        map.retain(|k, _| k % 2 == 0);
+        assert_eq!(tree.len(), map.len());
+        assert!(tree.check_invariants());
+
+        let right = tree.split_off(&150);
+        let map_right = map.split_off(&150);
+        assert_eq!((tree.len(), right.len()), (map.len(), map_right.len()));
+        assert!(tree.check_invariants() && right.check_invariants());
+
+        tree.clear();
+        assert_eq!(tree.len(), 0);
+        assert!(tree.is_empty());
+    }
+}
+
+#[test]
+fn test_split_off_counts_both_halves() {
+    for &cap in &[4_usize, 7] {
+        for cut in [-1, 0, 1, 50, 199, 200, 399, 400, 401] {
+            let (mut tree, mut map) =
+                build_with_reference(cap, scattered_keys(400).map(|k| (k, k)));
+            let right = tree.split_off(&cut);
+            let map_right = map.split_off(&cut);
+            assert_eq!(tree.len(), map.len(), "cap={} cut={}", cap, cut);
+            assert_eq!(right.len(), map_right.len(), "cap={} cut={}", cap, cut);
+            assert_eq!(tree.is_empty(), map.is_empty());
+            assert_eq!(right.is_empty(), map_right.is_empty());
+        }
+    }
+}
+
+#[test]
+fn test_append_adds_lengths() {
+    let (mut left, _) = build_with_reference(4, scattered_keys(100).map(|k| (k, k)));
+    let mut right = BPlusTreeMap::new(4).unwrap();
+    for k in 100..250 {
+        right.insert(k, k);
+    }
+    left.append(&mut right);
+    assert_eq!((left.len(), right.len()), (250, 0));
+
+    let mut overlapping = BPlusTreeMap::new(4).unwrap();
+    for k in (0..400).step_by(2) {
+        overlapping.insert(k, k);
+    }
+    left.append(&mut overlapping);
+    assert_eq!((left.len(), overlapping.len()), (325, 0));
+    assert!(left.check_invariants());
+}
@@ -0,0 +1,48 @@
+mod test_utils;
+use test_utils::*;
+
+#[test]
+fn test_iter_mut_updates_every_value() {
+    for &cap in &[4_usize, 5, 16] {
+        let (mut tree, mut map) = build_with_reference(cap, (0..150).map(|i| (i, i)));
+        for (k, v) in tree.iter_mut() {
+            *v += *k * 100;
+        }
+        for (k, v) in map.iter_mut() {
+            *v += *k * 100;
+        }
+        assert!(tree.items().eq(map.iter()), "cap={}", cap);
+        assert!(tree.check_invariants());
+    }
+}
+
+#[test]
+fn test_values_mut_is_double_ended() {
+    let (mut tree, _) = build_with_reference(4, (0..40).map(|i| (i, i)));
+    let mut it = tree.values_mut();
+    *it.next().unwrap() = -1;
+    *it.next_back().unwrap() = -2;
+    let rest = it.count();
+    assert_eq!(rest, 38);
+    assert_eq!(tree.get(&0), Some(&-1));
+    assert_eq!(tree.get(&39), Some(&-2));
+}
+
+#[test]
+fn test_range_mut_touches_only_the_range() {
+    let (mut tree, mut map) = build_with_reference(4, (0..100).map(|i| (i, i)));
+    for (_, v) in tree.range_mut(20..=35) {
+        *v = 0;
+    }
+    for (_, v) in map.range_mut(20..=35) {
+        *v = 0;
+    }
+    for (_, v) in tree.range_mut(60..70).rev().step_by(2) {
+        *v *= -1;
+    }
+    for (_, v) in map.range_mut(60..70).rev().step_by(2) {
+        *v *= -1;
+    }
+    assert!(tree.items().eq(map.iter()));
+    assert_eq!(tree.range_mut(150..).next(), None);
+}
@@ -0,0 +1,198 @@
+mod test_utils;
+use bplustree::{BPlusTreeError, BPlusTreeMap, NodeAllocator};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+use std::rc::Rc;
+use test_utils::*;
+
+/// The system allocator, keeping the layout of every live block so that frees
+/// can be checked against it.
+#[derive(Default)]
+struct Tracking {
+    live: RefCell<HashMap<usize, Layout>>,
+    allocations: Cell<usize>,
+}
+
+impl Tracking {
+    fn live_blocks(&self) -> usize {
+        self.live.borrow().len()
+    }
+}
+
+unsafe impl NodeAllocator for Tracking {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = NonNull::new(unsafe { System.alloc(layout) })?;
+        self.live.borrow_mut().insert(ptr.as_ptr() as usize, layout);
+        self.allocations.set(self.allocations.get() + 1);
+        Some(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let recorded = self.live.borrow_mut().remove(&(ptr.as_ptr() as usize));
+        assert_eq!(recorded, Some(layout), "freed a block it does not own");
+        System.dealloc(ptr.as_ptr(), layout);
+    }
+}
+
+/// A fixed buffer handed out front to back; frees are ignored.
+struct Bump {
+    buf: Box<[MaybeUninit<u8>]>,
+    used: Cell<usize>,
+}
+
+impl Bump {
+    fn new(bytes: usize) -> Self {
+        Bump {
+            buf: vec![MaybeUninit::uninit(); bytes].into_boxed_slice(),
+            used: Cell::new(0),
+        }
+    }
+}
+
+unsafe impl NodeAllocator for Bump {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.buf.as_ptr() as usize;
+        let start = (base + self.used.get()).next_multiple_of(layout.align()) - base;
+        let end = start.checked_add(layout.size())?;
+        if end > self.buf.len() {
+            return None;
+        }
+        self.used.set(end);
+        NonNull::new(self.buf[start..].as_ptr() as *mut u8)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+#[test]
+fn test_every_node_goes_through_the_allocator() {
+    let alloc = Tracking::default();
+    for counted in [false, true] {
+        let mut tree = if counted {
+            BPlusTreeMap::with_budgets_counted_in(128, 128, &alloc)
+        } else {
+            BPlusTreeMap::new_in(4, &alloc).unwrap()
+        };
+        let mut map = BTreeMap::new();
+        for i in 0..3000 {
+            let k = (i * 7919) % 1009;
+            if i % 3 == 2 {
+                assert_eq!(tree.remove(&k), map.remove(&k));
+            } else {
+                assert_eq!(tree.insert(k, i), map.insert(k, i));
+            }
+        }
+        assert_matches_btreemap(&tree, &map, &format!("counted={}", counted));
+        assert!(alloc.live_blocks() > tree.leaf_count());
+        tree.batch_insert((0..500).map(|k| (k * 2, k)).collect())
+            .unwrap();
+        map.extend((0..500).map(|k| (k * 2, k)));
+        tree.remove_range(100..400);
+        map.retain(|k, _| !(100..400).contains(k));
+        assert_matches_btreemap(&tree, &map, &format!("counted={} remove_range", counted));
+        if counted {
+            assert_eq!(tree.select(200), map.iter().nth(200));
+        }
+        drop(tree);
+        assert_eq!(alloc.live_blocks(), 0, "counted={}", counted);
+    }
+    assert!(alloc.allocations.get() > 100);
+}
+
+#[test]
+fn test_clear_and_owning_iterators_free_every_node() {
+    let alloc = Tracking::default();
+    let mut tree = BPlusTreeMap::with_budgets_in(128, 128, &alloc);
+    tree.extend((0..1000).map(|k| (k, k)));
+    tree.clear();
+    assert_eq!(alloc.live_blocks(), tree.pooled_nodes());
+    tree.shrink_to_fit();
+    assert_eq!(alloc.live_blocks(), 0);
+
+    tree.extend((0..1000).map(|k| (k, k)));
+    let mut iter = tree.into_iter();
+    assert_eq!(iter.next(), Some((0, 0)));
+    assert_eq!(iter.next_back(), Some((999, 999)));
+    assert!(alloc.live_blocks() > 0);
+    drop(iter);
+    assert_eq!(alloc.live_blocks(), 0);
+
+    let mut tree = BPlusTreeMap::new_in(5, &alloc).unwrap();
+    tree.extend((0..1000).map(|k| (k, k)));
+    assert!(tree.into_values().eq(0..1000));
+    assert_eq!(alloc.live_blocks(), 0);
+}
+
+#[test]
+fn test_clone_split_and_append_share_the_allocator() {
+    let alloc = Tracking::default();
+    let mut tree = BPlusTreeMap::new_in(4, &alloc).unwrap();
+    tree.extend((0..800).map(|k| (k, k)));
+    let copy = tree.try_clone().unwrap();
+    let mut right = tree.split_off(&300);
+    let mut map: BTreeMap<i32, i32> = (0..300).map(|k| (k, k)).collect();
+    assert_matches_btreemap(&tree, &map, "split_off");
+    right.insert(-1, -1);
+    tree.append(&mut right);
+    map.extend((300..800).map(|k| (k, k)));
+    map.insert(-1, -1);
+    assert_matches_btreemap(&tree, &map, "append");
+    assert!(right.is_empty());
+    assert!(std::ptr::eq(*copy.allocator(), &alloc));
+    drop((tree, right, copy));
+    assert_eq!(alloc.live_blocks(), 0);
+}
+
+#[test]
+fn test_an_exhausted_arena_fails_the_fallible_operations() {
+    let arena = Bump::new(16 * 1024);
+    let marker = Rc::new(());
+    let mut tree = BPlusTreeMap::with_budgets_in(256, 256, &arena);
+    let mut inserted = 0;
+    let err = loop {
+        match tree.try_insert(inserted, Rc::clone(&marker)) {
+            Ok(old) => assert!(old.is_none()),
+            Err(e) => break e,
+        }
+        inserted += 1;
+    };
+    assert!(matches!(err, BPlusTreeError::AllocationError(_)));
+    assert!(tree.check_invariants());
+    assert_eq!(tree.len(), inserted as usize);
+    assert_eq!(Rc::strong_count(&marker), inserted as usize + 1);
+    let err = tree.try_batch_insert(
+        (inserted..inserted + 500)
+            .map(|k| (k, Rc::clone(&marker)))
+            .collect(),
+    );
+    assert!(err.is_err());
+    assert_eq!(tree.len(), inserted as usize);
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+#[test]
+fn test_append_between_different_allocators_copies_the_pairs() {
+    let (a, b) = (Tracking::default(), Tracking::default());
+    let mut left = BPlusTreeMap::new_in(4, &a).unwrap();
+    let mut right = BPlusTreeMap::new_in(4, &b).unwrap();
+    left.extend((0..300).map(|k| (k, k)));
+    right.extend((300..700).map(|k| (k, k)));
+    left.append(&mut right);
+    let map: BTreeMap<i32, i32> = (0..700).map(|k| (k, k)).collect();
+    assert_matches_btreemap(&left, &map, "append across allocators");
+    assert!(std::ptr::eq(*left.allocator(), &a));
+    drop(right);
+    assert_eq!(b.live_blocks(), 0);
+
+    let mut empty = BPlusTreeMap::new_in(4, &b).unwrap();
+    empty.clear();
+    empty.append(&mut left);
+    assert_matches_btreemap(&empty, &map, "append into empty");
+    assert_eq!(a.live_blocks(), 0);
+    drop(empty);
+    assert_eq!(b.live_blocks(), 0);
+}
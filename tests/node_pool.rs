@@ -0,0 +1,154 @@
+mod test_utils;
+use bplustree::{BPlusTreeMap, NodeAllocator};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ptr::NonNull;
+use test_utils::*;
+
+/// The system allocator, counting allocations and live blocks.
+#[derive(Default)]
+struct Counting {
+    allocations: Cell<usize>,
+    live: Cell<usize>,
+    /// Allocations fail once this many blocks are live.
+    cap: Cell<Option<usize>>,
+}
+
+unsafe impl NodeAllocator for Counting {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.cap.get().is_some_and(|cap| self.live.get() >= cap) {
+            return None;
+        }
+        self.allocations.set(self.allocations.get() + 1);
+        self.live.set(self.live.get() + 1);
+        NonNull::new(unsafe { System.alloc(layout) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live.set(self.live.get() - 1);
+        System.dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+/// Insert and remove a band of keys over and over, splitting and merging the
+/// same leaves each round, and return the allocations made once the shape of the
+/// tree has settled.
+fn churn(tree: &mut BPlusTreeMap<i32, i32, &Counting>, alloc: &Counting) -> usize {
+    let mut map: BTreeMap<i32, i32> = tree.items().map(|(k, v)| (*k, *v)).collect();
+    let mut settled = 0;
+    for round in 0..20 {
+        if round == 10 {
+            settled = alloc.allocations.get();
+        }
+        for k in (1000..1400).step_by(3) {
+            assert_eq!(tree.insert(k, round), map.insert(k, round));
+        }
+        for k in (1000..1400).step_by(3) {
+            assert_eq!(tree.remove(&k), map.remove(&k));
+        }
+        assert!(tree.pooled_nodes() <= tree.max_pooled_nodes());
+    }
+    assert_matches_btreemap(tree, &map, "churn");
+    alloc.allocations.get() - settled
+}
+
+#[test]
+fn test_churn_reuses_pooled_blocks() {
+    for counted in [false, true] {
+        let alloc = Counting::default();
+        let mut tree = if counted {
+            BPlusTreeMap::with_budgets_counted_in(128, 128, &alloc)
+        } else {
+            BPlusTreeMap::with_budgets_in(128, 128, &alloc)
+        };
+        tree.extend((0..2000).step_by(2).map(|k| (k, k)));
+        tree.set_max_pooled_nodes(64);
+        assert_eq!(churn(&mut tree, &alloc), 0, "counted={}", counted);
+        if counted {
+            // The churn also removes the even keys of the band it passes over.
+            let map: BTreeMap<i32, i32> = (0..2000)
+                .step_by(2)
+                .filter(|k| !(1000..1400).contains(k) || k % 3 != 1)
+                .map(|k| (k, k))
+                .collect();
+            assert_eq!(tree.select(700), map.iter().nth(700));
+            assert_eq!(tree.rank(&1500), map.range(..1500).count());
+        }
+
+        tree.set_max_pooled_nodes(0);
+        assert_eq!(tree.pooled_nodes(), 0);
+        assert!(churn(&mut tree, &alloc) > 0);
+        drop(tree);
+        assert_eq!(alloc.live.get(), 0);
+    }
+}
+
+#[test]
+fn test_pool_limit_and_shrink_to_fit() {
+    let alloc = Counting::default();
+    let mut tree = BPlusTreeMap::new_in(4, &alloc).unwrap();
+    assert_eq!(tree.max_pooled_nodes(), 16);
+    tree.extend((0..1000).map(|k| (k, k)));
+    let live = alloc.live.get();
+    for k in 0..900 {
+        tree.remove(&k);
+        assert!(tree.pooled_nodes() <= 16);
+    }
+    assert_eq!(tree.pooled_nodes(), 16);
+    tree.set_max_pooled_nodes(5);
+    assert_eq!(tree.pooled_nodes(), 5);
+    tree.shrink_to_fit();
+    assert_eq!(tree.pooled_nodes(), 0);
+    assert!(alloc.live.get() < live / 5);
+
+    // A clone and the right half of a split keep the limit.
+    tree.set_max_pooled_nodes(3);
+    let mut copy = tree.clone();
+    let right = copy.split_off(&950);
+    assert_eq!((copy.max_pooled_nodes(), right.max_pooled_nodes()), (3, 3));
+    copy.clear();
+    assert_eq!(copy.pooled_nodes(), 3);
+    drop((tree, copy, right));
+    assert_eq!(alloc.live.get(), 0);
+}
+
+#[test]
+fn test_same_sized_leaves_and_branches_share_the_pool() {
+    let alloc = Counting::default();
+    let mut tree = BPlusTreeMap::with_budgets_in(256, 256, &alloc);
+    assert_eq!(tree.leaf_layout().bytes, tree.branch_layout().bytes);
+    tree.extend((0..2000).map(|k| (k, k)));
+    tree.clear();
+    assert_eq!(tree.pooled_nodes(), 16);
+    // The pooled blocks were mostly leaves; rebuilding needs branches as well.
+    let allocations = alloc.allocations.get();
+    tree.extend((0..200).map(|k| (k, k)));
+    assert!(tree.leaf_count() > 1);
+    assert_eq!(alloc.allocations.get(), allocations);
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_pooled_blocks_back_fallible_inserts() {
+    let alloc = Counting::default();
+    let mut tree = BPlusTreeMap::new_in(4, &alloc).unwrap();
+    tree.extend((0..400).map(|k| (k * 2, k)));
+    let mut map: BTreeMap<i32, i32> = tree.items().map(|(k, v)| (*k, *v)).collect();
+    alloc.cap.set(Some(alloc.live.get()));
+    let mut next = 1;
+    while tree.try_insert(next, 0).is_ok() {
+        map.insert(next, 0);
+        next += 2;
+    }
+    assert_matches_btreemap(&tree, &map, "inserts up to the cap");
+    // Merges refill the pool, which the next inserts draw from.
+    for k in 0..200 {
+        assert_eq!(tree.remove(&k), map.remove(&k));
+    }
+    assert!(tree.pooled_nodes() > 0);
+    for k in next..next + 20 {
+        assert_eq!(tree.try_insert(k, 0), Ok(map.insert(k, 0)));
+    }
+    assert_matches_btreemap(&tree, &map, "inserts from the pool");
+}
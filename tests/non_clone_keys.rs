@@ -0,0 +1,189 @@
+use bplustree::BPlusTreeMap;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Heap-backed key without `Clone`, so separators cannot be clones; a separator
+/// left linked to a freed leaf would read freed memory.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Handle(String);
+
+fn handle(i: u32) -> Handle {
+    Handle(format!("{:06}", i))
+}
+
+fn assert_same(tree: &BPlusTreeMap<Handle, u32>, map: &BTreeMap<u32, u32>, ctx: &str) {
+    if let Err(e) = tree.check_invariants_detailed() {
+        panic!("{}: {}", ctx, e);
+    }
+    assert_eq!(tree.len(), map.len(), "{}", ctx);
+    assert!(
+        tree.items()
+            .map(|(k, v)| (k.0.parse::<u32>().unwrap(), *v))
+            .eq(map.iter().map(|(k, v)| (*k, *v))),
+        "{}",
+        ctx
+    );
+}
+
+#[test]
+fn test_insert_remove_matches_btreemap() {
+    let mut seed = 0x9e37_79b9_u32;
+    for &cap in &[4_usize, 5, 8] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for step in 0..4000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let k = (seed >> 16) % 500;
+            if (seed >> 8) % 3 == 1 {
+                assert_eq!(tree.remove(&handle(k)), map.remove(&k), "cap={}", cap);
+            } else {
+                assert_eq!(
+                    tree.insert(handle(k), step),
+                    map.insert(k, step),
+                    "cap={}",
+                    cap
+                );
+            }
+            if step % 500 == 0 {
+                assert_same(&tree, &map, "random ops");
+            }
+        }
+        assert_same(&tree, &map, "random ops");
+        for k in 0..500 {
+            assert_eq!(tree.get(&handle(k)), map.get(&k));
+        }
+    }
+}
+
+#[test]
+fn test_removing_first_keys_keeps_lookups_working() {
+    for &cap in &[4_usize, 5, 8] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for i in 0..600 {
+            tree.insert(handle(i), i);
+            map.insert(i, i);
+        }
+        // Ascending removals keep taking the first key of some leaf, which the
+        // separators linked to that leaf must follow.
+        for stride in [5_u32, 3, 2] {
+            for k in (0..600).filter(|k| k % stride == 0) {
+                assert_eq!(tree.remove(&handle(k)), map.remove(&k));
+            }
+            assert_same(&tree, &map, "first keys");
+            for k in 0..600 {
+                assert_eq!(tree.contains_key(&handle(k)), map.contains_key(&k));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_bulk_operations_without_clone() {
+    for &cap in &[4_usize, 5, 8] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for i in 0..800 {
+            tree.insert(handle(i * 2), i);
+            map.insert(i * 2, i);
+        }
+
+        tree.retain(|k, _| !k.0.ends_with('4'));
+        map.retain(|k, _| k % 10 != 4);
+        assert_same(&tree, &map, "retain");
+
+        let lo = handle(301);
+        let hi = handle(901);
+        let got: Vec<u32> = tree
+            .extract_if((Bound::Included(lo), Bound::Excluded(hi)), |_, v| {
+                *v % 3 != 0
+            })
+            .map(|(_, v)| v)
+            .collect();
+        let expected: Vec<u32> = map
+            .range(301..901)
+            .filter(|(_, v)| *v % 3 != 0)
+            .map(|(_, v)| *v)
+            .collect();
+        map.retain(|k, v| !((301..901).contains(k) && *v % 3 != 0));
+        assert_eq!(got, expected);
+        assert_same(&tree, &map, "extract_if");
+
+        let mut right = tree.split_off(&handle(777));
+        let mut map_right = map.split_off(&777);
+        assert_same(&tree, &map, "split_off left");
+        assert_same(&right, &map_right, "split_off right");
+
+        right.insert(handle(5000), 1);
+        map_right.insert(5000, 1);
+        tree.append(&mut right);
+        map.append(&mut map_right);
+        assert!(right.is_empty());
+        assert_same(&tree, &map, "append");
+
+        while let Some((k, v)) = tree.pop_first() {
+            assert_eq!(Some((k.0.parse().unwrap(), v)), map.pop_first());
+            if map.len() % 97 == 0 {
+                assert_same(&tree, &map, "pop_first");
+            }
+        }
+        assert!(map.is_empty());
+    }
+}
+
+#[test]
+fn test_cursor_and_entry_removal_without_clone() {
+    for &cap in &[4_usize, 5] {
+        let mut tree = BPlusTreeMap::new(cap).unwrap();
+        let mut map = BTreeMap::new();
+        for i in 0..400 {
+            tree.insert(handle(i), i);
+            map.insert(i, i);
+        }
+        let mut cursor = tree.lower_bound_mut(Bound::Included(&handle(100)));
+        for _ in 0..150 {
+            let (k, v) = cursor.remove_next().unwrap();
+            assert_eq!(map.remove(&v), Some(v));
+            assert_eq!(k.0.parse::<u32>().unwrap(), v);
+            cursor.move_next();
+        }
+        assert_same(&tree, &map, "cursor");
+
+        for i in (0..400).step_by(7) {
+            if let bplustree::Entry::Occupied(e) = tree.entry(handle(i)) {
+                assert_eq!(e.remove(), i);
+                map.remove(&i);
+            }
+        }
+        assert_same(&tree, &map, "entry");
+    }
+}
+
+/// Key whose text can be rewritten while it sits in the map.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Mutable(RefCell<String>);
+
+#[test]
+fn test_keys_mutated_in_place_stay_reachable() {
+    let key = |i: u32| Mutable(RefCell::new(format!("{:06}", i)));
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    for i in 0..300 {
+        tree.insert(key(i), i);
+    }
+    // Growing each key reallocates its buffer. Separators read keys from the
+    // leaves, and order is kept, so every key stays reachable.
+    for k in tree.keys() {
+        k.0.borrow_mut().push_str("-with-a-much-longer-suffix");
+    }
+    for i in 0..300 {
+        let grown = Mutable(RefCell::new(format!("{:06}-with-a-much-longer-suffix", i)));
+        assert_eq!(tree.get(&grown), Some(&i));
+        if i % 2 == 0 {
+            assert_eq!(tree.remove(&grown), Some(i));
+        }
+    }
+    assert_eq!(tree.len(), 150);
+    assert!(tree.check_invariants());
+    assert!(tree.values().copied().eq((1..300).step_by(2)));
+}
@@ -0,0 +1,252 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use test_utils::*;
+
+/// Deterministic pseudo-random keys in `0..modulus`.
+fn keys(n: usize, seed: u64, modulus: i32) -> Vec<i32> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % modulus as u64) as i32
+        })
+        .collect()
+}
+
+fn counted(cap: usize) -> BPlusTreeMap<i32, i32> {
+    let tree = BPlusTreeMap::new_counted(cap).unwrap();
+    assert!(tree.is_counted());
+    tree
+}
+
+/// Every order statistic against the ordered contents of `map`.
+fn assert_ranks(tree: &BPlusTreeMap<i32, i32>, map: &BTreeMap<i32, i32>, ctx: &str) {
+    let sorted: Vec<(&i32, &i32)> = map.iter().collect();
+    for (i, &(k, v)) in sorted.iter().enumerate() {
+        assert_eq!(tree.select(i), Some((k, v)), "{} select {}", ctx, i);
+        assert_eq!(tree.rank(k), i, "{} rank {}", ctx, k);
+        assert_eq!(tree.rank(&(k + 1)), map.range(..k + 1).count(), "{}", ctx);
+    }
+    assert_eq!(tree.select(map.len()), None, "{}", ctx);
+    let hi = map.keys().next_back().map_or(0, |k| k + 2);
+    for lo in (-2..hi).step_by(7) {
+        for len in [0, 1, 5, 40] {
+            let end = lo + len;
+            assert_eq!(
+                tree.range_count(lo..end),
+                map.range(lo..end).count(),
+                "{}",
+                ctx
+            );
+            assert_eq!(tree.range_count(lo..=end), map.range(lo..=end).count());
+            assert_eq!(tree.range_count(..end), map.range(..end).count());
+            assert_eq!(tree.range_count(lo..), map.range(lo..).count());
+        }
+    }
+    assert_eq!(tree.range_count::<i32, _>(..), map.len());
+}
+
+#[test]
+fn test_rank_select_match_btreemap_while_growing_and_shrinking() {
+    for &cap in &[4_usize, 5, 16] {
+        let mut tree = counted(cap);
+        let mut map = BTreeMap::new();
+        for (step, k) in keys(1500, cap as u64, 900).into_iter().enumerate() {
+            if step % 3 == 2 {
+                assert_eq!(tree.remove(&k), map.remove(&k));
+            } else {
+                assert_eq!(tree.insert(k, step as i32), map.insert(k, step as i32));
+            }
+            if step % 250 == 0 {
+                let ctx = format!("cap={} step={}", cap, step);
+                assert_matches_btreemap(&tree, &map, &ctx);
+                assert_ranks(&tree, &map, &ctx);
+            }
+        }
+        let ctx = format!("cap={}", cap);
+        assert_matches_btreemap(&tree, &map, &ctx);
+        assert_ranks(&tree, &map, &ctx);
+    }
+}
+
+#[test]
+fn test_remove_nth_matches_btreemap() {
+    for &cap in &[4_usize, 5, 16] {
+        let mut tree = counted(cap);
+        let mut map = BTreeMap::new();
+        for k in 0..600 {
+            tree.insert(k * 3, k);
+            map.insert(k * 3, k);
+        }
+        for (step, r) in keys(550, 7, 1_000_000).into_iter().enumerate() {
+            let index = r as usize % map.len();
+            let expected = *map.keys().nth(index).unwrap();
+            let pair = map.remove_entry(&expected);
+            assert_eq!(tree.remove_nth(index), pair);
+            if step % 100 == 0 {
+                assert_matches_btreemap(&tree, &map, &format!("cap={} step={}", cap, step));
+            }
+        }
+        assert_eq!(tree.remove_nth(map.len()), None);
+        assert_matches_btreemap(&tree, &map, &format!("cap={}", cap));
+        assert_ranks(&tree, &map, &format!("cap={}", cap));
+    }
+}
+
+#[test]
+fn test_counts_survive_every_mutating_api() {
+    for &cap in &[4_usize, 5] {
+        let mut tree = counted(cap);
+        let mut map = BTreeMap::new();
+        for k in keys(800, 3, 2000) {
+            *tree.entry(k).or_insert(0) += 1;
+            *map.entry(k).or_insert(0) += 1;
+        }
+        assert_matches_btreemap(&tree, &map, "entry");
+
+        {
+            let mut cursor = tree.lower_bound_mut(Bound::Included(&500));
+            let mut expected = map.range(500..).map(|(k, _)| *k);
+            for _ in 0..60 {
+                let k = expected.next().unwrap();
+                assert_eq!(cursor.remove_next().map(|(k, _)| k), Some(k));
+            }
+        }
+        let gone: Vec<i32> = map.range(500..).take(60).map(|(k, _)| *k).collect();
+        for k in gone {
+            map.remove(&k);
+        }
+        assert_matches_btreemap(&tree, &map, "cursor remove");
+
+        {
+            let mut cursor = tree.lower_bound_mut(Bound::Unbounded);
+            while let Some((&k, _)) = cursor.peek_next() {
+                if k % 4 == 1 && !map.contains_key(&(k - 1)) {
+                    cursor.insert_before(k - 1, 0).unwrap();
+                    map.insert(k - 1, 0);
+                }
+                cursor.move_next();
+            }
+        }
+        assert_matches_btreemap(&tree, &map, "cursor insert");
+        assert_ranks(&tree, &map, "cursor insert");
+
+        for k in (1..2000).step_by(2) {
+            if let Some(e) = tree.first_entry() {
+                if e.key() % 5 == 0 {
+                    e.remove();
+                    map.pop_first();
+                }
+            }
+            tree.entry(k).or_insert(k);
+            map.entry(k).or_insert(k);
+        }
+        assert_matches_btreemap(&tree, &map, "entry churn");
+
+        tree.retain(|k, _| k % 7 != 0);
+        map.retain(|k, _| k % 7 != 0);
+        assert_matches_btreemap(&tree, &map, "retain");
+        assert_ranks(&tree, &map, "retain");
+
+        let taken: Vec<_> = tree.extract_if(.., |k, _| k % 3 == 0).collect();
+        let expected: Vec<_> = map.extract_if(.., |k, _| k % 3 == 0).collect();
+        assert_eq!(taken, expected);
+        assert_matches_btreemap(&tree, &map, "extract_if");
+
+        let right = tree.split_off(&900);
+        let map_right = map.split_off(&900);
+        assert_matches_btreemap(&tree, &map, "split left");
+        assert_matches_btreemap(&right, &map_right, "split right");
+        assert_ranks(&right, &map_right, "split right");
+
+        let mut right = right;
+        let mut map_right = map_right;
+        tree.append(&mut right);
+        map.append(&mut map_right);
+        assert_matches_btreemap(&tree, &map, "append");
+        assert_ranks(&tree, &map, "append");
+
+        let copy = tree.clone();
+        assert!(copy.is_counted());
+        assert_matches_btreemap(&copy, &map, "clone");
+        assert_ranks(&copy, &map, "clone");
+    }
+}
+
+#[test]
+fn test_uncounted_maps_answer_the_same_queries() {
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    assert!(!tree.is_counted());
+    let mut map = BTreeMap::new();
+    for k in keys(500, 11, 700) {
+        tree.insert(k, -k);
+        map.insert(k, -k);
+    }
+    assert_ranks(&tree, &map, "uncounted");
+    for index in [0, 17, 100] {
+        let expected = *map.keys().nth(index).unwrap();
+        assert_eq!(tree.remove_nth(index), map.remove_entry(&expected));
+    }
+    assert_matches_btreemap(&tree, &map, "uncounted remove_nth");
+}
+
+#[test]
+fn test_iterators_know_their_length_and_skip_ahead() {
+    for counted_map in [true, false] {
+        let mut tree = if counted_map {
+            counted(5)
+        } else {
+            BPlusTreeMap::new(5).unwrap()
+        };
+        let mut map = BTreeMap::new();
+        for k in 0..1000 {
+            tree.insert(k * 2, k);
+            map.insert(k * 2, k);
+        }
+        assert_eq!(tree.items().len(), 1000);
+        assert_eq!(tree.keys().len(), 1000);
+        assert_eq!(tree.values().len(), 1000);
+        // A range only knows its length from the counts; without them it does
+        // not walk its leaves to find out.
+        let hint = |n: usize| if counted_map { (n, Some(n)) } else { (0, None) };
+        assert_eq!(tree.range(100..=300).size_hint(), hint(101));
+        assert_eq!(tree.range(100..=300).count(), map.range(100..=300).count());
+        assert_eq!(tree.range(7..7).size_hint(), hint(0));
+        assert_eq!(tree.range(..).size_hint(), (1000, Some(1000)));
+        assert_eq!(tree.range(51..1700).nth(5000), None);
+        assert_eq!(tree.range(51..1700).nth_back(5000), None);
+
+        let mut it = tree.range(51..1700);
+        let mut expected = map.range(51..1700);
+        for step in [0, 3, 40, 1, 200, 0, 500] {
+            assert_eq!(it.nth(step), expected.nth(step));
+            assert_eq!(it.size_hint(), hint(expected.clone().count()));
+            assert_eq!(it.nth_back(step / 2), expected.nth_back(step / 2));
+            assert_eq!(it.size_hint(), hint(expected.clone().count()));
+        }
+        assert!(it.eq(expected));
+        let mut keys = tree.keys();
+        let mut expected = map.keys();
+        while let Some(k) = keys.next() {
+            assert_eq!(Some(k), expected.next());
+            assert_eq!(keys.next_back(), expected.next_back());
+            assert_eq!(keys.len(), expected.len());
+        }
+        assert!(tree.items().skip(777).eq(map.iter().skip(777)));
+        assert_eq!(tree.items().nth(999), map.iter().nth(999));
+        assert_eq!(tree.items().nth(1000), None);
+    }
+}
+
+#[test]
+fn test_counted_layout_trades_branch_capacity_for_counts() {
+    let plain = BPlusTreeMap::<u64, u64>::with_budgets(512, 512);
+    let counted = BPlusTreeMap::<u64, u64>::with_budgets_counted(512, 512);
+    assert!(counted.is_counted());
+    assert_eq!(plain.leaf_layout(), counted.leaf_layout());
+    assert!(counted.branch_layout().cap < plain.branch_layout().cap);
+}
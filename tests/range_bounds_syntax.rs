@@ -97,6 +97,7 @@ fn test_range_syntax_empty_ranges() {
     }
 
     // Empty range - start > end
+    #[allow(clippy::reversed_empty_ranges)]
     let range: Vec<_> = tree.range(7..3).collect();
     assert_eq!(range, vec![]);
 
@@ -0,0 +1,107 @@
+mod test_utils;
+use bplustree::{BPlusTreeError, BPlusTreeMap, Region};
+use std::collections::BTreeMap;
+use std::mem::MaybeUninit;
+use std::rc::Rc;
+use test_utils::*;
+
+#[test]
+fn test_region_map_matches_btreemap() {
+    let mut buf = vec![MaybeUninit::uninit(); 256 * 1024];
+    for counted in [false, true] {
+        let mut tree = if counted {
+            BPlusTreeMap::with_budgets_counted_in(128, 128, Region::new(&mut buf))
+        } else {
+            BPlusTreeMap::in_region(&mut buf, 128, 128)
+        };
+        let mut map = BTreeMap::new();
+        for i in 0..6000 {
+            let k = (i * 7919) % 1999;
+            if i % 3 == 2 {
+                assert_eq!(tree.remove(&k), map.remove(&k));
+            } else {
+                assert_eq!(tree.try_insert(k, i), Ok(map.insert(k, i)));
+            }
+        }
+        assert_matches_btreemap(&tree, &map, &format!("counted={}", counted));
+        if counted {
+            assert_eq!(tree.select(400), map.iter().nth(400));
+        }
+        drop(tree);
+    }
+}
+
+#[test]
+fn test_freed_nodes_are_reused() {
+    let mut buf = vec![MaybeUninit::uninit(); 64 * 1024];
+    let mut tree = BPlusTreeMap::in_region(&mut buf, 128, 128);
+    tree.set_max_pooled_nodes(0);
+    for round in 0..10 {
+        for k in 0..500 {
+            tree.insert(k, round);
+        }
+        for k in 0..500 {
+            assert_eq!(tree.remove(&k), Some(round));
+        }
+        if round == 0 {
+            tree.clear();
+        }
+    }
+    let unused = tree.allocator().unused_bytes();
+    for k in 0..500 {
+        tree.insert(k, 0);
+    }
+    assert_eq!(tree.allocator().unused_bytes(), unused);
+    let live = tree.allocator().live_nodes();
+    assert!(live > tree.leaf_count());
+    tree.clear();
+    assert_eq!(tree.allocator().live_nodes(), 0);
+}
+
+#[test]
+fn test_an_exhausted_region_fails_the_fallible_operations() {
+    let mut buf = [MaybeUninit::uninit(); 8 * 1024];
+    let marker = Rc::new(());
+    let mut tree = BPlusTreeMap::in_region(&mut buf, 256, 256);
+    let mut inserted = 0;
+    let err = loop {
+        match tree.try_insert(inserted, Rc::clone(&marker)) {
+            Ok(old) => assert!(old.is_none()),
+            Err(e) => break e,
+        }
+        inserted += 1;
+    };
+    assert!(matches!(err, BPlusTreeError::AllocationError(_)));
+    assert!(tree.check_invariants());
+    assert_eq!(tree.len(), inserted as usize);
+    assert_eq!(Rc::strong_count(&marker), inserted as usize + 1);
+    assert!(tree.try_reserve(1000).is_err());
+
+    // Removals free room for more inserts.
+    for k in 0..inserted / 2 {
+        tree.remove(&k);
+    }
+    for k in inserted..inserted + 10 {
+        assert_eq!(tree.try_insert(k, Rc::clone(&marker)), Ok(None));
+    }
+    assert!(tree.check_invariants());
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+#[test]
+fn test_maps_can_share_a_region() {
+    let mut buf = vec![MaybeUninit::uninit(); 128 * 1024];
+    let region = Region::new(&mut buf);
+    let mut small = BPlusTreeMap::with_budgets_in(128, 128, &region);
+    let mut large = BPlusTreeMap::with_budgets_in(512, 256, &region);
+    small.extend((0..1000).map(|k| (k, k)));
+    large.extend((0..1000).map(|k| (k, -k)));
+    assert!(small.leaf_count() > large.leaf_count());
+    large.append(&mut small);
+    assert!(small.is_empty());
+    assert_matches_btreemap(&large, &(0..1000).map(|k| (k, k)).collect(), "append");
+    drop((small, large));
+    assert_eq!(region.live_nodes(), 0);
+    assert!(region.unused_bytes() < 128 * 1024);
+}
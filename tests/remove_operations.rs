@@ -12,10 +12,10 @@ static ALLOC_BYTES: AtomicUsize = AtomicUsize::new(0);
 static DEALLOC_CALLS: AtomicUsize = AtomicUsize::new(0);
 
 thread_local! {
-    static TL_ALLOC_CALLS: Cell<usize> = Cell::new(0);
-    static TL_ALLOC_BYTES: Cell<usize> = Cell::new(0);
-    static TL_DEALLOC_CALLS: Cell<usize> = Cell::new(0);
-    static TL_DEALLOC_BYTES: Cell<usize> = Cell::new(0);
+    static TL_ALLOC_CALLS: Cell<usize> = const { Cell::new(0) };
+    static TL_ALLOC_BYTES: Cell<usize> = const { Cell::new(0) };
+    static TL_DEALLOC_CALLS: Cell<usize> = const { Cell::new(0) };
+    static TL_DEALLOC_BYTES: Cell<usize> = const { Cell::new(0) };
 }
 
 static DEALLOC_BYTES: AtomicUsize = AtomicUsize::new(0);
@@ -106,6 +106,9 @@ fn test_delete_forces_root_collapse() {
         2,
         "setup should create a branch root with two leaves"
     );
+    // Count the frees themselves: by default the pool would keep both nodes,
+    // which test_root_collapse_pools_freed_nodes_by_default covers.
+    tree.set_max_pooled_nodes(0);
 
     reset_alloc_metrics();
     let removed = tree.remove(&0);
@@ -152,6 +155,9 @@ fn test_delete_last_forces_root_collapse() {
         2,
         "setup should create a branch root with two leaves"
     );
+    // Count the frees themselves: by default the pool would keep both nodes,
+    // which test_root_collapse_pools_freed_nodes_by_default covers.
+    tree.set_max_pooled_nodes(0);
 
     reset_alloc_metrics();
     let removed = tree.remove(&4);
@@ -186,6 +192,35 @@ fn test_delete_last_forces_root_collapse() {
     );
 }
 
+#[test]
+fn test_root_collapse_pools_freed_nodes_by_default() {
+    let mut tree = create_tree_capacity_int(4);
+    for i in 0..5 {
+        tree.insert(i, i * 10);
+    }
+    assert!(!tree.is_leaf_root());
+    assert_eq!(tree.pooled_nodes(), 0);
+
+    reset_alloc_metrics();
+    assert_eq!(tree.remove(&0), Some(0));
+    let (alloc_calls, _alloc_bytes, dealloc_calls, _dealloc_bytes) = alloc_metrics();
+    assert_eq!((alloc_calls, dealloc_calls), (0, 0));
+    assert_eq!(
+        tree.pooled_nodes(),
+        2,
+        "the emptied leaf and the old branch root should be pooled"
+    );
+    assert!(tree.is_leaf_root());
+
+    // The next split takes its leaf and branch from the pool.
+    reset_alloc_metrics();
+    tree.insert(0, 0);
+    tree.insert(5, 50);
+    assert!(!tree.is_leaf_root());
+    assert_eq!(alloc_metrics().0, 0);
+    assert_eq!(tree.pooled_nodes(), 0);
+}
+
 #[test]
 fn test_delete_requires_branch_borrow() {
     let mut tree = create_tree_capacity_int(4);
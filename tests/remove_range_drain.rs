@@ -0,0 +1,157 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+use test_utils::*;
+
+/// Remove `range` from `map` the slow way.
+fn expected_removal<R: RangeBounds<i32>>(
+    map: &mut BTreeMap<i32, i32>,
+    range: R,
+) -> Vec<(i32, i32)> {
+    let keys: Vec<i32> = map.range(range).map(|(k, _)| *k).collect();
+    keys.iter().map(|k| map.remove_entry(k).unwrap()).collect()
+}
+
+fn ranges(n: i32) -> Vec<(Bound<i32>, Bound<i32>)> {
+    let mut out = vec![(Bound::Unbounded, Bound::Unbounded)];
+    for lo in [-3, 0, 1, n / 7, n / 3, n / 2, n - 1, n + 4] {
+        for len in [0, 1, 2, 9, n / 5, n] {
+            out.push((Bound::Included(lo), Bound::Excluded(lo + len)));
+            out.push((Bound::Excluded(lo), Bound::Included(lo + len)));
+        }
+        out.push((Bound::Included(lo), Bound::Unbounded));
+        out.push((Bound::Unbounded, Bound::Excluded(lo)));
+    }
+    out
+}
+
+#[test]
+fn test_remove_range_matches_btreemap() {
+    for &cap in &[4_usize, 5, 8] {
+        for n in [0, 1, 6, 70, 400] {
+            for range in ranges(n) {
+                let (mut tree, mut map) =
+                    build_with_reference(cap, scattered_keys(n).map(|k| (k, k * 10)));
+                let removed = tree.remove_range(range);
+                assert_eq!(removed, expected_removal(&mut map, range).len());
+                let ctx = format!("cap={} n={} range={:?}", cap, n, range);
+                assert_matches_btreemap(&tree, &map, &ctx);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_drain_yields_the_range_from_both_ends() {
+    for &cap in &[4_usize, 5, 8] {
+        for range in ranges(300) {
+            let (mut tree, mut map) =
+                build_with_reference(cap, scattered_keys(300).map(|k| (k, k * 10)));
+            let expected = expected_removal(&mut map, range);
+            let mut drain = tree.drain(range);
+            // Without counts only draining the whole map (or nothing) knows its
+            // length; no leaves are walked to find it.
+            let exact = range == (Bound::Unbounded, Bound::Unbounded) || expected.is_empty();
+            let hint = |left: usize| if exact { (left, Some(left)) } else { (0, None) };
+            assert_eq!(drain.size_hint(), hint(expected.len()));
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            let mut from_back = false;
+            loop {
+                let next = if from_back {
+                    drain.next_back().map(|p| back.push(p))
+                } else {
+                    drain.next().map(|p| front.push(p))
+                };
+                if next.is_none() {
+                    break;
+                }
+                assert_eq!(
+                    drain.size_hint(),
+                    hint(expected.len() - front.len() - back.len())
+                );
+                from_back = !from_back;
+            }
+            drop(drain);
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, expected);
+            assert_matches_btreemap(&tree, &map, &format!("cap={} range={:?}", cap, range));
+        }
+    }
+}
+
+#[test]
+fn test_dropping_a_partially_consumed_drain_removes_the_whole_range() {
+    for &cap in &[4_usize, 5] {
+        for taken in [0, 1, 7, 100] {
+            let (mut tree, mut map) =
+                build_with_reference(cap, scattered_keys(500).map(|k| (k, k * 10)));
+            let expected = expected_removal(&mut map, 40..260);
+            let got: Vec<(i32, i32)> = tree.drain(40..260).take(taken).collect();
+            assert_eq!(got, expected[..taken]);
+            assert_matches_btreemap(&tree, &map, &format!("cap={} taken={}", cap, taken));
+            for k in (0..600).step_by(3) {
+                assert_eq!(tree.insert(k, k), map.insert(k, k));
+            }
+            assert_matches_btreemap(&tree, &map, &format!("cap={} taken={} reuse", cap, taken));
+        }
+    }
+}
+
+#[test]
+fn test_counted_maps_keep_counts_through_range_removal() {
+    let mut tree = BPlusTreeMap::new_counted(4).unwrap();
+    let mut map = BTreeMap::new();
+    for k in 0..800 {
+        tree.insert(k, k);
+        map.insert(k, k);
+    }
+    tree.remove_range(100..650);
+    expected_removal(&mut map, 100..650);
+    assert_matches_btreemap(&tree, &map, "counted");
+    assert_eq!(tree.rank(&700), map.range(..700).count());
+    assert_eq!(tree.select(120), map.iter().nth(120));
+    let mut drain = tree.drain(20..=700);
+    let len = map.range(20..=700).count();
+    assert_eq!(drain.size_hint(), (len, Some(len)));
+    drain.nth_back(9);
+    assert_eq!(drain.size_hint(), (len - 10, Some(len - 10)));
+    drop(drain);
+    expected_removal(&mut map, 20..=700);
+    assert!(tree.drain(..).rev().eq(map.into_iter().rev()));
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn test_drain_drops_each_value_once() {
+    let marker = Rc::new(());
+    let mut tree: BPlusTreeMap<i32, Rc<()>> = BPlusTreeMap::new(4).unwrap();
+    for i in 0..200 {
+        tree.insert(i, Rc::clone(&marker));
+    }
+    let drained: Vec<_> = tree.drain(50..150).take(30).collect();
+    assert_eq!(Rc::strong_count(&marker), 131);
+    drop(drained);
+    assert_eq!(Rc::strong_count(&marker), 101);
+    assert_eq!(tree.remove_range(..10), 10);
+    assert_eq!(Rc::strong_count(&marker), 91);
+    assert_eq!(tree.len(), 90);
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+#[test]
+fn test_drain_on_empty_trees_and_ranges() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    assert_eq!(tree.drain(..).next(), None);
+    assert_eq!(tree.remove_range(3..9), 0);
+    let (mut tree, map) = build_with_reference(4, scattered_keys(100).map(|k| (k, k * 10)));
+    assert_eq!(tree.drain(30..30).size_hint(), (0, Some(0)));
+    assert_eq!(
+        tree.remove_range((Bound::Excluded(5), Bound::Excluded(5))),
+        0
+    );
+    assert_matches_btreemap(&tree, &map, "empty ranges");
+}
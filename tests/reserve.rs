@@ -0,0 +1,148 @@
+use bplustree::BPlusTreeMap;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+/// The system allocator, counting this thread's allocations while asked to.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get().map(|n| n + 1)));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Run `f` and count the allocations it makes.
+fn allocations_in(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|n| n.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|n| n.replace(None)).unwrap()
+}
+
+fn new_tree(capacity: usize, counted: bool) -> BPlusTreeMap<i32, i32> {
+    if counted {
+        BPlusTreeMap::new_counted(capacity).unwrap()
+    } else {
+        BPlusTreeMap::new(capacity).unwrap()
+    }
+}
+
+/// Key orders that split as many nodes as they can in different ways.
+fn patterns(n: i32) -> Vec<Vec<i32>> {
+    vec![
+        (0..n).collect(),
+        (0..n).rev().collect(),
+        (0..n).map(|i| (i * 7919) % n).collect(),
+        (0..n).map(|i| (i % 10) * n + i / 10).collect(),
+    ]
+}
+
+#[test]
+fn test_reserved_inserts_never_allocate() {
+    for (capacity, counted) in [(4, false), (4, true), (5, false), (16, true)] {
+        for (p, keys) in patterns(3000).into_iter().enumerate() {
+            for base in [0, 1, 2] {
+                let mut tree = new_tree(capacity, counted);
+                match base {
+                    // No root at all.
+                    0 => tree.clear(),
+                    // Full leaves and branches, so the first inserts split all the way up.
+                    1 => tree
+                        .bulk_load((0..2000).map(|k| (k * 20 + 1, k)), 1.0)
+                        .unwrap(),
+                    _ => tree.extend((0..500).map(|k| (k * 60 + 2, k))),
+                }
+                let mut map: BTreeMap<i32, i32> = tree.items().map(|(k, v)| (*k, *v)).collect();
+                tree.reserve(keys.len());
+                let allocations = allocations_in(|| {
+                    for &k in &keys {
+                        tree.insert(k, -k);
+                    }
+                });
+                let ctx = format!(
+                    "cap={} counted={} pattern={} base={}",
+                    capacity, counted, p, base
+                );
+                assert_eq!(allocations, 0, "{}", ctx);
+                map.extend(keys.iter().map(|&k| (k, -k)));
+                if let Err(e) = tree.check_invariants_detailed() {
+                    panic!("{}: {}", ctx, e);
+                }
+                assert!(tree.items().eq(map.iter()), "{}", ctx);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_with_capacity() {
+    let mut tree = BPlusTreeMap::with_capacity(5000);
+    assert!(tree.is_empty());
+    let keys = &patterns(5000)[2];
+    let allocations = allocations_in(|| {
+        for &k in keys {
+            tree.insert(k, k);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert!(tree.check_invariants());
+    assert!(tree.keys().copied().eq(0..5000));
+}
+
+#[test]
+fn test_reservations_survive_other_operations() {
+    let mut tree = new_tree(4, true);
+    tree.extend((0..1000).map(|k| (k * 2, k)));
+    tree.reserve(200);
+    let reserved = tree.pooled_nodes();
+    assert!(reserved > tree.max_pooled_nodes());
+    // Reserving again for the same inserts takes nothing more.
+    tree.reserve(100);
+    assert_eq!(tree.pooled_nodes(), reserved);
+    // Nor do fallible inserts or a lower pool limit trim the reservation.
+    tree.try_insert(1, 0).unwrap();
+    tree.set_max_pooled_nodes(0);
+    let allocations = allocations_in(|| {
+        for k in 0..199 {
+            tree.insert(k * 10 + 5, 0);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert!(tree.check_invariants());
+    tree.shrink_to_fit();
+    assert_eq!(tree.pooled_nodes(), 0);
+}
+
+#[test]
+fn test_reserve_stays_within_a_small_factor_of_the_nodes_used() {
+    for capacity in [4, 16, 64] {
+        let mut tree = BPlusTreeMap::new(capacity).unwrap();
+        tree.clear();
+        tree.set_max_pooled_nodes(0);
+        tree.reserve(10_000);
+        let reserved = tree.pooled_nodes();
+        for k in (0..10_000).map(|i| (i * 7919) % 10_000) {
+            tree.insert(k, k);
+        }
+        let used = reserved - tree.pooled_nodes();
+        assert!(
+            reserved <= 3 * used,
+            "cap={} reserved={} used={}",
+            capacity,
+            reserved,
+            used
+        );
+    }
+}
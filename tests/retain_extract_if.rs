@@ -0,0 +1,135 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+use test_utils::*;
+
+#[test]
+fn test_retain_matches_btreemap() {
+    for &cap in &[4_usize, 5, 8] {
+        for modulus in [1, 2, 3, 7, 50, 1000] {
+            let (mut tree, mut map) =
+                build_with_reference(cap, scattered_keys(500).map(|k| (k, k * 10)));
+            tree.retain(|k, v| {
+                *v += 1;
+                k % modulus == 0
+            });
+            map.retain(|k, v| {
+                *v += 1;
+                k % modulus == 0
+            });
+            assert_matches_btreemap(&tree, &map, &format!("cap={} modulus={}", cap, modulus));
+        }
+    }
+}
+
+#[test]
+fn test_retain_nothing_empties_the_tree() {
+    for &cap in &[4_usize, 5, 8] {
+        let (mut tree, _) = build_with_reference(cap, scattered_keys(300).map(|k| (k, k * 10)));
+        tree.retain(|_, _| false);
+        assert!(tree.is_empty());
+        assert!(tree.check_invariants());
+        tree.insert(3, 30);
+        assert_eq!(tree.get(&3), Some(&30));
+        assert!(tree.check_invariants());
+    }
+}
+
+#[test]
+fn test_extract_if_over_ranges() {
+    let ranges: Vec<(Bound<i32>, Bound<i32>)> = vec![
+        (Bound::Unbounded, Bound::Unbounded),
+        (Bound::Included(100), Bound::Excluded(250)),
+        (Bound::Excluded(0), Bound::Included(17)),
+        (Bound::Included(480), Bound::Unbounded),
+        (Bound::Unbounded, Bound::Excluded(3)),
+        (Bound::Included(42), Bound::Included(42)),
+        (Bound::Included(300), Bound::Excluded(200)),
+        (Bound::Included(600), Bound::Unbounded),
+    ];
+    for &cap in &[4_usize, 5, 8] {
+        for &range in &ranges {
+            for modulus in [1, 3] {
+                let (mut tree, mut map) =
+                    build_with_reference(cap, scattered_keys(500).map(|k| (k, k * 10)));
+                let got: Vec<(i32, i32)> =
+                    tree.extract_if(range, |k, _| k % modulus == 0).collect();
+                let expected: Vec<(i32, i32)> = map
+                    .iter()
+                    .filter(|(k, _)| range.contains(*k) && *k % modulus == 0)
+                    .map(|(k, v)| (*k, *v))
+                    .collect();
+                for (k, _) in &expected {
+                    map.remove(k);
+                }
+                assert_eq!(got, expected, "cap={} range={:?}", cap, range);
+                assert_matches_btreemap(&tree, &map, &format!("cap={} range={:?}", cap, range));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_dropping_partially_consumed_extract_if_keeps_the_rest() {
+    for &cap in &[4_usize, 5] {
+        for taken in [0_usize, 1, 10, 99] {
+            let (mut tree, mut map) =
+                build_with_reference(cap, scattered_keys(400).map(|k| (k, k * 10)));
+            let got: Vec<(i32, i32)> = tree.extract_if(50..350, |_, _| true).take(taken).collect();
+            for (k, _) in &got {
+                map.remove(k);
+            }
+            assert_eq!(got.len(), taken);
+            assert_matches_btreemap(&tree, &map, &format!("cap={} taken={}", cap, taken));
+        }
+    }
+}
+
+#[test]
+fn test_extract_if_drops_each_value_once() {
+    let marker = Rc::new(());
+    let mut tree: BPlusTreeMap<i32, Rc<()>> = BPlusTreeMap::new(4).unwrap();
+    for i in 0..200 {
+        tree.insert(i, Rc::clone(&marker));
+    }
+    let extracted: Vec<_> = tree.extract_if(.., |k, _| k % 4 != 0).collect();
+    assert_eq!(Rc::strong_count(&marker), 201);
+    drop(extracted);
+    assert_eq!(Rc::strong_count(&marker), 51);
+    assert_eq!(tree.len(), 50);
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
+
+#[test]
+fn test_extract_if_on_empty_trees() {
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    assert_eq!(tree.extract_if(.., |_, _| true).next(), None);
+    tree.retain(|_, _| false);
+    let mut tree: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    assert_eq!(tree.extract_if(.., |_, _| true).next(), None);
+    assert!(tree.check_invariants());
+}
+
+#[test]
+fn test_retain_with_scattered_predicates() {
+    let mut seed = 0x2545_f491_u32;
+    for &cap in &[4_usize, 5, 6, 9] {
+        for n in [10, 64, 257, 1000] {
+            for keep_percent in [5_u32, 30, 70, 95] {
+                let (mut tree, mut map) =
+                    build_with_reference(cap, scattered_keys(n).map(|k| (k, k * 10)));
+                let mut keep = Vec::new();
+                for _ in 0..n {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    keep.push((seed >> 16) % 100 < keep_percent);
+                }
+                tree.retain(|k, _| keep[*k as usize]);
+                map.retain(|k, _| keep[*k as usize]);
+                let ctx = format!("cap={} n={} keep={}", cap, n, keep_percent);
+                assert_matches_btreemap(&tree, &map, &ctx);
+            }
+        }
+    }
+}
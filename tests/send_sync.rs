@@ -0,0 +1,106 @@
+use bplustree::{
+    BPlusTreeMap, BPlusTreeSet, Cursor, CursorMut, Drain, Entry, ExtractIf, IntoIter, IntoKeys,
+    IntoValues, Items, ItemsMut, Keys, OccupiedEntry, SetIter, VacantEntry, Values, ValuesMut,
+};
+use std::ops::RangeFull;
+use std::sync::Mutex;
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn test_public_types_are_send_and_sync() {
+    type K = String;
+    type V = Vec<u8>;
+    assert_send::<BPlusTreeMap<K, V>>();
+    assert_sync::<BPlusTreeMap<K, V>>();
+    assert_send::<Items<'static, K, V>>();
+    assert_sync::<Items<'static, K, V>>();
+    assert_send::<Keys<'static, K, V>>();
+    assert_sync::<Keys<'static, K, V>>();
+    assert_send::<Values<'static, K, V>>();
+    assert_sync::<Values<'static, K, V>>();
+    assert_send::<ItemsMut<'static, K, V>>();
+    assert_sync::<ItemsMut<'static, K, V>>();
+    assert_send::<ValuesMut<'static, K, V>>();
+    assert_sync::<ValuesMut<'static, K, V>>();
+    assert_send::<IntoIter<K, V>>();
+    assert_sync::<IntoIter<K, V>>();
+    assert_send::<IntoKeys<K, V>>();
+    assert_sync::<IntoKeys<K, V>>();
+    assert_send::<IntoValues<K, V>>();
+    assert_sync::<IntoValues<K, V>>();
+    assert_send::<Cursor<'static, K, V>>();
+    assert_sync::<Cursor<'static, K, V>>();
+    assert_send::<CursorMut<'static, K, V>>();
+    assert_sync::<CursorMut<'static, K, V>>();
+    assert_send::<Entry<'static, K, V>>();
+    assert_sync::<Entry<'static, K, V>>();
+    assert_send::<VacantEntry<'static, K, V>>();
+    assert_send::<OccupiedEntry<'static, K, V>>();
+    assert_send::<BPlusTreeSet<K>>();
+    assert_sync::<BPlusTreeSet<K>>();
+    assert_send::<SetIter<'static, K>>();
+    assert_sync::<SetIter<'static, K>>();
+    type Pred = fn(&K, &mut V) -> bool;
+    assert_send::<ExtractIf<'static, K, V, RangeFull, Pred>>();
+    assert_sync::<ExtractIf<'static, K, V, RangeFull, Pred>>();
+    assert_send::<Drain<'static, K, V>>();
+    assert_sync::<Drain<'static, K, V>>();
+}
+
+#[test]
+fn test_map_moves_between_threads() {
+    let mut tree = BPlusTreeMap::new(4).unwrap();
+    for i in 0..1000 {
+        tree.insert(i.to_string(), i);
+    }
+    let tree = std::thread::spawn(move || {
+        tree.retain(|_, v| *v % 2 == 0);
+        tree
+    })
+    .join()
+    .unwrap();
+    assert_eq!(tree.len(), 500);
+    assert!(tree.check_invariants());
+
+    let drained: Vec<_> = std::thread::spawn(move || tree.into_iter().collect())
+        .join()
+        .unwrap();
+    assert_eq!(drained.len(), 500);
+}
+
+#[test]
+fn test_shared_readers_and_mutex_writers() {
+    let tree: BPlusTreeMap<u32, u32> = (0..2000).map(|i| (i, i)).collect();
+    let sums: Vec<u64> = std::thread::scope(|s| {
+        let readers: Vec<_> = (0..4)
+            .map(|t| {
+                let tree = &tree;
+                s.spawn(move || {
+                    tree.range(t * 500..(t + 1) * 500)
+                        .map(|(_, &v)| u64::from(v))
+                        .sum()
+                })
+            })
+            .collect();
+        readers.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(sums.iter().sum::<u64>(), (0..2000u64).sum());
+
+    let shared = Mutex::new(tree);
+    std::thread::scope(|s| {
+        for t in 0..4u32 {
+            let shared = &shared;
+            s.spawn(move || {
+                for k in (t..2000).step_by(4).filter(|k| k % 3 == 0) {
+                    shared.lock().unwrap().remove(&k);
+                }
+            });
+        }
+    });
+    let tree = shared.into_inner().unwrap();
+    assert!(tree.check_invariants());
+    assert!(tree.keys().all(|k| k % 3 != 0));
+    assert_eq!(tree.len(), 2000 - 667);
+}
@@ -0,0 +1,162 @@
+use bplustree::{BPlusTreeMap, BPlusTreeSet, LeafLayout};
+use std::collections::BTreeSet;
+
+fn pseudo_random(n: usize, modulus: u32, seed: u32) -> Vec<u32> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) % modulus
+        })
+        .collect()
+}
+
+fn both(values: &[u32]) -> (BPlusTreeSet<u32>, BTreeSet<u32>) {
+    let mut set = BPlusTreeSet::new(4).unwrap();
+    for &v in values {
+        set.insert(v);
+    }
+    (set, values.iter().copied().collect())
+}
+
+#[test]
+fn test_leaf_capacity_depends_on_key_size_alone() {
+    for bytes in [128, 512, 4096] {
+        let unit = LeafLayout::compute::<u64, ()>(bytes, true);
+        let header = unit.keys_off;
+        assert_eq!(unit.cap as usize, (bytes - header) / 8, "bytes={}", bytes);
+        assert!(unit.cap >= 2 * LeafLayout::compute::<u64, u64>(bytes, true).cap);
+        let bytes_key = LeafLayout::compute::<u8, ()>(bytes, true);
+        assert_eq!(bytes_key.cap as usize, bytes - bytes_key.keys_off);
+    }
+    let exact = LeafLayout::compute_for_cap::<u32, ()>(10, true);
+    assert_eq!(exact.bytes, exact.keys_off + 40);
+
+    let set: BPlusTreeSet<u64> = BPlusTreeSet::default();
+    let map: BPlusTreeMap<u64, u64> = BPlusTreeMap::default();
+    assert!(set.leaf_layout().cap > map.leaf_layout().cap);
+    assert_eq!(set.branch_layout(), map.branch_layout());
+}
+
+#[test]
+fn test_set_operations_match_btreeset() {
+    let (mut set, mut reference) = both(&pseudo_random(3000, 1000, 1));
+    assert!(set.check_invariants());
+    assert_eq!(set.len(), reference.len());
+    assert!(set.iter().eq(reference.iter()));
+    assert!(set.iter().rev().eq(reference.iter().rev()));
+
+    for v in pseudo_random(2000, 1200, 2) {
+        assert_eq!(set.insert(v), reference.insert(v), "insert {}", v);
+        assert_eq!(set.contains(&v), reference.contains(&v));
+    }
+    for v in pseudo_random(2000, 1200, 3) {
+        assert_eq!(set.remove(&v), reference.remove(&v), "remove {}", v);
+    }
+    assert!(set.check_invariants());
+    assert!(set.iter().eq(reference.iter()));
+
+    assert_eq!(set.first(), reference.first());
+    assert_eq!(set.last(), reference.last());
+    assert_eq!(set.pop_first(), reference.pop_first());
+    assert_eq!(set.pop_last(), reference.pop_last());
+    for v in [0, 1, 500, 1199, 5000] {
+        assert_eq!(set.get(&v), reference.get(&v));
+        assert_eq!(set.take(&v), reference.take(&v));
+    }
+    assert!(set.range(100..300).eq(reference.range(100..300)));
+    assert!(set.range(..=50).rev().eq(reference.range(..=50).rev()));
+    assert!(set.range(900..).eq(reference.range(900..)));
+
+    set.retain(|v| v % 3 != 0);
+    reference.retain(|v| v % 3 != 0);
+    let right = set.split_off(&600);
+    let reference_right = reference.split_off(&600);
+    assert!(right.iter().eq(reference_right.iter()));
+    assert!(set.iter().eq(reference.iter()));
+    assert!(set.check_invariants() && right.check_invariants());
+
+    assert!(set.into_iter().eq(reference.into_iter()));
+}
+
+#[test]
+fn test_set_algebra_matches_btreeset() {
+    // Sizes cover both the merge walk and the lookup strategy (ratio above 16).
+    let shapes = [
+        (0, 0),
+        (0, 50),
+        (10, 10),
+        (200, 300),
+        (20, 2000),
+        (2000, 20),
+        (1, 5000),
+    ];
+    for (i, &(na, nb)) in shapes.iter().enumerate() {
+        let (a, ra) = both(&pseudo_random(na, 3000, 10 + i as u32));
+        let (b, rb) = both(&pseudo_random(nb, 3000, 50 + i as u32));
+        let ctx = format!("sizes {}x{}", na, nb);
+        assert!(a.union(&b).eq(ra.union(&rb)), "union {}", ctx);
+        assert!(
+            a.intersection(&b).eq(ra.intersection(&rb)),
+            "intersection {}",
+            ctx
+        );
+        assert!(
+            b.intersection(&a).eq(rb.intersection(&ra)),
+            "intersection {}",
+            ctx
+        );
+        assert!(
+            a.difference(&b).eq(ra.difference(&rb)),
+            "difference {}",
+            ctx
+        );
+        assert!(
+            b.difference(&a).eq(rb.difference(&ra)),
+            "difference {}",
+            ctx
+        );
+        assert!(
+            a.symmetric_difference(&b).eq(ra.symmetric_difference(&rb)),
+            "symmetric_difference {}",
+            ctx
+        );
+        assert_eq!(a.is_subset(&b), ra.is_subset(&rb), "{}", ctx);
+        assert_eq!(a.is_superset(&b), ra.is_superset(&rb), "{}", ctx);
+        assert_eq!(a.is_disjoint(&b), ra.is_disjoint(&rb), "{}", ctx);
+
+        assert!((&a | &b).iter().eq((&ra | &rb).iter()));
+        assert!((&a & &b).iter().eq((&ra & &rb).iter()));
+        assert!((&a - &b).iter().eq((&ra - &rb).iter()));
+        assert!((&a ^ &b).iter().eq((&ra ^ &rb).iter()));
+    }
+
+    let small: BPlusTreeSet<u32> = (0..10).map(|v| v * 100).collect();
+    let large: BPlusTreeSet<u32> = (0..2000).collect();
+    assert!(small.is_subset(&large) && large.is_superset(&small));
+    assert!(!large.is_subset(&small));
+    let odd: BPlusTreeSet<u32> = (0..1000).map(|v| v * 2 + 1).collect();
+    assert!(small.is_disjoint(&odd) && odd.is_disjoint(&small));
+    assert!(!odd.is_disjoint(&large));
+}
+
+#[test]
+fn test_set_traits() {
+    let set = BPlusTreeSet::from([3, 1, 2, 3]);
+    assert_eq!(format!("{:?}", set), "{1, 2, 3}");
+    assert_eq!(set.len(), 3);
+    let copy = set.clone();
+    assert_eq!(copy, set);
+    assert!(BPlusTreeSet::from([1, 2]) < set);
+    let mut extended = BPlusTreeSet::default();
+    extended.extend(&[2, 1]);
+    extended.extend(vec![3, 3]);
+    assert_eq!(extended, set);
+    assert_eq!((&set).into_iter().sum::<i32>(), 6);
+
+    let mut strings: BPlusTreeSet<String> = ["b", "a"].iter().map(|s| s.to_string()).collect();
+    assert!(strings.contains("a"));
+    assert!(!strings.insert("a".to_string()));
+    assert!(strings.remove("b"));
+    assert_eq!(strings.iter().collect::<Vec<_>>(), ["a"]);
+}
@@ -0,0 +1,161 @@
+mod test_utils;
+use bplustree::{BPlusTreeError, BPlusTreeMap, NodeRef, SlabArena};
+use std::collections::BTreeMap;
+use test_utils::*;
+
+#[test]
+fn test_arena_maps_match_btreemap() {
+    let arena = SlabArena::new();
+    for counted in [false, true] {
+        let mut tree = if counted {
+            BPlusTreeMap::with_budgets_counted_in(128, 128, &arena)
+        } else {
+            BPlusTreeMap::with_budgets_in(128, 128, &arena)
+        };
+        assert!(tree.leaf_layout().links.is_compact());
+        let mut map = BTreeMap::new();
+        for i in 0..5000 {
+            let k = (i * 7919) % 2003;
+            if i % 3 == 2 {
+                assert_eq!(tree.remove(&k), map.remove(&k));
+            } else {
+                assert_eq!(tree.insert(k, i), map.insert(k, i));
+            }
+        }
+        assert_matches_btreemap(&tree, &map, &format!("counted={}", counted));
+        tree.batch_insert((0..800).map(|k| (k * 3, k)).collect())
+            .unwrap();
+        map.extend((0..800).map(|k| (k * 3, k)));
+        tree.remove_range(200..700);
+        map.retain(|k, _| !(200..700).contains(k));
+        tree.retain(|k, _| k % 5 != 0);
+        map.retain(|k, _| k % 5 != 0);
+        assert_matches_btreemap(&tree, &map, &format!("counted={} bulk", counted));
+        assert!(tree.range(1000..1500).eq(map.range(1000..1500)));
+        if counted {
+            assert_eq!(tree.select(300), map.iter().nth(300));
+            assert_eq!(tree.rank(&1200), map.range(..1200).count());
+        }
+        assert!(arena.live_nodes() > tree.leaf_count());
+        drop(tree);
+        assert_eq!(arena.live_nodes(), 0, "counted={}", counted);
+    }
+}
+
+#[test]
+fn test_maps_sharing_an_arena_move_nodes_between_them() {
+    let arena = SlabArena::new();
+    let mut tree = BPlusTreeMap::new_in(4, &arena).unwrap();
+    tree.extend((0..1000).map(|k| (k, k)));
+    let copy = tree.try_clone().unwrap();
+    let mut right = tree.split_off(&400);
+    let mut map: BTreeMap<i32, i32> = (0..400).map(|k| (k, k)).collect();
+    assert_matches_btreemap(&tree, &map, "split_off");
+    right.insert(-1, -1);
+    tree.append(&mut right);
+    map.extend((400..1000).map(|k| (k, k)));
+    map.insert(-1, -1);
+    assert_matches_btreemap(&tree, &map, "append");
+    assert!(right.is_empty());
+    map.remove(&-1);
+    assert_matches_btreemap(&copy, &map, "try_clone");
+
+    let mut cursor = tree.lower_bound_mut(std::ops::Bound::Included(&500));
+    assert_eq!(cursor.remove_next(), Some((500, 500)));
+    assert!(tree.drain(..100).map(|(k, _)| k).eq(-1..100));
+    drop((copy, right));
+    let mut iter = tree.into_iter();
+    assert_eq!(iter.next(), Some((100, 100)));
+    assert_eq!(iter.next_back(), Some((999, 999)));
+    drop(iter);
+    assert_eq!(arena.live_nodes(), 0);
+    assert!(arena.slab_bytes() > 0);
+}
+
+#[test]
+fn test_a_map_can_own_its_arena() {
+    let mut tree = BPlusTreeMap::with_budgets_in(256, 256, SlabArena::new());
+    tree.extend((0..3000).map(|k| (k, k * 2)));
+    let live = tree.allocator().live_nodes();
+    assert!(live > tree.leaf_count());
+    for k in 0..2900 {
+        tree.remove(&k);
+    }
+    tree.shrink_to_fit();
+    assert!(tree.allocator().live_nodes() < live / 10);
+    assert!(tree.into_values().eq((2900..3000).map(|k| k * 2)));
+}
+
+#[test]
+fn test_id_links_widen_branches() {
+    let arena = SlabArena::new();
+    let pointers: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_budgets(256, 256);
+    let ids: BPlusTreeMap<i32, i32, _> = BPlusTreeMap::with_budgets_in(256, 256, &arena);
+    assert_eq!(ids.branch_layout().bytes, pointers.branch_layout().bytes);
+    assert!(ids.branch_layout().cap > pointers.branch_layout().cap);
+    assert!(ids.leaf_layout().cap >= pointers.leaf_layout().cap);
+}
+
+#[test]
+fn test_node_refs_walk_the_tree() {
+    let arena = SlabArena::new();
+    let mut tree = BPlusTreeMap::new_in(4, &arena).unwrap();
+    let leaf = tree.root_ref().unwrap().unwrap();
+    assert!(leaf.is_leaf());
+    assert_eq!(tree.next_leaf_ref(leaf), Ok(None));
+    tree.extend((0..500).map(|k| (k, k)));
+
+    // Down the left edge to the first leaf, counting branches on the way.
+    let mut node = tree.root_ref().unwrap().unwrap();
+    let mut branches = 0;
+    while !node.is_leaf() {
+        let children = tree.child_refs(node).unwrap();
+        branches += children.iter().filter(|c| !c.is_leaf()).count();
+        node = children[0];
+    }
+    let mut leaves = vec![node];
+    while let Some(next) = tree.next_leaf_ref(*leaves.last().unwrap()).unwrap() {
+        leaves.push(next);
+    }
+    assert_eq!(leaves.len(), tree.leaf_count());
+    assert!(branches > 0);
+
+    // Refs to nodes of another layout, or to the wrong kind, are rejected.
+    let mut other = BPlusTreeMap::new_in(8, &arena).unwrap();
+    other.extend((0..500).map(|k| (k, k)));
+    let foreign = other.root_ref().unwrap().unwrap();
+    assert!(matches!(
+        tree.child_refs(foreign),
+        Err(BPlusTreeError::NodeError(_))
+    ));
+    assert!(matches!(
+        tree.child_refs(leaves[0]),
+        Err(BPlusTreeError::NodeError(_))
+    ));
+    let root = tree.root_ref().unwrap().unwrap();
+    assert!(matches!(
+        tree.next_leaf_ref(root),
+        Err(BPlusTreeError::NodeError(_))
+    ));
+    assert!(matches!(
+        tree.next_leaf_ref(NodeRef::Leaf(u32::MAX - 1, Default::default())),
+        Err(BPlusTreeError::NodeError(_))
+    ));
+
+    // Once freed, or kept in the pool, the nodes are no longer found.
+    tree.set_max_pooled_nodes(8);
+    tree.clear();
+    assert_eq!(tree.pooled_nodes(), 8);
+    for &leaf in &leaves {
+        assert!(matches!(
+            tree.next_leaf_ref(leaf),
+            Err(BPlusTreeError::NodeError(_))
+        ));
+    }
+
+    let plain: BPlusTreeMap<i32, i32> = (0..10).map(|k| (k, k)).collect();
+    assert!(matches!(
+        plain.root_ref(),
+        Err(BPlusTreeError::ArenaError(_))
+    ));
+}
@@ -94,7 +94,7 @@ fn demonstrate_linked_list_merge_corruption() {
 
     // Check if iteration is consistent
     let expected: Vec<_> = (0..20)
-        .filter(|&i| i < 8 || i >= 12)
+        .filter(|&i| !(8..12).contains(&i))
         .map(|i| i * 10)
         .collect();
     println!("Expected: {:?}", expected);
@@ -0,0 +1,116 @@
+mod test_utils;
+use bplustree::BPlusTreeMap;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use test_utils::*;
+
+#[test]
+fn test_split_off_matches_btreemap_at_every_cut() {
+    for &cap in &[4_usize, 5, 8] {
+        for n in [0, 1, 7, 60, 301] {
+            // Keys are even, so odd cuts fall between two keys (often between leaves).
+            for at in (-2..2 * n + 2).step_by(if n > 100 { 13 } else { 1 }) {
+                let (mut tree, mut map) =
+                    build_with_reference(cap, scattered_keys(n).map(|k| (k * 2, k * 20)));
+                let right = tree.split_off(&at);
+                let expected = map.split_off(&at);
+                let ctx = format!("cap={} n={} at={}", cap, n, at);
+                assert_matches_btreemap(&tree, &map, &ctx);
+                assert_matches_btreemap(&right, &expected, &ctx);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_split_off_between_keys_and_keep_using_both_halves() {
+    let (mut tree, mut map) = build_with_reference(4, (0..400).map(|k| (k * 2, k * 20)));
+    let mut right = tree.split_off(&201);
+    let mut expected = map.split_off(&201);
+    for k in (0..800).step_by(7) {
+        assert_eq!(tree.insert(k, -k), map.insert(k, -k));
+        assert_eq!(right.remove(&(k + 1)), expected.remove(&(k + 1)));
+    }
+    assert_matches_btreemap(&tree, &map, "left");
+    assert_matches_btreemap(&right, &expected, "right");
+}
+
+#[test]
+fn test_append_disjoint_ranges_of_different_heights() {
+    for &cap in &[4_usize, 5, 8] {
+        for (left_n, right_n) in [(1, 1), (1, 500), (500, 1), (40, 40), (3, 90), (700, 12)] {
+            for self_is_left in [true, false] {
+                let (lo, hi) = (
+                    build_with_reference(cap, (0..left_n).map(|k| (k, k * 10))),
+                    build_with_reference(cap, (10_000..10_000 + right_n).map(|k| (k, k * 10))),
+                );
+                let ((mut a, mut ma), (mut b, mut mb)) =
+                    if self_is_left { (lo, hi) } else { (hi, lo) };
+                a.append(&mut b);
+                ma.append(&mut mb);
+                let ctx = format!(
+                    "cap={} left={} right={} self_is_left={}",
+                    cap, left_n, right_n, self_is_left
+                );
+                assert_matches_btreemap(&a, &ma, &ctx);
+                assert!(b.is_empty(), "{}", ctx);
+                assert!(b.check_invariants(), "{}", ctx);
+                b.insert(1, 1);
+                assert_eq!(b.len(), 1);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_append_overlapping_ranges_prefers_other_values() {
+    for &cap in &[4_usize, 5, 8] {
+        let (mut a, mut ma) = build_with_reference(cap, (0..300).step_by(2).map(|k| (k, k * 10)));
+        let mut b = BPlusTreeMap::new(cap).unwrap();
+        let mut mb = BTreeMap::new();
+        for k in (0..450).step_by(3) {
+            b.insert(k, -k);
+            mb.insert(k, -k);
+        }
+        a.append(&mut b);
+        ma.append(&mut mb);
+        assert_matches_btreemap(&a, &ma, &format!("cap={}", cap));
+        assert!(b.is_empty());
+    }
+}
+
+#[test]
+fn test_append_with_different_capacities_and_empty_sides() {
+    let (mut a, mut ma) = build_with_reference(4, (0..100).map(|k| (k, k * 10)));
+    let (mut b, mut mb) = build_with_reference(9, (100..250).map(|k| (k, k * 10)));
+    a.append(&mut b);
+    ma.append(&mut mb);
+    assert_matches_btreemap(&a, &ma, "different capacities");
+
+    let mut empty: BPlusTreeMap<i32, i32> = BPlusTreeMap::new(4).unwrap();
+    a.append(&mut empty);
+    assert_matches_btreemap(&a, &ma, "append empty");
+    let mut fresh: BPlusTreeMap<i32, i32> = BPlusTreeMap::with_cache_lines(2, 2);
+    fresh.append(&mut a);
+    assert_matches_btreemap(&fresh, &ma, "append into empty");
+    assert!(a.is_empty());
+}
+
+#[test]
+fn test_split_off_then_append_round_trips_without_drops() {
+    let marker = Rc::new(());
+    let mut tree: BPlusTreeMap<i32, Rc<()>> = BPlusTreeMap::new(5).unwrap();
+    for i in 0..500 {
+        tree.insert(i, Rc::clone(&marker));
+    }
+    for cut in [0, 1, 250, 499, 500] {
+        let mut right = tree.split_off(&cut);
+        assert_eq!(tree.len() + right.len(), 500);
+        assert_eq!(Rc::strong_count(&marker), 501);
+        tree.append(&mut right);
+        assert!(tree.check_invariants());
+        assert!(tree.keys().copied().eq(0..500));
+    }
+    drop(tree);
+    assert_eq!(Rc::strong_count(&marker), 1);
+}
@@ -0,0 +1,143 @@
+use bplustree::BPlusTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn pseudo_random_pairs(n: usize) -> Vec<(u32, u32)> {
+    let mut seed = 0x1234_5678_u32;
+    (0..n)
+        .map(|i| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((seed >> 16) % 700, i as u32)
+        })
+        .collect()
+}
+
+#[test]
+fn test_default_and_debug() {
+    let mut tree: BPlusTreeMap<i32, &str> = BPlusTreeMap::default();
+    assert!(tree.is_empty());
+    assert_eq!(format!("{:?}", tree), "{}");
+    tree.insert(2, "b");
+    tree.insert(1, "a");
+    assert_eq!(format!("{:?}", tree), r#"{1: "a", 2: "b"}"#);
+    assert_eq!(
+        tree.leaf_layout().bytes,
+        BPlusTreeMap::<i32, &str>::DEFAULT_LEAF_BYTES
+    );
+
+    // Types too large for the default budgets still get usable nodes.
+    let mut big: BPlusTreeMap<[u64; 64], [u64; 64]> = BPlusTreeMap::default();
+    assert!(big.leaf_layout().cap >= 4 && big.branch_layout().cap >= 4);
+    for i in 0..100 {
+        big.insert([i; 64], [i; 64]);
+    }
+    assert_eq!(big.len(), 100);
+    assert!(big.check_invariants());
+
+    let mut unit: BPlusTreeMap<(), ()> = BPlusTreeMap::default();
+    unit.insert((), ());
+    assert_eq!(unit.len(), 1);
+}
+
+#[test]
+fn test_from_iter_matches_btreemap_with_last_value_winning() {
+    for n in [0, 1, 5, 100, 5000] {
+        let pairs = pseudo_random_pairs(n);
+        let tree: BPlusTreeMap<u32, u32> = pairs.iter().copied().collect();
+        let map: BTreeMap<u32, u32> = pairs.iter().copied().collect();
+        assert!(tree.check_invariants(), "n={}", n);
+        assert_eq!(tree.len(), map.len());
+        assert!(tree.items().eq(map.iter()), "n={}", n);
+    }
+}
+
+#[test]
+fn test_extend_by_value_and_by_reference() {
+    let pairs = pseudo_random_pairs(2000);
+    let (first, second) = pairs.split_at(1000);
+    let mut tree: BPlusTreeMap<u32, u32> = first.iter().copied().collect();
+    let mut map: BTreeMap<u32, u32> = first.iter().copied().collect();
+    tree.extend(second);
+    map.extend(second.iter().copied());
+    assert!(tree.items().eq(map.iter()));
+
+    let other: BTreeMap<u32, u32> = (1000..1100).map(|k| (k, k)).collect();
+    tree.extend(other.iter());
+    map.extend(other.iter());
+    tree.extend(vec![(5000, 1), (5000, 2)]);
+    map.extend(vec![(5000, 1), (5000, 2)]);
+    assert!(tree.check_invariants());
+    assert!(tree.items().eq(map.iter()));
+}
+
+#[test]
+fn test_conversions() {
+    let tree = BPlusTreeMap::from([(3, "c"), (1, "a"), (2, "b"), (1, "z")]);
+    assert_eq!(format!("{:?}", tree), r#"{1: "z", 2: "b", 3: "c"}"#);
+    let empty: BPlusTreeMap<i32, i32> = BPlusTreeMap::from([]);
+    assert!(empty.is_empty());
+
+    let map: BTreeMap<u32, u32> = pseudo_random_pairs(3000).into_iter().collect();
+    let tree = BPlusTreeMap::from(map.clone());
+    assert!(tree.check_invariants());
+    assert!(tree.items().eq(map.iter()));
+}
+
+#[test]
+fn test_index() {
+    let tree = BPlusTreeMap::from([(String::from("a"), 1), (String::from("b"), 2)]);
+    assert_eq!(tree["a"], 1);
+    assert_eq!(tree[&String::from("b")], 2);
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key")]
+fn test_index_missing_key_panics() {
+    let tree = BPlusTreeMap::from([(1, 1)]);
+    let _ = tree[&2];
+}
+
+#[test]
+fn test_comparisons_and_hash_follow_btreemap() {
+    let cases: Vec<Vec<(u32, u32)>> = vec![
+        vec![],
+        vec![(1, 1)],
+        vec![(1, 2)],
+        vec![(1, 1), (2, 2)],
+        vec![(2, 2)],
+        pseudo_random_pairs(300),
+        pseudo_random_pairs(301),
+    ];
+    for a in &cases {
+        for b in &cases {
+            let (ta, tb): (BPlusTreeMap<u32, u32>, BPlusTreeMap<u32, u32>) =
+                (a.iter().copied().collect(), b.iter().copied().collect());
+            let (ma, mb): (BTreeMap<u32, u32>, BTreeMap<u32, u32>) =
+                (a.iter().copied().collect(), b.iter().copied().collect());
+            assert_eq!(ta == tb, ma == mb);
+            assert_eq!(ta.partial_cmp(&tb), ma.partial_cmp(&mb));
+            assert_eq!(ta.cmp(&tb), ma.cmp(&mb));
+            if ta == tb {
+                assert_eq!(hash_of(&ta), hash_of(&tb));
+            }
+        }
+    }
+
+    // Equal contents compare and hash equal regardless of node layout.
+    let mut small = BPlusTreeMap::new(4).unwrap();
+    small.extend(pseudo_random_pairs(500));
+    let large: BPlusTreeMap<u32, u32> = pseudo_random_pairs(500).into_iter().collect();
+    assert_eq!(small, large);
+    assert_eq!(hash_of(&small), hash_of(&large));
+
+    let mut by_map: HashMap<BPlusTreeMap<u32, u32>, &str> = HashMap::new();
+    by_map.insert(large, "large");
+    assert_eq!(by_map.get(&small), Some(&"large"));
+}
@@ -2,7 +2,9 @@
 
 /// Comprehensive test utilities to eliminate massive test duplication
 /// This module provides reusable patterns for adversarial testing and common operations
-use bplustree::BPlusTreeMap;
+use bplustree::{BPlusTreeMap, NodeAllocator};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
 
 // ============================================================================
 // TREE CREATION UTILITIES - Replace 185 instances of BPlusTreeMap::new()
@@ -30,15 +32,14 @@ pub fn create_tree_6() -> BPlusTreeMap<i32, String> {
 
 /// Generic tree creation with custom capacity
 pub fn create_tree_capacity(capacity: usize) -> BPlusTreeMap<i32, String> {
-    BPlusTreeMap::new(capacity).expect(&format!("Failed to create tree with capacity {}", capacity))
+    BPlusTreeMap::new(capacity)
+        .unwrap_or_else(|_| panic!("Failed to create tree with capacity {}", capacity))
 }
 
 /// Generic integer tree creation with custom capacity
 pub fn create_tree_capacity_int(capacity: usize) -> BPlusTreeMap<i32, i32> {
-    BPlusTreeMap::new(capacity).expect(&format!(
-        "Failed to create integer tree with capacity {}",
-        capacity
-    ))
+    BPlusTreeMap::new(capacity)
+        .unwrap_or_else(|_| panic!("Failed to create integer tree with capacity {}", capacity))
 }
 
 // ============================================================================
@@ -192,6 +193,50 @@ pub fn assert_full_validation_int(tree: &BPlusTreeMap<i32, i32>, context: &str)
     verify_ordering_int(tree);
 }
 
+// ============================================================================
+// DIFFERENTIAL TESTING UTILITIES - Compare against std's BTreeMap
+// ============================================================================
+
+/// Tree with the given capacity plus a `BTreeMap` reference, both filled by
+/// inserting `pairs` in order
+pub fn build_with_reference<K: Ord + Clone, V: Clone>(
+    capacity: usize,
+    pairs: impl IntoIterator<Item = (K, V)>,
+) -> (BPlusTreeMap<K, V>, BTreeMap<K, V>) {
+    let mut tree = BPlusTreeMap::new(capacity)
+        .unwrap_or_else(|_| panic!("Failed to create tree with capacity {}", capacity));
+    let mut map = BTreeMap::new();
+    for (k, v) in pairs {
+        tree.insert(k.clone(), v.clone());
+        map.insert(k, v);
+    }
+    (tree, map)
+}
+
+/// Keys `0..n` in the order `(i * 37) % n`, so inserts split leaves on both sides
+pub fn scattered_keys(n: i32) -> impl Iterator<Item = i32> {
+    (0..n).map(move |i| (i * 37) % n)
+}
+
+/// Tree invariants hold and the tree holds exactly the pairs of `map`, walked
+/// from both ends
+pub fn assert_matches_btreemap<K, V, A>(
+    tree: &BPlusTreeMap<K, V, A>,
+    map: &BTreeMap<K, V>,
+    context: &str,
+) where
+    K: Ord + Debug,
+    V: PartialEq + Debug,
+    A: NodeAllocator,
+{
+    if let Err(e) = tree.check_invariants_detailed() {
+        panic!("Invariant violation in {}: {}", context, e);
+    }
+    assert_eq!(tree.len(), map.len(), "{}", context);
+    assert!(tree.items().eq(map.iter()), "{}", context);
+    assert!(tree.items().rev().eq(map.iter().rev()), "{}", context);
+}
+
 // ============================================================================
 // ADVERSARIAL ATTACK PATTERNS - Common deletion patterns
 // ============================================================================
@@ -345,15 +390,18 @@ pub fn create_branch_test_tree(capacity: usize) -> BPlusTreeMap<i32, String> {
     }
 
     // Delete strategically to make siblings exactly at minimum
-    for key in vec![18, 28, 38, 48] {
+    for key in [18, 28, 38, 48] {
         tree.remove(&key);
     }
 
     tree
 }
 
+/// A scripted sequence of (is_insert, key) operations
+pub type OpScript = Vec<(bool, i32)>;
+
 /// Standard setup for concurrent access simulation
-pub fn setup_concurrent_simulation() -> (Vec<(bool, i32)>, Vec<(bool, i32)>) {
+pub fn setup_concurrent_simulation() -> (OpScript, OpScript) {
     let thread1_ops = vec![
         (true, 1),
         (true, 3),